@@ -0,0 +1,211 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                                 CLI                                   ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::error::Error;
+use std::fs;
+use std::thread;
+
+use crate::map_builders::autotile;
+use crate::map_builders::benchmark::{self, BenchmarkConfig, BenchmarkReport};
+use crate::map_builders::constraints::Search;
+use crate::map_builders::prefabs::PrefabStamper;
+use crate::map_builders::recipe::Recipe;
+use crate::map_builders::registry::{self, BUILT_IN};
+
+
+const USAGE: &str = "\
+usage: procgengo                        open the TUI
+       procgengo --recipe <path>        open the TUI on a recipe's map
+       procgengo generate --recipe <path> [--seed <n>] [--autotile] [constraints]
+       procgengo bench <builder|all> [options]
+       procgengo bench --recipe <path> [options]
+
+generate builds a recipe's map and writes it to the recipe's export targets,
+or prints it if it has none, with walls in box-drawing lines for --autotile.
+Export targets can use the autotiled format for the same. Recipes are TOML, or RON if they end in .ron.
+
+generate constraints, added to any in the recipe's [constraints]. Maps are
+made from the seed on until one meets them all or the attempts run out:
+  --floor <min-max>          percent of the tiles that are floor, like 40-60
+  --single-region            all the floor joined up
+  --min-rooms <n>            at least n rooms
+  --min-exit-distance <n>    at least n steps from the start to the exit
+  --attempts <n>             maps to make before giving up (default 100)
+
+bench options:
+  --runs <n>          maps to build, one seed each (default 100)
+  --seed <n>          seed of the first run (default 0)
+  --iterations <n>    most iterations after each build (default one per
+                      tile of the map, at least 500), or exactly n in place
+                      of a recipe's iterations
+  --threads <n>       threads to spread the runs over (default 1)
+  --parallel          use a thread per core
+  --format <f>        json, csv for the summary, or runs-csv for every run
+  --output <path>     write to a file instead of stdout";
+
+
+/// Runs the command named by `args`, leaving out the program name.
+pub fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    match args[0].as_str() {
+        "generate" => generate(&args[1..]),
+        "bench" => bench(&args[1..]),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        },
+        other => Err(format!("unknown command `{other}`\n\n{USAGE}").into()),
+    }
+}
+
+
+fn generate(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut recipe = None;
+    let mut seed = None;
+    let mut autotiled = false;
+    let mut floor_percent = None;
+    let mut single_region = false;
+    let mut min_rooms = None;
+    let mut min_exit_distance = None;
+    let mut max_attempts = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--recipe" => recipe = Some(Recipe::load(value()?)?),
+            "--seed" => seed = Some(value()?.parse()?),
+            "--autotile" => autotiled = true,
+            "--floor" => floor_percent = Some(percent_range(value()?)?),
+            "--single-region" => single_region = true,
+            "--min-rooms" => min_rooms = Some(value()?.parse()?),
+            "--min-exit-distance" => min_exit_distance = Some(value()?.parse()?),
+            "--attempts" => max_attempts = Some(value()?.parse()?),
+            other => return Err(format!("unexpected argument `{other}`\n\n{USAGE}").into()),
+        }
+    }
+
+    let mut recipe = recipe.ok_or("generate needs a --recipe")?;
+    if seed.is_some() {
+        recipe.seed = seed;
+    }
+    let constraints = &mut recipe.constraints;
+    constraints.floor_percent = floor_percent.or(constraints.floor_percent);
+    constraints.single_region |= single_region;
+    constraints.min_rooms = min_rooms.or(constraints.min_rooms);
+    constraints.min_exit_distance = min_exit_distance.or(constraints.min_exit_distance);
+    constraints.max_attempts = max_attempts.unwrap_or(constraints.max_attempts);
+
+    let mut stamper = load_stamper();
+    let generated = if recipe.constraints.is_empty() {
+        recipe.generate(&mut stamper)?
+    } else {
+        let mut search = Search::new(recipe.clone(), stamper);
+        let generated = search.run()?;
+        let plural = if search.attempts == 1 { "" } else { "s" };
+        eprintln!("met {} after {} attempt{plural}", recipe.constraints.summary(), search.attempts);
+        generated
+    };
+    eprintln!("built {} with seed {} in {} µs", recipe.algorithm, generated.seed, generated.duration.as_micros());
+    if recipe.export.is_empty() {
+        let map = generated.builder.get_map();
+        print!("{}", if autotiled { autotile::to_text(map) } else { map.to_text() });
+    }
+    recipe.write_exports(generated.builder.as_ref())?;
+    for target in &recipe.export {
+        eprintln!("wrote {}", target.path);
+    }
+    Ok(())
+}
+
+
+fn bench(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut config = BenchmarkConfig::new();
+    let mut iterations = None;
+    let mut builder = None;
+    let mut recipe = None;
+    let mut format = "json".to_string();
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--runs" => config.runs = value()?.parse()?,
+            "--seed" => config.first_seed = value()?.parse()?,
+            "--iterations" => iterations = Some(value()?.parse()?),
+            "--threads" => config.threads = value()?.parse()?,
+            "--parallel" => config.threads = thread::available_parallelism().map_or(1, |n| n.get()),
+            "--format" => format = value()?.clone(),
+            "--output" => output = Some(value()?.clone()),
+            "--recipe" => recipe = Some(Recipe::load(value()?)?),
+            id if builder.is_none() && !id.starts_with("--") => builder = Some(id.to_string()),
+            other => return Err(format!("unexpected argument `{other}`\n\n{USAGE}").into()),
+        }
+    }
+
+    let reports: Vec<BenchmarkReport> = match (recipe, builder) {
+        (Some(mut recipe), None) => {
+            recipe.iterations = iterations.or(recipe.iterations);
+            eprintln!("benchmarking {} from the recipe ({} runs)", recipe.algorithm, config.runs);
+            vec![benchmark::run_recipe(&recipe, &load_stamper(), &config)?]
+        },
+        (None, Some(builder)) => {
+            config.max_iterations = iterations.or(config.max_iterations);
+            let builders: Vec<_> = match builder.as_str() {
+                "all" => BUILT_IN.iter().collect(),
+                id => vec![BUILT_IN.iter().find(|info| info.id == id).ok_or_else(|| format!("no builder called `{id}`, try one of: {}", registry::ids().join(", ")))?],
+            };
+            builders
+                .into_iter()
+                .map(|info| {
+                    eprintln!("benchmarking {} ({} runs)", info.id, config.runs);
+                    benchmark::run(info.id, info.create, &config)
+                })
+                .collect()
+        },
+        (Some(_), Some(_)) => return Err("bench takes a builder or a --recipe, not both".into()),
+        (None, None) => return Err(format!("bench needs a builder, one of: all, {}", registry::ids().join(", ")).into()),
+    };
+
+    let text = match format.as_str() {
+        "json" => serde_json::to_string_pretty(&reports)?,
+        // each report starts with the same header, which only needs to go out once
+        "csv" => csv(reports.iter().map(BenchmarkReport::summary_csv)),
+        "runs-csv" => csv(reports.iter().map(BenchmarkReport::runs_csv)),
+        other => return Err(format!("unknown format `{other}`, use json, csv or runs-csv").into()),
+    };
+
+    match output {
+        Some(path) => fs::write(path, text)?,
+        None => println!("{}", text.trim_end()),
+    }
+    Ok(())
+}
+
+// the built in prefabs and any in the prefabs directory
+fn load_stamper() -> PrefabStamper {
+    let mut stamper = PrefabStamper::new();
+    match stamper.load_dir("prefabs") {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => eprintln!("using built in prefabs, couldn't load prefabs: {err}"),
+        _ => {},
+    }
+    stamper
+}
+
+// a range like `40-60`
+fn percent_range(text: &str) -> Result<(f64, f64), Box<dyn Error>> {
+    let (min, max) = text.split_once('-').ok_or_else(|| format!("expected a range like 40-60, got `{text}`"))?;
+    Ok((min.trim().parse()?, max.trim().parse()?))
+}
+
+fn csv(tables: impl Iterator<Item = String>) -> String {
+    let mut csv = String::new();
+    for (i, table) in tables.enumerate() {
+        let rows = if i == 0 { &table[..] } else { table.split_once('\n').map_or("", |(_, rows)| rows) };
+        csv.push_str(rows);
+    }
+    csv
+}
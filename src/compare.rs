@@ -0,0 +1,264 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                             Comparison                                ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::time::Duration;
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::map_builders::map::Map;
+use crate::map_builders::metrics::Metric;
+use crate::map_builders::recipe::Recipe;
+use crate::map_builders::registry::BuilderInfo;
+use crate::map_builders::worker::{Job, Progress, Worker};
+use crate::map_builders::MapBuilder;
+
+
+/// One of the two maps being compared, with the builder that makes it.
+pub struct Side {
+    pub info: BuilderInfo,
+    pub builder: Box<dyn MapBuilder>,
+    pub seed: u64,
+    /// Time taken by the last job.
+    pub duration: Duration,
+    pub metrics: Vec<Metric>,
+    /// Why the last job ended without handing the builder back, if it did.
+    pub failure: Option<String>,
+    rng: StdRng,
+    // the job running in the background, and its latest map
+    worker: Option<Worker>,
+    snapshot: Option<Map>,
+}
+
+impl Side {
+    /// A side with a fresh builder set up and sized like `builder`.
+    pub fn new(info: BuilderInfo, builder: &dyn MapBuilder) -> Result<Side, String> {
+        let builder = Recipe::from_builder(info.id, builder, 0, 0).builder()?;
+        Ok(Side::with_builder(info, builder))
+    }
+
+    fn with_builder(info: BuilderInfo, builder: Box<dyn MapBuilder>) -> Side {
+        Side {
+            info,
+            metrics: builder.report(),
+            builder,
+            seed: 0,
+            duration: Duration::ZERO,
+            failure: None,
+            rng: StdRng::seed_from_u64(0),
+            worker: None,
+            snapshot: None,
+        }
+    }
+
+    /// The map as it stands, part way through a job if one is running.
+    pub fn map(&self) -> &Map {
+        self.snapshot.as_ref().unwrap_or_else(|| self.builder.get_map())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    // hands the builder to a worker, starting again from `seed` if given
+    fn start(&mut self, job: Job, seed: Option<u64>) {
+        if let Some(seed) = seed {
+            self.seed = seed;
+            self.rng = StdRng::seed_from_u64(seed);
+        }
+        self.failure = None;
+        self.snapshot = Some(self.builder.get_map().clone());
+        let builder = std::mem::replace(&mut self.builder, (self.info.create)());
+        let rng = std::mem::replace(&mut self.rng, StdRng::seed_from_u64(0));
+        self.worker = Some(Worker::spawn(builder, rng, job));
+    }
+
+    fn poll(&mut self) {
+        let Some(worker) = &mut self.worker else {
+            return;
+        };
+        for event in worker.poll() {
+            match event {
+                Progress::Step { snapshot, .. } => self.snapshot = Some(snapshot),
+                Progress::Done(outcome) => {
+                    self.worker = None;
+                    self.snapshot = None;
+                    self.builder = outcome.builder;
+                    self.rng = outcome.rng;
+                    self.metrics = self.builder.report();
+                    self.duration = outcome.duration;
+                    return;
+                },
+                // the fresh builder left in its place is kept, sized like
+                // the map that was being made
+                Progress::Failed(err) => {
+                    self.worker = None;
+                    if let Some(map) = self.snapshot.take() {
+                        self.builder.resize(map.width, map.height);
+                    }
+                    self.metrics = self.builder.report();
+                    self.failure = Some(err);
+                    return;
+                },
+            }
+        }
+    }
+
+    // whether both sides would make the same map from the same seed
+    fn same_setup(&self, other: &Side) -> bool {
+        self.info.id == other.info.id && self.builder.settings() == other.builder.settings()
+    }
+}
+
+/// Two maps built and iterated side by side. The left side is set up like
+/// the tab it was opened from; the right can be given another builder or
+/// other settings.
+pub struct Comparison {
+    pub sides: [Side; 2],
+    /// The setting of the right side that `tab` and `enter` change.
+    pub setting_index: usize,
+}
+
+impl Comparison {
+    /// Starts building both sides from one seed, so the right side's
+    /// settings and builder can be changed from the same starting point.
+    pub fn new(info: BuilderInfo, builder: &dyn MapBuilder) -> Result<Comparison, String> {
+        let mut comparison = Comparison { sides: [Side::new(info, builder)?, Side::new(info, builder)?], setting_index: 0 };
+        let seed = rand::random();
+        comparison.sides.iter_mut().for_each(|side| side.start(Job::Build, Some(seed)));
+        Ok(comparison)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.sides.iter().any(Side::is_running)
+    }
+
+    pub fn cancel(&self) {
+        self.sides.iter().filter_map(|side| side.worker.as_ref()).for_each(Worker::cancel);
+    }
+
+    /// Checks on both jobs, returning true once the last of them has ended.
+    pub fn poll(&mut self) -> bool {
+        let running = self.is_running();
+        self.sides.iter_mut().for_each(Side::poll);
+        running && !self.is_running()
+    }
+
+    /// Builds both maps from a new seed. Sides set up differently share the
+    /// seed, so only the setup tells them apart, and sides set up the same
+    /// get a seed each.
+    pub fn build(&mut self) {
+        let seed = rand::random();
+        let other = if self.sides[0].same_setup(&self.sides[1]) { rand::random() } else { seed };
+        self.sides[0].start(Job::Build, Some(seed));
+        self.sides[1].start(Job::Build, Some(other));
+    }
+
+    /// Starts `job` on both sides, carrying on from where they are.
+    pub fn start(&mut self, job: Job) {
+        self.sides.iter_mut().for_each(|side| side.start(job, None));
+    }
+
+    /// Swaps the right side for a fresh builder of another kind, the size of
+    /// the left's map, and builds it from the left's seed.
+    pub fn set_right(&mut self, info: BuilderInfo) {
+        let map = self.sides[0].builder.get_map();
+        let mut builder = (info.create)();
+        builder.resize(map.width, map.height);
+
+        self.sides[1] = Side::with_builder(info, builder);
+        self.setting_index = 0;
+        let seed = self.sides[0].seed;
+        self.sides[1].start(Job::Build, Some(seed));
+    }
+
+    /// Moves on to the right side's next setting.
+    pub fn next_setting(&mut self) {
+        let count = self.sides[1].builder.settings().len();
+        if count > 0 {
+            self.setting_index = (self.setting_index + 1) % count;
+        }
+    }
+
+    /// Changes the right side's current setting and builds it again from the
+    /// left's seed, so the setting is all that differs.
+    pub fn cycle_setting(&mut self) {
+        let seed = self.sides[0].seed;
+        let right = &mut self.sides[1];
+        right.builder.cycle_setting(self.setting_index);
+        right.start(Job::Build, Some(seed));
+    }
+
+    /// A line for the time taken and each metric, with the value from either
+    /// side. Metrics only one side has are shown as `-` on the other.
+    pub fn metric_rows(&self) -> Vec<(String, String, String)> {
+        let time = |side: &Side| format!("{} µs", side.duration.as_micros());
+        let mut rows = vec![("Time".to_string(), time(&self.sides[0]), time(&self.sides[1]))];
+
+        let [left, right] = [&self.sides[0].metrics, &self.sides[1].metrics];
+        let value = |metrics: &[Metric], name: &str| metrics.iter().find(|m| m.name == name).map_or("-".to_string(), |m| m.to_string());
+        for metric in left {
+            rows.push((metric.name.to_string(), metric.to_string(), value(right, metric.name)));
+        }
+        for metric in right.iter().filter(|m| !left.iter().any(|l| l.name == m.name)) {
+            rows.push((metric.name.to_string(), "-".to_string(), metric.to_string()));
+        }
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_builders::registry::BUILT_IN;
+
+    fn info(id: &str) -> BuilderInfo {
+        *BUILT_IN.iter().find(|info| info.id == id).expect("a built in builder")
+    }
+
+    fn comparison(id: &str) -> Comparison {
+        let sides = [Side::with_builder(info(id), (info(id).create)()), Side::with_builder(info(id), (info(id).create)())];
+        Comparison { sides, setting_index: 0 }
+    }
+
+    fn row(name: &str, left: &str, right: &str) -> (String, String, String) {
+        (name.to_string(), left.to_string(), right.to_string())
+    }
+
+    #[test]
+    fn metrics_only_one_side_has_are_shown_as_a_dash_on_the_other() {
+        let mut comparison = comparison("drunkard");
+        comparison.sides[0].metrics = vec![Metric::integer("Walls", 10), Metric::integer("Rooms", 3)];
+        comparison.sides[1].metrics = vec![Metric::integer("Rooms", 4).with_unit("rooms"), Metric::flag("Finished", true)];
+        comparison.sides[1].duration = Duration::from_micros(250);
+
+        assert_eq!(comparison.metric_rows(), vec![
+            row("Time", "0 µs", "250 µs"),
+            row("Walls", "10", "-"),
+            row("Rooms", "3", "4 rooms"),
+            row("Finished", "-", "yes"),
+        ]);
+    }
+
+    #[test]
+    fn only_sides_set_up_the_same_get_a_seed_each() {
+        let mut same = comparison("maze");
+        same.build();
+        assert_ne!(same.sides[0].seed, same.sides[1].seed);
+        same.cancel();
+
+        let mut other_settings = comparison("maze");
+        other_settings.sides[1].builder.cycle_setting(0);
+        other_settings.build();
+        assert_eq!(other_settings.sides[0].seed, other_settings.sides[1].seed);
+        other_settings.cancel();
+
+        let mut other_builders = comparison("maze");
+        other_builders.sides[1] = Side::with_builder(info("drunkard"), (info("drunkard").create)());
+        other_builders.build();
+        assert_eq!(other_builders.sides[0].seed, other_builders.sides[1].seed);
+        other_builders.cancel();
+    }
+}
@@ -0,0 +1,60 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                             Exploration                               ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use crate::map_builders::analysis;
+use crate::map_builders::fov;
+use crate::map_builders::map::{Map, TileType};
+
+// how far the player can see
+const SIGHT_RADIUS: usize = 8;
+
+
+/// A player walking a map, seeing what's in their field of view and
+/// remembering the rest of what they've seen.
+pub struct Exploration {
+    pub player: (usize, usize),
+    /// Tiles by index in view right now.
+    pub visible: Vec<bool>,
+    /// Tiles by index that have ever been in view.
+    pub explored: Vec<bool>,
+    pub steps: usize,
+}
+
+impl Exploration {
+    /// Starts the player at the map's start point, if it has one.
+    pub fn new(map: &Map) -> Option<Exploration> {
+        let player = analysis::start_point(map)?;
+        let mut exploration = Exploration { player, visible: Vec::new(), explored: vec![false; map.tiles.len()], steps: 0 };
+        exploration.look(map);
+        Some(exploration)
+    }
+
+    /// Takes a step if the tile that way is floor, returning whether it did.
+    pub fn step(&mut self, map: &Map, dx: i32, dy: i32) -> bool {
+        let (x, y) = (self.player.0 as i32 + dx, self.player.1 as i32 + dy);
+        if x < 0 || y < 0 || x as usize >= map.width || map.get(x as usize, y as usize) != Some(&TileType::Floor) {
+            return false;
+        }
+        self.player = (x as usize, y as usize);
+        self.steps += 1;
+        self.look(map);
+        true
+    }
+
+    /// The share of the map's floor seen so far, from 0 to 100.
+    pub fn explored_percent(&self, map: &Map) -> f64 {
+        let floors = map.tiles.iter().filter(|tile| **tile == TileType::Floor).count();
+        let seen = map.tiles.iter().zip(&self.explored).filter(|(tile, seen)| **tile == TileType::Floor && **seen).count();
+        if floors == 0 { 0.0 } else { 100.0 * seen as f64 / floors as f64 }
+    }
+
+    fn look(&mut self, map: &Map) {
+        self.visible = fov::symmetric_shadowcast(map, self.player, SIGHT_RADIUS);
+        for (explored, visible) in self.explored.iter_mut().zip(&self.visible) {
+            *explored |= visible;
+        }
+    }
+}
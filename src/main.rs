@@ -5,90 +5,144 @@
 /////////// ------------------------------------------------------///////////
 
 pub mod map_builders;
+mod cli;
+mod compare;
+mod explore;
+mod overlay;
+mod theme;
+mod viewport;
 
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseButton, MouseEvent, MouseEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use map_builders::{bsp_dungeon::BSPDungeonBuilder, cellular_automata::CellularAutomataBuilder, df_aggregation::DiffusionLimitedAggregationBuilder, drunkard::DrunkardBuilder, MapBuilder};
-use std::{cmp::Ordering, error::Error, io, time::{Duration}};
+use map_builders::{benchmark::{self, BenchmarkConfig, BenchmarkReport}, constraints::{Constraints, Found, Search, SearchProgress, SearchWorker}, recipe::{Generated, Recipe}, prefabs::{MarkerKind, Placement, PlacementMode, PrefabStamper}, map::{Map, TileType}, metrics::Metric, pipeline::PipelineBuilder, registry::BuilderRegistry, worker::{Job, Outcome, Progress, Worker}, MapBuilder};
+use rand::{rngs::StdRng, SeedableRng};
+use compare::Comparison;
+use explore::Exploration;
+use overlay::Overlay;
+use theme::Theme;
+use viewport::Viewport;
+use std::{cmp::Ordering, error::Error, io, thread::{self, JoinHandle}, time::{Duration}};
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Span, Spans, Text},
-    widgets::{BorderType, Block, Borders, Tabs, Paragraph, BarChart, Sparkline, ListItem, List},
+    widgets::{BorderType, Block, Clear, Borders, Tabs, Paragraph, BarChart, Sparkline, ListItem, List, Wrap},
     Frame, Terminal,
 };
 
 
-enum Algorithm {
-    BSP,
-    Cellular,
-    Drunkard,
-    Aggregation,
-}
-
+// what maps searched for with the `v` key have to meet when the recipe doesn't say
+const CONSTRAINTS_PATH: &str = "constraints.toml";
 
-struct MapBuilders {
-    bsp: BSPDungeonBuilder,
-    cellular: CellularAutomataBuilder,
-    drunkard: DrunkardBuilder,
-    dfa: DiffusionLimitedAggregationBuilder,
-}
 
-
-struct App<'a> {
+struct App {
     gen_time: u128,
-    time_barchart: Vec<(&'a str, u64)>,
+    time_barchart: Vec<(&'static str, u64)>,
     time_sparkline: Vec<u64>,
-    algorithm: Algorithm,
-    tab_titles: Vec<&'a str>,
+    registry: BuilderRegistry,
     tab_index: usize,
-    map_data: Vec<(&'a str, String)>,
-    map_builders: MapBuilders,
+    map_data: Vec<Metric>,
+    status: String,
+    setting_index: usize,
+    prefabs: PrefabStamper,
+    placements: Vec<Placement>,
+    // the job running in the background, with its latest map and progress
+    worker: Option<Worker>,
+    snapshot: Option<Map>,
+    progress: Option<String>,
+    benchmark: Option<JoinHandle<BenchmarkReport>>,
+    // the generator the current builder draws from, and the seed and
+    // iterations that got its map where it is
+    rng: StdRng,
+    seed: Option<u64>,
+    iterations: usize,
+    recipe: Option<Recipe>,
+    // where the map and minimap were last drawn, and the tile a drag is
+    // painting with
+    map_area: Rect,
+    minimap_area: Option<Rect>,
+    paint: Option<TileType>,
+    viewport: Viewport,
+    themes: Vec<Theme>,
+    theme_index: usize,
+    // two maps shown in place of the current tab's
+    comparison: Option<Comparison>,
+    // the player walking the current map
+    exploration: Option<Exploration>,
+    // maps being made from new seeds until one meets the constraints
+    search: Option<RunningSearch>,
+    constraints: Constraints,
 }
 
 
-impl<'a> Default for App<'a> {
+// a search on its own thread, with the recipe it makes maps from
+struct RunningSearch {
+    worker: SearchWorker,
+    recipe: Recipe,
+    attempts: usize,
+}
+
+
+impl Default for App {
     fn default() -> Self {
         App {
             gen_time: 0,
-            time_barchart: Vec::<(&'a str, u64)>::new(),
+            time_barchart: Vec::<(&'static str, u64)>::new(),
             time_sparkline: Vec::<u64>::new(),
-            algorithm: Algorithm::BSP,
-            tab_titles: vec!["(1) BSP", "(2) Cellular", "(3) Drunkard", "(4) DFA"],
+            registry: BuilderRegistry::built_in(),
             tab_index: 0,
-            map_data: Vec::<(&str, String)>::new(),
-            map_builders: MapBuilders {
-                bsp: BSPDungeonBuilder::new(),
-                cellular: CellularAutomataBuilder::new(),
-                dfa: DiffusionLimitedAggregationBuilder::new(),
-                drunkard: DrunkardBuilder::new(),
-            }
+            map_data: Vec::<Metric>::new(),
+            status: String::new(),
+            setting_index: 0,
+            prefabs: PrefabStamper::new(),
+            placements: Vec::new(),
+            worker: None,
+            snapshot: None,
+            progress: None,
+            benchmark: None,
+            rng: StdRng::from_entropy(),
+            seed: None,
+            iterations: 0,
+            recipe: None,
+            map_area: Rect::default(),
+            minimap_area: None,
+            paint: None,
+            viewport: Viewport::new(),
+            themes: Theme::built_in(),
+            theme_index: 0,
+            comparison: None,
+            exploration: None,
+            search: None,
+            constraints: Constraints::new(),
         }
     }
 }
 
 
-impl<'a> App<'a> {
-    fn update_current_algo(&mut self) {
-        match self.tab_index {
-            0 => self.algorithm = Algorithm::BSP,
-            1 => self.algorithm = Algorithm::Cellular,
-            2 => self.algorithm = Algorithm::Drunkard,
-            3 => self.algorithm = Algorithm::Aggregation,
-            _ => self.algorithm = Algorithm::BSP,
-        }
+impl App {
+    fn select_tab(&mut self, index: usize) {
+        self.tab_index = index;
+        self.setting_index = 0;
         rebuild(self);
     }
 
+    fn builder(&self) -> &dyn MapBuilder {
+        self.registry.get(self.tab_index).expect("tab has a builder").builder.as_ref()
+    }
+
+    fn builder_mut(&mut self) -> &mut dyn MapBuilder {
+        self.registry.get_mut(self.tab_index).expect("tab has a builder").builder.as_mut()
+    }
+
     // prevents iteration on unbuilt maps which breaks app
     fn build_iter_maps(&mut self) {
-        self.map_builders.cellular.build();
-        self.map_builders.dfa.build();
-        self.map_builders.drunkard.build();
+        for entry in self.registry.entries_mut() {
+            entry.builder.build();
+        }
     }
 
     fn set_gen_time(&mut self, gen_time: Duration) {
@@ -97,23 +151,16 @@ impl<'a> App<'a> {
 
     fn update_time_charts(&mut self) {
         // update sparkline time data
-        match self.time_sparkline.len().cmp(&150) {
-            Ordering::Greater => { self.time_sparkline.pop(); },
-            _ => {}
+        if self.time_sparkline.len().cmp(&150) == Ordering::Greater {
+            self.time_sparkline.pop();
         }
         self.time_sparkline.insert(0, self.gen_time as u64);
     
         // update barchart time data
-        let title: &str = match self.algorithm {
-            Algorithm::Aggregation => "DFA",
-            Algorithm::Cellular => "CELL",
-            Algorithm::Drunkard => "DRU",
-            Algorithm::BSP => "HUE",
-        };
+        let title = self.registry.get(self.tab_index).expect("tab has a builder").info.label;
     
-        match self.time_barchart.len().cmp(&10) {
-            Ordering::Greater => { self.time_barchart.pop(); },
-            _ => {}
+        if self.time_barchart.len().cmp(&10) == Ordering::Greater {
+            self.time_barchart.pop();
         }
         self.time_barchart.insert(0, (title, self.gen_time as u64));
     }
@@ -121,6 +168,24 @@ impl<'a> App<'a> {
 
 
 fn main() -> Result<(), Box<dyn Error>> {
+    // other than a recipe to open, any arguments are a command to run
+    // without the TUI
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let recipe = match args.as_slice() {
+        [] => None,
+        [flag, path] if flag == "--recipe" => Some(Recipe::load(path).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            std::process::exit(1);
+        })),
+        _ => {
+            if let Err(err) = cli::run(&args) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            return Ok(());
+        },
+    };
+
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -130,7 +195,15 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // create app, initialize map builders, and run
     let mut app = App::default();
+    load_prefabs(&mut app);
+    load_pipelines(&mut app);
+    load_themes(&mut app);
+    load_constraints(&mut app);
     app.build_iter_maps();
+    match recipe {
+        Some(recipe) => load_recipe(&mut app, recipe),
+        None => rebuild(&mut app),
+    }
     let res = run_app(&mut terminal, app);
 
     // restore terminal
@@ -155,8 +228,62 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
         // draws the current state of the app
         terminal.draw(|f| ui(f, &mut app))?;
 
+        // keeps the ui moving while a job runs in the background
+        poll_worker(&mut app);
+        poll_benchmark(&mut app);
+        poll_comparison(&mut app);
+        poll_search(&mut app);
+        let running = app.worker.is_some() || app.benchmark.is_some() || app.search.is_some() || app.comparison.as_ref().is_some_and(Comparison::is_running);
+        let timeout = if running { 50 } else { 250 };
+        if !event::poll(Duration::from_millis(timeout))? {
+            continue;
+        }
+
         // handles user input
-        if let Event::Key(key) = event::read()? {
+        let event = event::read()?;
+        if let Event::Mouse(mouse) = event {
+            paint(&mut app, mouse);
+        }
+        if let Event::Key(key) = event {
+            // only cancelling and quitting work while a job runs
+            if let Some(worker) = &app.worker {
+                match key.code {
+                    KeyCode::Char('x') | KeyCode::Esc => {
+                        worker.cancel();
+                        app.progress = Some("Cancelling once the running build or iteration finishes...".to_string());
+                    },
+                    KeyCode::Char('q') => return Ok(()),
+                    _ => {}
+                }
+                continue;
+            }
+            if let Some(search) = &app.search {
+                match key.code {
+                    KeyCode::Char('x') | KeyCode::Esc => {
+                        // the thread stops on its own once the map it is making is done
+                        app.status = format!("Stopped looking after {} attempts", search.attempts);
+                        app.search = None;
+                    },
+                    KeyCode::Char('q') => return Ok(()),
+                    _ => {}
+                }
+                continue;
+            }
+            if let Some(comparison) = app.comparison.as_ref().filter(|c| c.is_running()) {
+                match key.code {
+                    KeyCode::Char('x') | KeyCode::Esc => {
+                        comparison.cancel();
+                        app.status = "Cancelling once the running builds or iterations finish...".to_string();
+                    },
+                    KeyCode::Char('q') => return Ok(()),
+                    _ => {}
+                }
+                continue;
+            }
+            if compare_key(&mut app, key.code) || explore_key(&mut app, key.code) {
+                continue;
+            }
+
             match key.code {
                 KeyCode::Char('b') => {
                     rebuild(&mut app);
@@ -166,29 +293,103 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
                     iterate(&mut app);
                 },
 
-                KeyCode::Char('q') => {
-                    return Ok(());
+                KeyCode::Char('r') => {
+                    start_job(&mut app, Job::Run);
                 },
 
-                KeyCode::Char('1') => {
-                    app.tab_index = 0;
-                    app.update_current_algo();
+                KeyCode::Char('v') => {
+                    start_search(&mut app);
                 },
 
-                KeyCode::Char('2') => {
-                    app.tab_index = 1;
-                    app.update_current_algo();
+                KeyCode::Char('S') => {
+                    save_recipe(&mut app);
                 },
 
-                KeyCode::Char('3') => {
-                    app.tab_index = 2;
-                    app.update_current_algo();
+                KeyCode::Up | KeyCode::Char('w') => pan(&mut app, 0, -1),
+                KeyCode::Down | KeyCode::Char('s') => pan(&mut app, 0, 1),
+                KeyCode::Left | KeyCode::Char('a') => pan(&mut app, -1, 0),
+                KeyCode::Right | KeyCode::Char('d') => pan(&mut app, 1, 0),
+
+                KeyCode::Char('z') => {
+                    zoom(&mut app);
                 },
 
-                KeyCode::Char('4') => {
-                    app.tab_index = 3;
-                    app.update_current_algo();
-                }
+                KeyCode::Char('g') => {
+                    app.viewport.autotile = !app.viewport.autotile;
+                    app.status = format!("Autotiled walls {}", if app.viewport.autotile { "on" } else { "off" });
+                },
+
+                KeyCode::Char('o') => {
+                    app.viewport.overlay = app.viewport.overlay.next();
+                    let map = app.registry.get(app.tab_index).expect("tab has a builder").builder.get_map();
+                    app.status = app.viewport.overlay.summary(map);
+                },
+
+                KeyCode::Char('t') => {
+                    app.theme_index = (app.theme_index + 1) % app.themes.len();
+                    app.status = format!("Using the {} theme", app.themes[app.theme_index].name);
+                },
+
+                KeyCode::Char('m') => {
+                    start_benchmark(&mut app);
+                },
+
+                KeyCode::Char('c') => {
+                    open_comparison(&mut app);
+                },
+
+                KeyCode::Char('p') => {
+                    start_exploring(&mut app);
+                },
+
+                KeyCode::Char(']') => {
+                    app.select_tab((app.tab_index + 1) % app.registry.len());
+                },
+
+                KeyCode::Char('[') => {
+                    app.select_tab((app.tab_index + app.registry.len() - 1) % app.registry.len());
+                },
+
+                KeyCode::Tab => {
+                    let num_settings = app.builder().settings().len();
+                    if num_settings > 0 {
+                        app.setting_index = (app.setting_index + 1) % num_settings;
+                    }
+                },
+
+                KeyCode::Enter => {
+                    cycle_setting(&mut app);
+                },
+
+                KeyCode::Char('e') => {
+                    export(&mut app);
+                },
+
+                KeyCode::Char('y') => {
+                    use_as_sample(&mut app);
+                },
+
+                KeyCode::Char('f') => {
+                    stamp_prefabs(&mut app);
+                },
+
+                KeyCode::Char('F') => {
+                    app.prefabs.mode = match app.prefabs.mode {
+                        PlacementMode::OverwriteWalls => PlacementMode::OverlapFloor,
+                        PlacementMode::OverlapFloor => PlacementMode::OverwriteWalls,
+                    };
+                    app.status = format!("Prefabs will {}", app.prefabs.mode.name().to_lowercase());
+                },
+
+                KeyCode::Char('q') => {
+                    return Ok(());
+                },
+
+                KeyCode::Char(c) => {
+                    if let Some(index) = app.registry.index_of_key(c) {
+                        app.select_tab(index);
+                    }
+                },
 
                 _ => {}
             }
@@ -198,76 +399,633 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
 
 
 fn rebuild(app: &mut App) {
-    let duration = match app.algorithm {
-        Algorithm::BSP => {
-            let builder = &mut app.map_builders.bsp;
-            let duration = map_builders::rebuild(builder);
-            builder.update_map_data(&mut app.map_data);
-            duration
+    start_job(app, Job::Build);
+}
+
+
+fn iterate(app: &mut App) {
+    start_job(app, Job::Iterate);
+}
+
+
+// hands the current builder to a worker thread, leaving a fresh one in its
+// place until the job is done
+fn start_job(app: &mut App, job: Job) {
+    if app.worker.is_some() {
+        return;
+    }
+    let entry = app.registry.get_mut(app.tab_index).expect("tab has a builder");
+    let builder = std::mem::replace(&mut entry.builder, (entry.info.create)());
+
+    // every build starts from a new seed, which a saved recipe can replay
+    if job == Job::Build {
+        let seed = rand::random();
+        app.seed = Some(seed);
+        app.rng = StdRng::seed_from_u64(seed);
+    }
+    let rng = std::mem::replace(&mut app.rng, StdRng::seed_from_u64(0));
+
+    app.snapshot = Some(builder.get_map().clone());
+    let running = match builder.running_step(job == Job::Build) {
+        Some(step) => format!("{}: {}", entry.info.name, step),
+        None => entry.info.name.to_string(),
+    };
+    app.progress = Some(format!("{} running, press x to cancel", running));
+    app.worker = Some(Worker::spawn(builder, rng, job));
+}
+
+
+fn poll_worker(app: &mut App) {
+    let Some(worker) = &mut app.worker else {
+        return;
+    };
+    let cancelling = worker.is_cancelled();
+
+    for event in worker.poll() {
+        match event {
+            Progress::Step { iteration, percent, next_step, snapshot } => {
+                if !cancelling {
+                    let done = percent.map_or(String::new(), |p| format!(" ({:.0}%)", p));
+                    let running = next_step.map_or(String::new(), |step| format!(", running {}", step));
+                    app.progress = Some(format!("Iteration {}{}{}, press x to cancel", iteration, done, running));
+                }
+                app.snapshot = Some(snapshot);
+            },
+            Progress::Done(outcome) => finish_job(app, *outcome),
+            Progress::Failed(err) => fail_job(app, &err),
+        }
+    }
+}
+
+
+// the builder went down with the worker, so the tab gets a fresh one the
+// size of the map it was working on
+fn fail_job(app: &mut App, err: &str) {
+    app.worker = None;
+    app.progress = None;
+    let snapshot = app.snapshot.take();
+
+    let entry = app.registry.get_mut(app.tab_index).expect("tab has a builder");
+    entry.builder = (entry.info.create)();
+    if let Some(map) = snapshot {
+        entry.builder.resize(map.width, map.height);
+    }
+    app.map_data = entry.builder.report();
+    app.iterations = 0;
+    app.placements.clear();
+    app.status = format!("{} failed: {}. Its settings are back to the defaults", entry.info.name, err);
+}
+
+
+fn finish_job(app: &mut App, outcome: Outcome) {
+    app.worker = None;
+    app.snapshot = None;
+    app.progress = None;
+
+    let entry = app.registry.get_mut(app.tab_index).expect("tab has a builder");
+    entry.builder = outcome.builder;
+    app.map_data = entry.builder.report();
+    app.rng = outcome.rng;
+    app.iterations = match outcome.job {
+        Job::Build => 0,
+        _ => app.iterations + outcome.iterations,
+    };
+
+    if outcome.cancelled {
+        app.status = format!("Cancelled after {} iterations", outcome.iterations);
+    } else if let Job::Run = outcome.job {
+        app.status = format!("Ran {} iterations", outcome.iterations);
+    }
+    if outcome.job == Job::Build {
+        app.placements.clear();
+    }
+
+    // updates the state of the ui data for the next render
+    if outcome.job == Job::Build || outcome.duration.as_micros() > 0 {
+        app.set_gen_time(outcome.duration);
+        app.update_time_charts();
+    }
+}
+
+
+// benchmarks fresh copies of the current builder in the background, so the
+// ui keeps working meanwhile
+fn start_benchmark(app: &mut App) {
+    const RUNS: usize = 100;
+
+    if app.benchmark.is_some() {
+        return;
+    }
+    let info = app.registry.get(app.tab_index).expect("tab has a builder").info;
+    let config = BenchmarkConfig {
+        runs: RUNS,
+        first_seed: rand::random(),
+        max_iterations: None,
+        threads: thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+
+    app.status = format!("Benchmarking {} over {} seeds...", info.name, RUNS);
+    app.benchmark = Some(thread::spawn(move || benchmark::run(info.id, info.create, &config)));
+}
+
+
+fn poll_benchmark(app: &mut App) {
+    const BENCHMARK_PATH: &str = "benchmark.json";
+
+    if !app.benchmark.as_ref().is_some_and(|handle| handle.is_finished()) {
+        return;
+    }
+    let Ok(report) = app.benchmark.take().expect("benchmark is running").join() else {
+        app.status = "Benchmark failed".to_string();
+        return;
+    };
+
+    let written = report.to_json().map_err(io::Error::from).and_then(|json| std::fs::write(BENCHMARK_PATH, json));
+    app.status = match (&report.time, &report.floor_percent, written) {
+        (Some(time), Some(floor), Ok(())) => format!(
+            "{} runs: median {:.0} µs, p95 {:.0} µs, max {:.0} µs, floor {:.1}% on average, saved to {BENCHMARK_PATH}",
+            report.runs.len(), time.median, time.p95, time.max, floor.mean,
+        ),
+        (_, _, Err(err)) => format!("Benchmark could not be saved: {err}"),
+        _ => "Benchmark ran no maps".to_string(),
+    };
+}
+
+
+// compares the current tab's builder with a copy of itself, which can then
+// be given other settings or swapped for another builder
+fn open_comparison(app: &mut App) {
+    let entry = app.registry.get(app.tab_index).expect("tab has a builder");
+    match Comparison::new(entry.info, entry.builder.as_ref()) {
+        Ok(comparison) => {
+            app.status = format!("Comparing {} with itself, press a builder's key to compare with it instead, or c to stop", entry.info.name);
+            app.comparison = Some(comparison);
         },
-        Algorithm::Cellular => {
-            let builder = &mut app.map_builders.cellular;
-            let duration = map_builders::rebuild(builder);
-            builder.update_map_data(&mut app.map_data);
-            duration
+        Err(err) => app.status = format!("Couldn't compare: {err}"),
+    }
+}
+
+
+// handles the keys that act on both maps while comparing, returning whether
+// the key was used up
+fn compare_key(app: &mut App, code: KeyCode) -> bool {
+    let Some(comparison) = &mut app.comparison else {
+        return false;
+    };
+
+    match code {
+        KeyCode::Char('c') => {
+            app.comparison = None;
+            app.status = "Stopped comparing".to_string();
         },
-        Algorithm::Aggregation => {
-            let builder = &mut app.map_builders.dfa;
-            let duration = map_builders::rebuild(builder);
-            builder.update_map_data(&mut app.map_data);
-            duration
+        KeyCode::Char('b') => comparison.build(),
+        KeyCode::Char('i') => comparison.start(Job::Iterate),
+        KeyCode::Char('r') => comparison.start(Job::Run),
+        KeyCode::Tab => comparison.next_setting(),
+        KeyCode::Enter => comparison.cycle_setting(),
+        KeyCode::Char(c) if app.registry.index_of_key(c).is_some() => {
+            let info = app.registry.get(app.registry.index_of_key(c).expect("key was found")).expect("tab has a builder").info;
+            comparison.set_right(info);
+            app.status = format!("Comparing {} with {}", comparison.sides[0].info.name, info.name);
         },
-        Algorithm::Drunkard => {
-            let builder = &mut app.map_builders.drunkard;
-            let duration = map_builders::rebuild(builder);
-            builder.update_map_data(&mut app.map_data);
-            duration
+        // the rest act on a single map
+        KeyCode::Char('e' | 'y' | 'f' | 'F' | 'S' | 'm' | 'p' | 'v') => app.status = "Press c to stop comparing first".to_string(),
+        _ => return false,
+    }
+    true
+}
+
+
+// drops the player at the start of the current map
+fn start_exploring(app: &mut App) {
+    let map = app.registry.get(app.tab_index).expect("tab has a builder").builder.get_map();
+    let Some(exploration) = Exploration::new(map) else {
+        app.status = "There's no floor to explore".to_string();
+        return;
+    };
+    app.viewport.centre_on(exploration.player.0, exploration.player.1, map, app.map_area);
+    app.status = "Exploring, arrows to walk and p to stop".to_string();
+    app.exploration = Some(exploration);
+}
+
+
+// walks the player with the arrow keys while exploring, leaving the keys
+// that only change the view to work as usual, and returns whether the key
+// was used up
+fn explore_key(app: &mut App, code: KeyCode) -> bool {
+    let Some(exploration) = &mut app.exploration else {
+        return false;
+    };
+    let map = app.registry.get(app.tab_index).expect("tab has a builder").builder.get_map();
+
+    let (dx, dy) = match code {
+        KeyCode::Up => (0, -1),
+        KeyCode::Down => (0, 1),
+        KeyCode::Left => (-1, 0),
+        KeyCode::Right => (1, 0),
+        KeyCode::Char('p') | KeyCode::Esc => {
+            app.exploration = None;
+            app.status = "Stopped exploring".to_string();
+            return true;
+        },
+        KeyCode::Char('w' | 'a' | 's' | 'd' | 'z' | 'g' | 'o' | 't' | 'q') => return false,
+        _ => {
+            app.status = "Press p to stop exploring first".to_string();
+            return true;
         },
     };
-    app.set_gen_time(duration);
 
-    // updates the state of the ui data for the next render
-    app.update_time_charts();
+    if exploration.step(map, dx, dy) {
+        app.viewport.centre_on(exploration.player.0, exploration.player.1, map, app.map_area);
+        app.status = format!("{} steps, {:.0}% of the floor explored", exploration.steps, exploration.explored_percent(map));
+    }
+    true
 }
 
 
-fn iterate(app: &mut App) {
-    match app.algorithm {
-        Algorithm::Cellular => {
-            let builder = &mut app.map_builders.cellular;
-            let duration = map_builders::iterate(builder);
-
-            builder.update_map_data(&mut app.map_data);
-            if duration.as_micros() > 0 {
-                app.set_gen_time(duration);
-                app.update_time_charts();
-            }
+fn poll_comparison(app: &mut App) {
+    let Some(comparison) = &mut app.comparison else {
+        return;
+    };
+    if comparison.poll() {
+        let [left, right] = &comparison.sides;
+        app.status = match comparison.sides.iter().find_map(|side| side.failure.as_ref().map(|err| (side.info.name, err))) {
+            Some((name, err)) => format!("{} failed: {}. Its settings are back to the defaults", name, err),
+            None => format!("{} with seed {} and {} with seed {}", left.info.name, left.seed, right.info.name, right.seed),
+        };
+    }
+}
+
+
+// makes maps for the current tab from new seeds until one meets the
+// constraints of the recipe it was loaded from, or those in CONSTRAINTS_PATH
+fn start_search(app: &mut App) {
+    let id = app.registry.get(app.tab_index).expect("tab has a builder").info.id;
+    let mut recipe = Recipe::from_builder(id, app.builder(), 0, 0);
+    recipe.seed = None;
+    recipe.iterations = None;
+    if let Some(loaded) = app.recipe.as_ref().filter(|loaded| loaded.algorithm == id) {
+        recipe.post_processing = loaded.post_processing.clone();
+        recipe.export = loaded.export.clone();
+        recipe.constraints = loaded.constraints.clone();
+    }
+    if recipe.constraints.is_empty() {
+        recipe.constraints = app.constraints.clone();
+    }
+    if recipe.constraints.is_empty() {
+        app.status = format!("There are no constraints to meet, add some to {CONSTRAINTS_PATH}");
+        return;
+    }
+
+    let mut stamper = PrefabStamper::new();
+    stamper.set_prefabs(app.prefabs.prefabs().to_vec());
+    app.status = format!("Looking for a map with {}...", recipe.constraints.summary());
+    let worker = SearchWorker::spawn(Search::new(recipe.clone(), stamper));
+    app.search = Some(RunningSearch { worker, recipe, attempts: 0 });
+}
+
+
+// shows how the search is getting on, and the map on its tab once one meets
+// the constraints
+fn poll_search(app: &mut App) {
+    let Some(search) = &mut app.search else {
+        return;
+    };
+    let summary = search.recipe.constraints.summary();
+    let max_attempts = search.recipe.constraints.max_attempts;
+
+    for event in search.worker.poll() {
+        match event {
+            SearchProgress::Attempt { attempts, failures } => {
+                if let Some(search) = &mut app.search {
+                    search.attempts = attempts;
+                }
+                app.status = format!("Attempt {} of {}: {}, press x to stop", attempts, max_attempts, failures.join("; "));
+            },
+            SearchProgress::Found(found) => {
+                let Found { generated, rng, attempts } = *found;
+                let recipe = app.search.take().expect("search is running").recipe;
+                let Some(index) = app.registry.index_of(&recipe.algorithm) else {
+                    app.status = format!("Recipe uses '{}', which isn't in the registry", recipe.algorithm);
+                    return;
+                };
+                let seed = generated.seed;
+                show_generated(app, index, generated);
+                app.rng = rng;
+                let plural = if attempts == 1 { "" } else { "s" };
+                app.status = format!("Met {} with seed {} after {} attempt{plural}", summary, seed, attempts);
+                app.recipe = Some(recipe);
+                return;
+            },
+            SearchProgress::GaveUp(reason) => {
+                app.status = format!("Gave up looking for {}: {}", summary, reason);
+                app.search = None;
+                return;
+            },
+        }
+    }
+}
+
+
+fn cycle_setting(app: &mut App) {
+    let index = app.setting_index;
+    app.builder_mut().cycle_setting(index);
+    rebuild(app);
+}
+
+
+// offers the current map to every other builder that learns from samples
+fn use_as_sample(app: &mut App) {
+    let sample = app.builder().get_map().clone();
+    let name = app.registry.get(app.tab_index).expect("tab has a builder").info.name;
+
+    let mut learners = Vec::new();
+    for (i, entry) in app.registry.entries_mut().iter_mut().enumerate() {
+        if i != app.tab_index && entry.builder.learn_from(&sample, name) {
+            learners.push(entry.info.name);
+        }
+    }
+    app.status = if learners.is_empty() {
+        "No builder learns from samples".to_string()
+    } else {
+        format!("{} will learn from {name} on the next build", learners.join(", "))
+    };
+}
+
+
+// designers' prefabs in the prefabs directory replace the built in ones
+fn load_prefabs(app: &mut App) {
+    const PREFAB_DIR: &str = "prefabs";
+    match app.prefabs.load_dir(PREFAB_DIR) {
+        Ok(()) => {},
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+        Err(err) => app.status = format!("Using built in prefabs, couldn't load {}: {}", PREFAB_DIR, err),
+    }
+}
+
+
+// themes in the themes directory come after the built in ones
+fn load_themes(app: &mut App) {
+    const THEME_DIR: &str = "themes";
+    match Theme::load_dir(THEME_DIR) {
+        Ok(themes) => app.themes.extend(themes),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+        Err(err) => app.status = format!("Couldn't load the themes in {}: {}", THEME_DIR, err),
+    }
+}
+
+
+fn load_constraints(app: &mut App) {
+    match std::fs::read_to_string(CONSTRAINTS_PATH) {
+        Ok(text) => match toml::from_str(&text) {
+            Ok(constraints) => app.constraints = constraints,
+            Err(err) => app.status = format!("Couldn't read {}: {}", CONSTRAINTS_PATH, err),
         },
-        Algorithm::Aggregation => {
-            let builder = &mut app.map_builders.dfa;
-            let duration = map_builders::iterate(builder);
-
-            builder.update_map_data(&mut app.map_data);
-            if duration.as_micros() > 0 {
-                app.set_gen_time(duration);
-                app.update_time_charts();
-            }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+        Err(err) => app.status = format!("Couldn't load {}: {}", CONSTRAINTS_PATH, err),
+    }
+}
+
+
+// pipelines in the pipelines directory are added to the built in ones
+fn load_pipelines(app: &mut App) {
+    const PIPELINE_DIR: &str = "pipelines";
+    let mut pipeline = PipelineBuilder::new();
+    pipeline.set_prefabs(app.prefabs.prefabs().to_vec());
+
+    match pipeline.load_presets(PIPELINE_DIR) {
+        Ok(()) => {},
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+        Err(err) => app.status = format!("Couldn't load all of {}: {}", PIPELINE_DIR, err),
+    }
+    if let Some(index) = app.registry.index_of("pipeline") {
+        app.registry.entries_mut()[index].builder = Box::new(pipeline);
+    }
+}
+
+
+fn stamp_prefabs(app: &mut App) {
+    let mut rng = map_builders::random::rng();
+    let map = app.registry.get_mut(app.tab_index).expect("tab has a builder").builder.get_map_mut();
+    let placements = app.prefabs.stamp(map, &mut rng);
+
+    app.status = if placements.is_empty() {
+        format!("No prefabs fit ({})", app.prefabs.mode.name())
+    } else {
+        let names: Vec<&str> = placements.iter().map(|p| p.name.as_str()).collect();
+        format!("Stamped {} ({})", names.join(", "), app.prefabs.mode.name())
+    };
+    app.placements.extend(placements);
+}
+
+
+// where the markers of stamped prefabs are drawn
+fn map_markers(placements: &[Placement]) -> Vec<(usize, usize, MarkerKind)> {
+    placements.iter().flat_map(|p| &p.markers).map(|marker| (marker.x, marker.y, marker.kind)).collect()
+}
+
+
+// clicking a tile toggles it, and dragging paints the tiles passed over the
+// same way
+fn paint(app: &mut App, mouse: MouseEvent) {
+    if app.worker.is_some() || app.comparison.is_some() || app.exploration.is_some() {
+        return;
+    }
+    let map = app.registry.get(app.tab_index).expect("tab has a builder").builder.get_map();
+
+    // the minimap sits on top of the map, and clicking it moves the view
+    let on_minimap = app.minimap_area.and_then(|area| app.viewport.minimap_tile(map, area, mouse.column, mouse.row));
+    if let (MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Drag(MouseButton::Left), Some((x, y))) = (mouse.kind, on_minimap) {
+        app.viewport.centre_on(x, y, map, app.map_area);
+        return;
+    }
+    let tile = app.viewport.tile_at(map, app.map_area, mouse.column, mouse.row);
+
+    match (mouse.kind, tile) {
+        (MouseEventKind::Down(MouseButton::Left), Some((x, y))) => {
+            let new_tile = match app.builder().get_map().get(x, y) {
+                Some(TileType::Wall) => TileType::Floor,
+                _ => TileType::Wall,
+            };
+            app.paint = Some(new_tile);
         },
-        Algorithm::Drunkard => {
-            let builder = &mut app.map_builders.drunkard;
-            let duration = map_builders::iterate(builder);
-
-            builder.update_map_data(&mut app.map_data);
-            if duration.as_micros() > 0 {
-                app.set_gen_time(duration);
-                app.update_time_charts();
-            }
+        (MouseEventKind::Drag(MouseButton::Left), Some(_)) => {},
+        (MouseEventKind::Up(MouseButton::Left), _) => {
+            app.paint = None;
+            app.map_data = app.builder().report();
+            return;
         },
-        _ => {}
+        _ => return,
+    }
+
+    if let (Some(new_tile), Some((x, y))) = (app.paint, tile) {
+        // builders rely on the outer ring staying wall
+        let map = app.builder().get_map();
+        if x == 0 || y == 0 || x + 1 == map.width || y + 1 == map.height {
+            app.status = "The edge of the map stays wall".to_string();
+            return;
+        }
+        if !app.builder_mut().get_map_mut().set(new_tile, x, y) {
+            return;
+        }
+        // the map can't be made again from its seed once it's been edited
+        app.seed = None;
+        app.status = format!("Painted {}, {}", x, y);
+    }
+}
+
+
+fn pan(app: &mut App, dx: i32, dy: i32) {
+    let map = app.registry.get(app.tab_index).expect("tab has a builder").builder.get_map();
+    app.viewport.pan(dx, dy, map, app.map_area);
+}
+
+
+fn zoom(app: &mut App) {
+    let map = app.registry.get(app.tab_index).expect("tab has a builder").builder.get_map();
+    let zoom = app.viewport.zoom.next();
+    app.viewport.set_zoom(zoom, map, app.map_area);
+    app.status = format!("Zoomed to {}", zoom.name());
+}
+
+
+// builds the recipe's map on its builder's tab
+fn load_recipe(app: &mut App, recipe: Recipe) {
+    let Some(index) = app.registry.index_of(&recipe.algorithm) else {
+        app.status = format!("Recipe uses '{}', which isn't in the registry", recipe.algorithm);
+        return;
+    };
+    let mut stamper = PrefabStamper::new();
+    stamper.set_prefabs(app.prefabs.prefabs().to_vec());
+
+    // a recipe with constraints is made until it meets them
+    if !recipe.constraints.is_empty() {
+        app.status = format!("Looking for a {} map with {}...", recipe.algorithm, recipe.constraints.summary());
+        let worker = SearchWorker::spawn(Search::new(recipe.clone(), stamper));
+        app.search = Some(RunningSearch { worker, recipe, attempts: 0 });
+        return;
+    }
+
+    match recipe.generate(&mut stamper) {
+        Ok(generated) => {
+            let seed = generated.seed;
+            show_generated(app, index, generated);
+            app.status = format!("Loaded recipe for {} with seed {}", recipe.algorithm, seed);
+            app.recipe = Some(recipe);
+        },
+        Err(err) => app.status = format!("Couldn't load recipe: {err}"),
+    }
+}
+
+
+// puts a map made on this thread on the tab at `index` and shows it
+fn show_generated(app: &mut App, index: usize, generated: Generated) {
+    app.tab_index = index;
+    app.setting_index = 0;
+    app.registry.entries_mut()[index].builder = generated.builder;
+    app.map_data = app.builder().report();
+    app.placements = generated.placements;
+    app.seed = Some(generated.seed);
+    app.iterations = generated.iterations;
+    // carries on the stream the map was built from
+    app.rng = map_builders::random::replace(StdRng::from_entropy());
+    app.set_gen_time(generated.duration);
+    app.update_time_charts();
+}
+
+
+// saves the current builder's setup, with the post-processing and exports of
+// any recipe it was loaded from
+fn save_recipe(app: &mut App) {
+    const RECIPE_PATH: &str = "recipe.toml";
+
+    let Some(seed) = app.seed else {
+        app.status = "This map can't be made from a seed, rebuild with b before saving".to_string();
+        return;
+    };
+    let id = app.registry.get(app.tab_index).expect("tab has a builder").info.id;
+    let mut recipe = Recipe::from_builder(id, app.builder(), seed, app.iterations);
+    if let Some(loaded) = app.recipe.as_ref().filter(|loaded| loaded.algorithm == id) {
+        recipe.post_processing = loaded.post_processing.clone();
+        recipe.export = loaded.export.clone();
+        recipe.constraints = loaded.constraints.clone();
+    }
+
+    app.status = match recipe.save(RECIPE_PATH) {
+        Ok(()) => format!("Saved {RECIPE_PATH}"),
+        Err(err) => format!("Couldn't save the recipe: {err}"),
+    };
+}
+
+
+fn export(app: &mut App) {
+    const EXPORT_PATH: &str = "map.json";
+
+    // a recipe's own targets take the place of the usual file
+    let id = app.registry.get(app.tab_index).expect("tab has a builder").info.id;
+    if let Some(recipe) = app.recipe.as_ref().filter(|recipe| recipe.algorithm == id && !recipe.export.is_empty()) {
+        let paths: Vec<&str> = recipe.export.iter().map(|target| target.path.as_str()).collect();
+        app.status = match recipe.write_exports(app.builder()) {
+            Ok(()) => format!("Exported {}", paths.join(", ")),
+            Err(err) => format!("Export failed: {err}"),
+        };
+        return;
+    }
+
+    let builder = app.builder();
+    let result = map_builders::export::write_json(EXPORT_PATH, builder.get_map(), builder.room_graph(), builder.regions(), &app.map_data);
+
+    app.status = match result {
+        Ok(()) => format!("Exported {EXPORT_PATH}"),
+        Err(err) => format!("Export failed: {err}"),
     };
 }
 
 
+// a line of help, each key in bold followed by what it does
+fn key_help(keys: &[(&'static str, &'static str)]) -> Spans<'static> {
+    let mut spans = Vec::new();
+    for (i, (key, action)) in keys.iter().enumerate() {
+        let separator = if i + 1 == keys.len() { "." } else { ", " };
+        spans.push(Span::styled(*key, Style::default().add_modifier(Modifier::BOLD)));
+        spans.push(Span::raw(format!(" {}{}", action, separator)));
+    }
+    Spans::from(spans)
+}
+
+
+// the keys that work in the app's current mode
+fn help_lines(app: &App) -> Vec<Spans<'static>> {
+    const VIEW_KEYS: [(&str, &str); 5] = [("arrows/wasd", "pan"), ("z", "zoom"), ("g", "autotile walls"), ("o", "overlays"), ("t", "theme")];
+
+    let running = app.worker.is_some() || app.search.is_some() || app.comparison.as_ref().is_some_and(Comparison::is_running);
+    if running {
+        return vec![key_help(&[("x", "cancel"), ("q", "quit")])];
+    }
+    if app.exploration.is_some() {
+        return vec![
+            key_help(&[("arrows", "walk"), ("p", "stop exploring"), ("q", "quit")]),
+            key_help(&[("wasd", "pan"), ("z", "zoom"), ("g", "autotile walls"), ("o", "overlays"), ("t", "theme")]),
+        ];
+    }
+    if app.comparison.is_some() {
+        return vec![
+            key_help(&[("b", "rebuild both from a new seed"), ("i", "iterate both"), ("r", "run both to the end"), ("c", "stop comparing"), ("q", "quit")]),
+            key_help(&[("0-9", "compare with that builder"), ("tab", "pick a setting of the right map"), ("enter", "change it")]),
+            key_help(&VIEW_KEYS),
+        ];
+    }
+    vec![
+        key_help(&[("b", "regenerate"), ("i", "iterate"), ("r", "run to the end"), ("x", "cancel"), ("q", "quit")]),
+        key_help(&[("0-9", "pick a builder"), ("[ ]", "switch builders"), ("tab", "pick a setting"), ("enter", "change it")]),
+        key_help(&[("c", "compare side by side"), ("p", "explore"), ("v", "regenerate until the constraints are met"), ("m", "benchmark")]),
+        key_help(&[("e", "export as JSON"), ("S", "save a recipe"), ("y", "use as the WFC sample"), ("f", "stamp prefabs"), ("F", "change how they're placed")]),
+        key_help(&VIEW_KEYS),
+        key_help(&[("click", "or drag on the map to paint walls and floors")]),
+    ]
+}
+
+
 fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     // define ui layout
     let data_map_chunks = Layout::default()
@@ -292,9 +1050,9 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         .direction(Direction::Vertical)
         .margin(2)
         .constraints([
-            Constraint::Percentage(10),
             Constraint::Percentage(20),
-            Constraint::Percentage(70)
+            Constraint::Percentage(15),
+            Constraint::Percentage(65)
         ].as_ref())
         .split(data_map_chunks[0]);
 
@@ -314,24 +1072,13 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
             ].as_ref())
             .split(data_chunks[1]);
 
-    // create help message
-    let (msg, style) = (vec![
-            Span::raw("Press "),
-            Span::styled("q", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" to exit, "),
-            Span::styled("b", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" to regenerate the map, "),
-            Span::styled("i", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" to iterate."),
-            ],
-            Style::default()
-        );
-
-    // render help message
-    let mut help_text = Text::from(Spans::from(msg));
-    help_text.patch_style(style);
+    // render help message, the keys for what the app is doing and the status
+    let status = app.progress.as_deref().unwrap_or(&app.status);
+    let mut help_text = Text::from(help_lines(app));
+    help_text.extend(Text::raw(status.to_string()));
     let time_render = Paragraph::new(help_text)
         .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: true })
         .block(Block::default().borders(Borders::ALL).title("Help").border_type(BorderType::Rounded));
     f.render_widget(time_render, left_chunks[0]);
 
@@ -345,26 +1092,42 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         .value_style(Style::default().fg(Color::Black).bg(Color::Yellow));
     f.render_widget(barchart, data_chunks[0]);
 
-    // render map data list
-    let map_render: Vec<ListItem> = app
-        .map_data
-        .iter()
-        .map(|(i, m)| {
-            let content = vec![Spans::from(Span::raw(format!("{}: {}", i, m)))];
-            ListItem::new(content)
-        })
-        .collect();
+    // render map data list, with a column for each side when comparing
+    let map_render: Vec<ListItem> = match &app.comparison {
+        Some(comparison) => comparison
+            .metric_rows()
+            .into_iter()
+            .map(|(name, left, right)| ListItem::new(format!("{}: {} | {}", name, left, right)))
+            .collect(),
+        None => app
+            .map_data
+            .iter()
+            .map(|m| {
+                let content = vec![Spans::from(Span::raw(format!("{}: {}", m.name, m)))];
+                ListItem::new(content)
+            })
+            .collect(),
+    };
     let messages =
         List::new(map_render).block(Block::default().borders(Borders::ALL).title("Map Data").border_type(BorderType::Rounded));
     f.render_widget(messages, data_notes_chunks[0]);
 
-    // render misc notes
-    let notes = Text::from(textwrap::fill(match app.algorithm {
-        Algorithm::BSP => app.map_builders.bsp.notes(),
-        Algorithm::Cellular => app.map_builders.cellular.notes(),
-        Algorithm::Aggregation => app.map_builders.dfa.notes(),
-        Algorithm::Drunkard => app.map_builders.drunkard.notes(),
-    }, 45));
+    // render misc notes, followed by the builder's settings, which are the
+    // right side's when comparing
+    let (builder, setting_index) = match &app.comparison {
+        Some(comparison) => (comparison.sides[1].builder.as_ref(), comparison.setting_index),
+        None => (app.builder(), app.setting_index),
+    };
+    let mut notes = Text::from(textwrap::fill(builder.notes(), 45));
+    for (i, (name, value)) in builder.settings().into_iter().enumerate() {
+        let line = format!("{} {}: {}", if i == setting_index { ">" } else { " " }, name, value);
+        let style = if i == setting_index {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        notes.extend(Text::styled(line, style));
+    }
     let notes_render =
         Paragraph::new(notes).block(Block::default().borders(Borders::ALL).title("Misc Notes").border_type(BorderType::Rounded));
     f.render_widget(notes_render, data_notes_chunks[1]);
@@ -377,8 +1140,16 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     f.render_widget(sparkline, left_chunks[1]);
 
     // render right chunks
-    let titles = app
-        .tab_titles
+    let tab_titles: Vec<String> = app
+        .registry
+        .entries()
+        .iter()
+        .map(|entry| match entry.info.key {
+            Some(key) => format!("({}) {}", key, entry.info.name),
+            None => entry.info.name.to_string(),
+        })
+        .collect();
+    let titles = tab_titles
         .iter()
         .map(|t| {
             let (first, rest) = t.split_at(1);
@@ -389,12 +1160,16 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         })
         .collect();
 
+    let selected = match &app.comparison {
+        Some(comparison) => app.registry.index_of(comparison.sides[1].info.id).unwrap_or(app.tab_index),
+        None => app.tab_index,
+    };
     let tabs = Tabs::new(titles)
         .block(Block::default()
             .borders(Borders::ALL)
             .title("Selected Algorithm")
             .border_type(BorderType::Rounded))
-        .select(app.tab_index)
+        .select(selected)
         .style(Style::default().fg(Color::White))
         .highlight_style(
             Style::default()
@@ -403,25 +1178,69 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         );
     f.render_widget(tabs, right_chunks[0]);
 
-    // render map
-    let style = Style::default().add_modifier(Modifier::BOLD);
-    let inner = {
-        let mut text = Text::from(match app.algorithm {
-            Algorithm::BSP => format!("{}", app.map_builders.bsp.get_map()),
-            Algorithm::Cellular => format!("{}", app.map_builders.cellular.get_map()),
-            Algorithm::Aggregation => format!("{}", app.map_builders.dfa.get_map()),
-            Algorithm::Drunkard => format!("{}", app.map_builders.drunkard.get_map()),
-        });
-        text.patch_style(style);
-        Paragraph::new(text)
-            .alignment(tui::layout::Alignment::Center)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .style(Style::default().fg(Color::Yellow))
-                    .title("Map")
-                    .border_type(BorderType::Rounded)
-            )
+    // render the two maps being compared, each in half of the map panel
+    if let Some(comparison) = &app.comparison {
+        let halves = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(right_chunks[1]);
+        app.map_area = Block::default().borders(Borders::ALL).inner(halves[0]);
+        app.minimap_area = None;
+        app.viewport.clamp(comparison.sides[0].map(), app.map_area);
+
+        for (side, area) in comparison.sides.iter().zip(halves.iter()) {
+            let name = format!("{}, seed {}", side.info.name, side.seed);
+            render_map(f, app, side.map(), &map_markers(side.builder.placements()), *area, &name);
+        }
+        return;
+    }
+
+    // render the part of the map in view
+    let builder = app.registry.get(app.tab_index).expect("tab has a builder").builder.as_ref();
+    let (map, markers) = match &app.snapshot {
+        Some(snapshot) => (snapshot, Vec::new()),
+        None => (builder.get_map(), map_markers(&[&app.placements[..], builder.placements()].concat())),
     };
-    f.render_widget(inner, right_chunks[1]);
-}
\ No newline at end of file
+    let map_area = Block::default().borders(Borders::ALL).inner(right_chunks[1]);
+    app.map_area = map_area;
+    app.viewport.clamp(map, map_area);
+    render_map(f, app, map, &markers, right_chunks[1], "Map");
+
+    // render the minimap over the bottom right of the map when it doesn't fit
+    app.minimap_area = None;
+    if let Some((text, width, height)) = app.viewport.minimap(map, map_area) {
+        let (width, height) = ((width + 2).min(map_area.width), (height + 2).min(map_area.height));
+        let area = Rect::new(map_area.right() - width, map_area.bottom() - height, width, height);
+        let block = Block::default().borders(Borders::ALL).title("Minimap").border_type(BorderType::Rounded);
+        app.minimap_area = Some(block.inner(area));
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(text).block(block), area);
+    }
+}
+
+
+// draws a map in a bordered block titled with `name` and what's in view
+fn render_map<B: Backend>(f: &mut Frame<B>, app: &App, map: &Map, markers: &[(usize, usize, MarkerKind)], area: Rect, name: &str) {
+    let viewport = &app.viewport;
+    let map_area = Block::default().borders(Borders::ALL).inner(area);
+    let (width, height) = viewport.visible(map, map_area);
+    let mut title = match viewport.is_cropped(map, map_area) {
+        true => format!("{} {}x{}, {} to {}, {} to {} in view ({})", name, map.width, map.height, viewport.x, (viewport.x + width).saturating_sub(1),
+            viewport.y, (viewport.y + height).saturating_sub(1), viewport.zoom.name()),
+        false => format!("{} {}x{} ({})", name, map.width, map.height, viewport.zoom.name()),
+    };
+    if viewport.overlay != Overlay::None {
+        title = format!("{}, showing {}", title, viewport.overlay.name());
+    }
+    let mut text = Text::from(viewport.lines(map, map_area, markers, &app.themes[app.theme_index], app.exploration.as_ref()));
+    text.patch_style(Style::default().add_modifier(Modifier::BOLD));
+    let inner = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::Yellow))
+                .title(title)
+                .border_type(BorderType::Rounded)
+        );
+    f.render_widget(inner, area);
+}
@@ -0,0 +1,253 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                              Analysis                                 ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::collections::VecDeque;
+
+use super::map::{Map, TileType};
+
+
+/// Areas of floor joined up and down or side to side.
+pub struct Regions {
+    /// The region of every tile by index, `None` for walls.
+    pub labels: Vec<Option<usize>>,
+    /// Tiles in each region.
+    pub sizes: Vec<usize>,
+}
+
+impl Regions {
+    pub fn new(map: &Map) -> Regions {
+        let mut labels = vec![None; map.tiles.len()];
+        let mut sizes = Vec::new();
+
+        for start in 0..map.tiles.len() {
+            if map.tiles[start] != TileType::Floor || labels[start].is_some() {
+                continue;
+            }
+            let label = sizes.len();
+            let mut stack = vec![start];
+            let mut size = 0;
+            labels[start] = Some(label);
+            while let Some(idx) = stack.pop() {
+                size += 1;
+                for next in floor_neighbours(map, idx) {
+                    if labels[next].is_none() {
+                        labels[next] = Some(label);
+                        stack.push(next);
+                    }
+                }
+            }
+            sizes.push(size);
+        }
+        Regions { labels, sizes }
+    }
+
+    pub fn count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    /// The label of the region with the most tiles.
+    pub fn largest(&self) -> Option<usize> {
+        (0..self.sizes.len()).max_by_key(|&i| self.sizes[i])
+    }
+}
+
+/// Where a player would start: the floor tile nearest the middle of the map.
+pub fn start_point(map: &Map) -> Option<(usize, usize)> {
+    let (cx, cy) = (map.width as i64 / 2, map.height as i64 / 2);
+    (0..map.tiles.len())
+        .filter(|&i| map.tiles[i] == TileType::Floor)
+        .map(|i| (i % map.width, i / map.width))
+        .min_by_key(|&(x, y)| (x as i64 - cx).pow(2) + (y as i64 - cy).pow(2))
+}
+
+/// Steps from `from` to every tile by index, moving up, down and side to
+/// side over floors. Tiles that can't be reached are `None`.
+pub fn dijkstra_map(map: &Map, from: (usize, usize)) -> Vec<Option<usize>> {
+    let mut distances = vec![None; map.tiles.len()];
+    let start = from.1 * map.width + from.0;
+    if map.tiles.get(start) != Some(&TileType::Floor) {
+        return distances;
+    }
+
+    // every step costs the same, so tiles come off the queue in order
+    distances[start] = Some(0);
+    let mut queue = VecDeque::from([start]);
+    while let Some(idx) = queue.pop_front() {
+        let next_distance = distances[idx].map(|d| d + 1);
+        for next in floor_neighbours(map, idx) {
+            if distances[next].is_none() {
+                distances[next] = next_distance;
+                queue.push_back(next);
+            }
+        }
+    }
+    distances
+}
+
+/// Where an exit would go: the floor tile furthest from `from`, and the
+/// steps to it.
+pub fn exit_point(map: &Map, from: (usize, usize)) -> Option<((usize, usize), usize)> {
+    dijkstra_map(map, from)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, distance)| Some((i, distance?)))
+        .max_by_key(|&(_, distance)| distance)
+        .map(|(i, distance)| ((i % map.width, i / map.width), distance))
+}
+
+/// Floor tiles with only one floor beside them, where a corridor stops.
+pub fn dead_ends(map: &Map) -> Vec<(usize, usize)> {
+    (0..map.tiles.len())
+        .filter(|&i| map.tiles[i] == TileType::Floor && floor_neighbours(map, i).count() == 1)
+        .map(|i| (i % map.width, i / map.width))
+        .collect()
+}
+
+/// Floor tiles that split their region in two if they were walled up, the
+/// articulation points of the floor.
+pub fn chokepoints(map: &Map) -> Vec<(usize, usize)> {
+    const UNSEEN: usize = usize::MAX;
+    // order each tile was reached in, and the earliest tile reachable from
+    // below it in the search through at most one back edge
+    let mut order = vec![UNSEEN; map.tiles.len()];
+    let mut low = vec![0; map.tiles.len()];
+    let mut is_chokepoint = vec![false; map.tiles.len()];
+    let mut counter = 0;
+
+    for root in 0..map.tiles.len() {
+        if map.tiles[root] != TileType::Floor || order[root] != UNSEEN {
+            continue;
+        }
+        order[root] = counter;
+        low[root] = counter;
+        counter += 1;
+        let mut root_children = 0;
+
+        // (tile, parent, neighbours still to look at), searched without
+        // recursion so large caves don't run out of stack
+        let mut stack = vec![(root, UNSEEN, floor_neighbours(map, root).collect::<Vec<_>>())];
+        while let Some((idx, parent, neighbours)) = stack.last_mut() {
+            let (idx, parent) = (*idx, *parent);
+            match neighbours.pop() {
+                Some(next) if order[next] == UNSEEN => {
+                    order[next] = counter;
+                    low[next] = counter;
+                    counter += 1;
+                    if idx == root {
+                        root_children += 1;
+                    }
+                    stack.push((next, idx, floor_neighbours(map, next).collect()));
+                },
+                Some(next) if next != parent => low[idx] = low[idx].min(order[next]),
+                Some(_) => {},
+                None => {
+                    stack.pop();
+                    if parent != UNSEEN {
+                        low[parent] = low[parent].min(low[idx]);
+                        if parent != root && low[idx] >= order[parent] {
+                            is_chokepoint[parent] = true;
+                        }
+                    }
+                },
+            }
+        }
+        is_chokepoint[root] = root_children > 1;
+    }
+
+    (0..map.tiles.len()).filter(|&i| is_chokepoint[i]).map(|i| (i % map.width, i / map.width)).collect()
+}
+
+// the floors up, down and to either side of a tile
+fn floor_neighbours(map: &Map, idx: usize) -> impl Iterator<Item = usize> + '_ {
+    let (x, y) = (idx % map.width, idx / map.width);
+    let neighbours = [
+        (x > 0).then(|| idx - 1),
+        (x + 1 < map.width).then(|| idx + 1),
+        (y > 0).then(|| idx - map.width),
+        (y + 1 < map.height).then(|| idx + map.width),
+    ];
+    neighbours.into_iter().flatten().filter(|&next| map.tiles[next] == TileType::Floor)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    // two rooms joined by a corridor three tiles long
+    const DUMBBELL: &str = "\
+#########
+#..###..#
+#.......#
+#..###..#
+#########";
+
+    fn map(text: &str) -> Map {
+        Map::from_text(text).unwrap()
+    }
+
+    #[test]
+    fn a_corridor_between_rooms_is_all_chokepoints() {
+        assert_eq!(chokepoints(&map(DUMBBELL)), vec![(2, 2), (3, 2), (4, 2), (5, 2), (6, 2)]);
+    }
+
+    #[test]
+    fn a_ring_has_no_chokepoints() {
+        let ring = map("#####\n#...#\n#.#.#\n#...#\n#####");
+        assert!(chokepoints(&ring).is_empty());
+    }
+
+    #[test]
+    fn chokepoints_are_the_tiles_that_split_their_region() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..20 {
+            let mut random = Map::with_size(14, 12);
+            random.tiles.iter_mut().for_each(|t| *t = if rng.gen_bool(0.6) { TileType::Floor } else { TileType::Wall });
+
+            let regions = Regions::new(&random).count();
+            let expected: Vec<(usize, usize)> = (0..random.tiles.len())
+                .filter(|&i| random.tiles[i] == TileType::Floor)
+                .filter(|&i| {
+                    let mut walled = random.clone();
+                    walled.tiles[i] = TileType::Wall;
+                    // walling up a lone tile loses a region rather than adding one
+                    Regions::new(&walled).count() > regions
+                })
+                .map(|i| (i % random.width, i / random.width))
+                .collect();
+            assert_eq!(chokepoints(&random), expected, "on\n{}", random.to_text());
+        }
+    }
+
+    #[test]
+    fn regions_only_join_up_and_down_or_side_to_side() {
+        let regions = Regions::new(&map("#.#\n.#.\n#.."));
+        assert_eq!(regions.count(), 3);
+        assert_eq!(regions.sizes[regions.largest().unwrap()], 3);
+    }
+
+    #[test]
+    fn the_exit_is_the_furthest_floor_from_the_start() {
+        let dumbbell = map(DUMBBELL);
+        let start = start_point(&dumbbell).unwrap();
+        assert_eq!(start, (4, 2));
+
+        let distances = dijkstra_map(&dumbbell, start);
+        assert_eq!(distances[dumbbell.width + 1], Some(4));
+        assert_eq!(distances[0], None);
+        let (exit, steps) = exit_point(&dumbbell, start).unwrap();
+        assert_eq!(steps, 4);
+        assert!([(1, 1), (1, 3), (7, 1), (7, 3)].contains(&exit));
+    }
+
+    #[test]
+    fn dead_ends_have_one_floor_beside_them() {
+        let spur = map("#####\n#...#\n##.##\n#####");
+        assert_eq!(dead_ends(&spur), vec![(1, 1), (3, 1), (2, 2)]);
+    }
+}
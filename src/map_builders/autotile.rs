@@ -0,0 +1,176 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                             Autotiling                                ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use serde::Serialize;
+
+use super::map::{Map, TileType};
+
+/// Bits of the 4-neighbour mask.
+pub const NORTH: u8 = 1;
+pub const EAST: u8 = 2;
+pub const SOUTH: u8 = 4;
+pub const WEST: u8 = 8;
+
+// offsets of the 8 neighbours, clockwise from north, in the order of their
+// bits in the 8-neighbour mask
+const NEIGHBOURS: [(i32, i32); 8] = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+
+// indexed by the 4-neighbour mask
+const BOX_GLYPHS: [char; 16] = ['■', '│', '─', '└', '│', '│', '┌', '├', '─', '┘', '─', '┴', '┐', '┤', '┬', '┼'];
+
+
+/// Neighbour masks of every tile, row by row, for renderers that pick their
+/// own wall tiles. Floors are 0 in both.
+#[derive(Serialize)]
+pub struct WallMasks {
+    /// See `mask4`.
+    pub mask4: Vec<Vec<u8>>,
+    /// See `mask8`.
+    pub mask8: Vec<Vec<u8>>,
+}
+
+impl WallMasks {
+    pub fn new(map: &Map) -> WallMasks {
+        let rows = |mask: fn(&Map, usize, usize) -> u8| {
+            (0..map.height).map(|y| (0..map.width).map(|x| mask(map, x, y)).collect()).collect()
+        };
+        WallMasks { mask4: rows(mask4), mask8: rows(mask8) }
+    }
+}
+
+// off the edge of the map is solid rock
+fn is_wall(map: &Map, x: i32, y: i32) -> bool {
+    if x < 0 || y < 0 || x as usize >= map.width || y as usize >= map.height {
+        return true;
+    }
+    map.get(x as usize, y as usize) == Some(&TileType::Wall)
+}
+
+/// Which of the 8 tiles around a wall are walls as well: 1 north, then
+/// clockwise through 2 north east to 128 north west. Off the map counts as
+/// wall. Floors are 0.
+pub fn mask8(map: &Map, x: usize, y: usize) -> u8 {
+    if !is_wall(map, x as i32, y as i32) {
+        return 0;
+    }
+    NEIGHBOURS
+        .iter()
+        .enumerate()
+        .filter(|(_, (dx, dy))| is_wall(map, x as i32 + dx, y as i32 + dy))
+        .fold(0, |mask, (bit, _)| mask | 1 << bit)
+}
+
+/// Whether a tile is a wall with no floor anywhere around it.
+pub fn is_hidden(map: &Map, x: usize, y: usize) -> bool {
+    is_wall(map, x as i32, y as i32) && mask8(map, x, y) == u8::MAX
+}
+
+/// Which of the 4 tiles beside a wall are walls that can be seen, using
+/// `NORTH`, `EAST`, `SOUTH` and `WEST`. Hidden walls and floors are 0.
+pub fn mask4(map: &Map, x: usize, y: usize) -> u8 {
+    if !is_wall(map, x as i32, y as i32) || is_hidden(map, x, y) {
+        return 0;
+    }
+    let shown = |dx: i32, dy: i32| {
+        let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+        let on_map = nx >= 0 && ny >= 0 && (nx as usize) < map.width && (ny as usize) < map.height;
+        on_map && is_wall(map, nx, ny) && !is_hidden(map, nx as usize, ny as usize)
+    };
+    [(NORTH, 0, -1), (EAST, 1, 0), (SOUTH, 0, 1), (WEST, -1, 0)]
+        .iter()
+        .filter(|(_, dx, dy)| shown(*dx, *dy))
+        .fold(0, |mask, (bit, _, _)| mask | bit)
+}
+
+/// The character a tile is drawn with: a box-drawing line joining each wall
+/// to the walls beside it, and a space for floors and hidden walls. Walls
+/// standing alone are `■`.
+pub fn glyph(map: &Map, x: usize, y: usize) -> char {
+    match map.get(x, y) {
+        Some(TileType::Wall) if !is_hidden(map, x, y) => BOX_GLYPHS[mask4(map, x, y) as usize],
+        _ => ' ',
+    }
+}
+
+/// Writes the map as text like `Map::to_text`, with walls drawn by `glyph`.
+pub fn to_text(map: &Map) -> String {
+    let mut text = String::with_capacity((map.width + 1) * map.height);
+    for y in 0..map.height {
+        text.extend((0..map.width).map(|x| glyph(map, x, y)));
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RING: &str = "###\n#.#\n###";
+
+    // the arms each glyph draws, as a 4-neighbour mask
+    fn arms(glyph: char) -> u8 {
+        match glyph {
+            '│' => NORTH | SOUTH,
+            '─' => EAST | WEST,
+            '└' => NORTH | EAST,
+            '┌' => EAST | SOUTH,
+            '┘' => NORTH | WEST,
+            '┐' => SOUTH | WEST,
+            '├' => NORTH | EAST | SOUTH,
+            '┤' => NORTH | SOUTH | WEST,
+            '┴' => NORTH | EAST | WEST,
+            '┬' => EAST | SOUTH | WEST,
+            '┼' => NORTH | EAST | SOUTH | WEST,
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn glyphs_join_the_walls_in_their_mask() {
+        assert_eq!(BOX_GLYPHS[0], '■');
+        for mask in 1..16u8 {
+            let glyph = BOX_GLYPHS[mask as usize];
+            if mask.count_ones() == 1 {
+                // a wall joined on one side is drawn as a straight line
+                // through that side
+                assert!(arms(glyph) & mask == mask && arms(glyph).count_ones() == 2, "{} {}", mask, glyph);
+            } else {
+                assert_eq!(arms(glyph), mask, "{}", glyph);
+            }
+        }
+    }
+
+    #[test]
+    fn a_ring_of_walls_is_drawn_as_a_box() {
+        let map = Map::from_text(RING).unwrap();
+        assert_eq!(to_text(&map), "┌─┐\n│ │\n└─┘\n");
+        assert_eq!(mask4(&map, 1, 1), 0);
+        assert_eq!(mask8(&map, 1, 1), 0);
+        // everything but the floor to the south east
+        assert_eq!(mask8(&map, 0, 0), !(1 << 3));
+    }
+
+    #[test]
+    fn solid_rock_is_hidden() {
+        let map = Map::from_text("#####\n#####\n#####\n####.").unwrap();
+        assert!(is_hidden(&map, 0, 0) && is_hidden(&map, 2, 1));
+        assert_eq!(glyph(&map, 2, 1), ' ');
+        // walls beside the floor, even diagonally, can be seen
+        assert!(!is_hidden(&map, 3, 2) && !is_hidden(&map, 3, 3) && !is_hidden(&map, 4, 2));
+        assert_eq!(to_text(&map), "     \n     \n   ┌─\n   │ \n");
+    }
+
+    #[test]
+    fn masks_serialize_row_by_row() {
+        let map = Map::from_text(RING).unwrap();
+        let value = serde_json::to_value(WallMasks::new(&map)).unwrap();
+        assert_eq!(value["mask4"], serde_json::json!([[6, 10, 12], [5, 0, 5], [3, 10, 9]]));
+        assert_eq!(value["mask8"][0][0], 247);
+        assert_eq!(value["mask8"][1][1], 0);
+        assert_eq!(value.as_object().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,306 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                             Benchmark                                 ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::fmt::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::MapBuilder;
+use super::prefabs::PrefabStamper;
+use super::quality::Quality;
+use super::random;
+use super::recipe::Recipe;
+use super::run_limit;
+
+
+pub struct BenchmarkConfig {
+    pub runs: usize,
+    /// Run `n` is seeded with `first_seed + n`.
+    pub first_seed: u64,
+    /// Iterations after each build, stopping early once the builder's
+    /// progress reaches 1. Left out, it is the run limit of the map.
+    pub max_iterations: Option<usize>,
+    /// Threads to spread the runs over. Timings are less steady with more.
+    pub threads: usize,
+}
+
+impl BenchmarkConfig {
+    pub fn new() -> BenchmarkConfig {
+        BenchmarkConfig { runs: 100, first_seed: 0, max_iterations: None, threads: 1 }
+    }
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RunResult {
+    pub seed: u64,
+    #[serde(rename = "time_us", serialize_with = "micros")]
+    pub duration: Duration,
+    pub iterations: usize,
+    #[serde(flatten)]
+    pub quality: Quality,
+}
+
+/// Spread of one figure over every run.
+#[derive(Clone, Debug, Serialize)]
+pub struct Summary {
+    pub min: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    /// Returns `None` if there are no values.
+    pub fn from_values(values: &[f64]) -> Option<Summary> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let len = sorted.len();
+
+        // nearest rank, so the p95 of a few runs is the slowest rather than
+        // something in between
+        let rank = |p: f64| sorted[((p * len as f64).ceil() as usize).clamp(1, len) - 1];
+        let median = if len.is_multiple_of(2) {
+            (sorted[len / 2 - 1] + sorted[len / 2]) / 2.0
+        } else {
+            sorted[len / 2]
+        };
+
+        Some(Summary {
+            min: sorted[0],
+            mean: sorted.iter().sum::<f64>() / len as f64,
+            median,
+            p95: rank(0.95),
+            max: sorted[len - 1],
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchmarkReport {
+    pub builder: String,
+    pub first_seed: u64,
+    pub threads: usize,
+    /// In microseconds.
+    pub time: Option<Summary>,
+    pub floor_percent: Option<Summary>,
+    pub regions: Option<Summary>,
+    pub largest_region_percent: Option<Summary>,
+    pub mean_corridor_width: Option<Summary>,
+    pub dead_ends: Option<Summary>,
+    pub enclosed_pillars: Option<Summary>,
+    pub rooms: Option<Summary>,
+    pub mean_path_length: Option<Summary>,
+    pub compactness: Option<Summary>,
+    pub runs: Vec<RunResult>,
+}
+
+impl BenchmarkReport {
+    fn new(builder: &str, config: &BenchmarkConfig, runs: Vec<RunResult>) -> BenchmarkReport {
+        let values = |f: fn(&RunResult) -> Option<f64>| -> Vec<f64> { runs.iter().filter_map(f).collect() };
+
+        BenchmarkReport {
+            builder: builder.to_string(),
+            first_seed: config.first_seed,
+            threads: config.threads,
+            time: Summary::from_values(&values(|r| Some(r.duration.as_secs_f64() * 1_000_000.0))),
+            floor_percent: Summary::from_values(&values(|r| Some(r.quality.floor_percent))),
+            regions: Summary::from_values(&values(|r| Some(r.quality.regions as f64))),
+            largest_region_percent: Summary::from_values(&values(|r| Some(r.quality.largest_region_percent))),
+            mean_corridor_width: Summary::from_values(&values(|r| Some(r.quality.mean_corridor_width))),
+            dead_ends: Summary::from_values(&values(|r| Some(r.quality.dead_ends as f64))),
+            enclosed_pillars: Summary::from_values(&values(|r| Some(r.quality.enclosed_pillars as f64))),
+            rooms: Summary::from_values(&values(|r| r.quality.rooms.map(|n| n as f64))),
+            mean_path_length: Summary::from_values(&values(|r| Some(r.quality.mean_path_length))),
+            compactness: Summary::from_values(&values(|r| Some(r.quality.compactness))),
+            runs,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// One row per figure with its summary, leaving out figures no run had.
+    pub fn summary_csv(&self) -> String {
+        let mut csv = String::from("builder,figure,min,mean,median,p95,max\n");
+        let figures = [
+            ("time_us", &self.time),
+            ("floor_percent", &self.floor_percent),
+            ("regions", &self.regions),
+            ("largest_region_percent", &self.largest_region_percent),
+            ("mean_corridor_width", &self.mean_corridor_width),
+            ("dead_ends", &self.dead_ends),
+            ("enclosed_pillars", &self.enclosed_pillars),
+            ("rooms", &self.rooms),
+            ("mean_path_length", &self.mean_path_length),
+            ("compactness", &self.compactness),
+        ];
+
+        for (name, summary) in figures {
+            if let Some(s) = summary {
+                let _ = writeln!(csv, "{},{},{:.2},{:.2},{:.2},{:.2},{:.2}", self.builder, name, s.min, s.mean, s.median, s.p95, s.max);
+            }
+        }
+        csv
+    }
+
+    /// One row per run, with an empty room count for builders without rooms.
+    /// Room sizes are left to the JSON report.
+    pub fn runs_csv(&self) -> String {
+        let mut csv = String::from(
+            "builder,seed,time_us,iterations,floor_percent,regions,largest_region_percent,mean_corridor_width,dead_ends,enclosed_pillars,rooms,mean_path_length,compactness\n",
+        );
+        for run in &self.runs {
+            let q = &run.quality;
+            let rooms = q.rooms.map_or(String::new(), |n| n.to_string());
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{:.2},{},{:.2},{:.2},{},{},{},{:.2},{:.3}",
+                self.builder,
+                run.seed,
+                run.duration.as_micros(),
+                run.iterations,
+                q.floor_percent,
+                q.regions,
+                q.largest_region_percent,
+                q.mean_corridor_width,
+                q.dead_ends,
+                q.enclosed_pillars,
+                rooms,
+                q.mean_path_length,
+                q.compactness,
+            );
+        }
+        csv
+    }
+}
+
+/// Builds a fresh map from `create` once per seed and reports how long each
+/// took and what came out.
+pub fn run<F>(name: &str, create: F, config: &BenchmarkConfig) -> BenchmarkReport
+where
+    F: Fn() -> Box<dyn MapBuilder> + Sync,
+{
+    let runs = run_seeds(config, |seed| run_once(create(), seed, config.max_iterations));
+    BenchmarkReport::new(name, config, runs)
+}
+
+/// Makes a recipe's map once per seed the way `Recipe::generate` does,
+/// post-processing and prefabs included, and reports how long each took and
+/// what came out. The recipe's own seed is ignored, and its iterations are
+/// used in place of the config's.
+pub fn run_recipe(recipe: &Recipe, stamper: &PrefabStamper, config: &BenchmarkConfig) -> Result<BenchmarkReport, String> {
+    // checked once here, so every run can expect it to work
+    recipe.builder()?;
+
+    let runs = run_seeds(config, |seed| {
+        let mut recipe = recipe.clone();
+        recipe.seed = Some(seed);
+        // every run starts from the same stamper settings
+        let generated = recipe.generate(&mut stamper.clone()).expect("recipe was checked");
+        RunResult { seed, duration: generated.duration, iterations: generated.iterations, quality: generated.builder.quality() }
+    });
+    Ok(BenchmarkReport::new(&recipe.algorithm, config, runs))
+}
+
+// calls `run` once per seed, spread over the config's threads
+fn run_seeds<F>(config: &BenchmarkConfig, run: F) -> Vec<RunResult>
+where
+    F: Fn(u64) -> RunResult + Sync,
+{
+    let seeds: Vec<u64> = (0..config.runs as u64).map(|n| config.first_seed.wrapping_add(n)).collect();
+    let threads = config.threads.clamp(1, seeds.len().max(1));
+    let chunk = seeds.len().div_ceil(threads).max(1);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = seeds
+            .chunks(chunk)
+            .map(|seeds| {
+                let run = &run;
+                scope.spawn(move || seeds.iter().map(|&seed| run(seed)).collect::<Vec<_>>())
+            })
+            .collect();
+
+        handles.into_iter().flat_map(|handle| handle.join().expect("benchmark thread panicked")).collect()
+    })
+}
+
+fn run_once(mut builder: Box<dyn MapBuilder>, seed: u64, max_iterations: Option<usize>) -> RunResult {
+    random::seed(seed);
+
+    let start = Instant::now();
+    builder.build();
+    let max_iterations = max_iterations.unwrap_or_else(|| run_limit(builder.get_map()));
+    let mut iterations = 0;
+    while iterations < max_iterations && !builder.progress().is_some_and(|p| p >= 1.0) {
+        builder.iterate();
+        iterations += 1;
+    }
+    let duration = start.elapsed();
+
+    RunResult { seed, duration, iterations, quality: builder.quality() }
+}
+
+fn micros<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_micros())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_builders::drunkard::DrunkardBuilder;
+
+    #[test]
+    fn nothing_to_summarize_gives_no_summary() {
+        assert!(Summary::from_values(&[]).is_none());
+    }
+
+    #[test]
+    fn summaries_sort_the_values_first() {
+        let s = Summary::from_values(&[4.0, 1.0, 3.0, 2.0]).unwrap();
+        assert_eq!((s.min, s.mean, s.median, s.p95, s.max), (1.0, 2.5, 2.5, 4.0, 4.0));
+
+        let s = Summary::from_values(&[5.0, 1.0, 3.0]).unwrap();
+        assert_eq!(s.median, 3.0);
+    }
+
+    #[test]
+    fn p95_is_the_nearest_rank() {
+        let values: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!(Summary::from_values(&values).unwrap().p95, 95.0);
+
+        let values: Vec<f64> = (1..=10).map(f64::from).collect();
+        assert_eq!(Summary::from_values(&values).unwrap().p95, 10.0);
+
+        assert_eq!(Summary::from_values(&[7.0]).unwrap().p95, 7.0);
+    }
+
+    #[test]
+    fn runs_match_their_seeds_however_many_threads_share_them() {
+        let config = |threads| BenchmarkConfig { runs: 6, first_seed: 40, max_iterations: None, threads };
+        let create = || Box::new(DrunkardBuilder::new()) as Box<dyn MapBuilder>;
+        let one = run("drunkard", create, &config(1));
+        let three = run("drunkard", create, &config(3));
+
+        let seeds: Vec<u64> = three.runs.iter().map(|r| r.seed).collect();
+        assert_eq!(seeds, (40..46).collect::<Vec<_>>());
+        for (a, b) in one.runs.iter().zip(&three.runs) {
+            assert_eq!(a.quality, b.quality);
+        }
+    }
+}
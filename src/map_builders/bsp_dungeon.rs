@@ -5,40 +5,27 @@
 /////////// ------------------------------------------------------///////////
 
 use super::MapBuilder;
+use super::metrics::Metric;
 use super::Map;
 use super::map::TileType;
+use super::corridors::{self, CorridorConfig};
+use super::room_shapes::{self, RoomShapeTable};
+use super::rooms::{Corridor, RoomGraph};
+use super::settings;
 use rand::Rng;
+use super::random::{self, BuilderRng};
 
-#[derive(Default, Copy, Clone)]
-pub struct Rect {
-    pub x1 : i32,
-    pub x2 : i32,
-    pub y1 : i32,
-    pub y2 : i32
-}
-
-impl Rect {
-    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Rect {
-        Rect{x1: x, y1: y, x2: x + w, y2: y + h}
-    }
-
-    // Returns true if this overlaps with other
-    pub fn intersect(&self, other:&Rect) -> bool {
-        self.x1 <= other.x2 && self.x2 >= other.x1 && self.y1 <= other.y2 && self.y2 >= other.y1
-    }
-
-    pub fn center(&self) -> (i32, i32) {
-        ((self.x1 + self.x2)/2, (self.y1 + self.y2)/2)
-    }
-}
+pub use super::rooms::Rect;
 
 pub struct BSPDungeonBuilder {
     map : Map,
-    rooms: Vec<Rect>,
+    graph: RoomGraph,
     rects: Vec<Rect>,
+    corridor_config: CorridorConfig,
+    room_shapes: RoomShapeTable,
 }
 
-impl<'a> MapBuilder<'a> for BSPDungeonBuilder {
+impl MapBuilder for BSPDungeonBuilder {
     fn build(&mut self) {
         self.clear();
         self.build();
@@ -46,14 +33,50 @@ impl<'a> MapBuilder<'a> for BSPDungeonBuilder {
 
     fn iterate(&mut self) {}
 
-    fn get_map(&self) -> Map {
-        self.map.clone()
+    fn get_map(&self) -> &Map {
+        &self.map
+    }
+
+    fn get_map_mut(&mut self) -> &mut Map {
+        &mut self.map
     }
 
-    fn update_map_data(&self, map_data: &mut Vec<(&'a str, String)>) {
-        map_data.clear();
-        map_data.push(("Name", "Binary Space Partitioning".to_string()));
-        map_data.push(("Number of Rooms", format!("{}", self.rooms.len())));
+    fn metrics(&self) -> Vec<Metric> {
+        vec![
+            Metric::text("Name", "Binary Space Partitioning"),
+            Metric::integer("Number of Rooms", self.graph.rooms.len()),
+            Metric::integer("Number of Corridors", self.graph.corridors.len()),
+            Metric::integer("Dead-End Rooms", self.graph.dead_ends().len()),
+        ]
+    }
+
+    fn room_graph(&self) -> Option<&RoomGraph> {
+        Some(&self.graph)
+    }
+
+    fn settings(&self) -> Vec<(&'static str, String)> {
+        let mut settings = vec![("Room Shapes", self.room_shapes.name.clone())];
+        settings.extend(self.corridor_config.settings());
+        settings
+    }
+
+    fn cycle_setting(&mut self, index: usize) {
+        match index {
+            0 => self.room_shapes = self.room_shapes.next_preset(),
+            _ => self.corridor_config.cycle_setting(index - 1),
+        }
+    }
+
+    fn set_setting(&mut self, index: usize, value: &str) -> Result<(), String> {
+        match index {
+            0 => self.room_shapes = settings::choice("Room Shapes", value, &RoomShapeTable::presets(), |table| table.name.clone())?,
+            _ => self.corridor_config.set_setting(index - 1, value)?,
+        }
+        Ok(())
+    }
+
+    fn progress(&self) -> Option<f64> {
+        Some(1.0)
     }
 
     fn notes(&self) -> &str {
@@ -65,19 +88,42 @@ impl BSPDungeonBuilder {
     pub fn new() -> BSPDungeonBuilder {
         BSPDungeonBuilder {
             map : Map::new(),
-            rooms: Vec::new(),
+            graph: RoomGraph::default(),
             rects: Vec::new(),
+            corridor_config: CorridorConfig::default(),
+            room_shapes: RoomShapeTable::default(),
         }
     }
 
     pub fn clear(&mut self) {
-        self.map = Map::new();
-        self.rooms = Vec::new();
+        self.map = Map::with_size(self.map.width, self.map.height);
+        self.graph.clear();
         self.rects = Vec::new();
     }
 
+    /// The rooms placed by the last build, sorted by their left edge.
+    pub fn rooms(&self) -> &[Rect] {
+        &self.graph.rooms
+    }
+
+    /// The corridors carved by the last build. Their `from` and `to`
+    /// fields index into `rooms`.
+    pub fn corridors(&self) -> &[Corridor] {
+        &self.graph.corridors
+    }
+
+    /// Sets how rooms are joined on the next build.
+    pub fn set_corridor_config(&mut self, config: CorridorConfig) {
+        self.corridor_config = config;
+    }
+
+    /// Sets the shapes rooms are rolled from on the next build.
+    pub fn set_room_shapes(&mut self, shapes: RoomShapeTable) {
+        self.room_shapes = shapes;
+    }
+
     pub fn build(&mut self) {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
 
         self.rects.clear();
         self.rects.push( Rect::new(2, 2, self.map.width as i32 - 5, self.map.height as i32 - 5) ); // Start with a single map-sized rectangle
@@ -92,25 +138,17 @@ impl BSPDungeonBuilder {
             let candidate = self.get_random_sub_rect(rect, &mut rng);
     
             if self.is_possible(candidate) {
-                self.apply_room_to_map(&candidate);
-                self.rooms.push(candidate);
+                self.apply_room_to_map(&candidate, &mut rng);
+                self.graph.rooms.push(candidate);
                 self.add_subrects(rect);
             }
     
             n_rooms += 1;
         }
 
-        self.rooms.sort_by(|a,b| a.x1.cmp(&b.x1) );
+        self.graph.rooms.sort_by_key(|room| room.x1);
 
-        for i in 0..self.rooms.len()-1 {
-            let room = self.rooms[i];
-            let next_room = self.rooms[i+1];
-            let start_x = room.x1 + (rng.gen_range(1..i32::abs(room.x1 - room.x2))-1);
-            let start_y = room.y1 + (rng.gen_range(1..i32::abs(room.y1 - room.y2))-1);
-            let end_x = next_room.x1 + (rng.gen_range(1..i32::abs(next_room.x1 - next_room.x2))-1);
-            let end_y = next_room.y1 + (rng.gen_range(1..i32::abs(next_room.y1 - next_room.y2))-1);
-            self.draw_corridor(start_x, start_y, end_x, end_y);
-        }
+        corridors::connect_rooms(&mut self.map, &mut self.graph, &self.corridor_config, &mut rng);
     }
 
     fn add_subrects(&mut self, rect : Rect) {
@@ -125,19 +163,21 @@ impl BSPDungeonBuilder {
         self.rects.push(Rect::new( rect.x1 + half_width, rect.y1 + half_height, half_width, half_height ));
     }
 
-    fn get_random_rect(&mut self, rng : &mut rand::rngs::ThreadRng) -> Rect {
+    fn get_random_rect(&mut self, rng : &mut BuilderRng) -> Rect {
         if self.rects.len() == 1 { return self.rects[0]; }
         let idx = (rng.gen_range(1..(self.rects.len() as i32))-1) as usize;
         self.rects[idx]
     }
 
-    fn get_random_sub_rect(&self, rect : Rect, rng : &mut rand::rngs::ThreadRng) -> Rect {
+    fn get_random_sub_rect(&self, rect : Rect, rng : &mut BuilderRng) -> Rect {
         let mut result = rect;
         let rect_width = i32::abs(rect.x1 - rect.x2);
         let rect_height = i32::abs(rect.y1 - rect.y2);
     
-        let w = i32::max(3, rng.gen_range(1..i32::min(rect_width, 10))-1) + 1;
-        let h = i32::max(3, rng.gen_range(1..i32::min(rect_height, 10))-1) + 1;
+        // slivers left by a split leave nothing to pick from, so they get
+        // the smallest room instead
+        let w = i32::max(3, rng.gen_range(1..i32::clamp(rect_width, 2, 10))-1) + 1;
+        let h = i32::max(3, rng.gen_range(1..i32::clamp(rect_height, 2, 10))-1) + 1;
     
         result.x1 += rng.gen_range(1..6)-1;
         result.y1 += rng.gen_range(1..6)-1;
@@ -174,33 +214,9 @@ impl BSPDungeonBuilder {
         can_build
     }
 
-    fn apply_room_to_map(&mut self, room : &Rect) {
-        for y in room.y1 +1 ..= room.y2 {
-            for x in room.x1 + 1 ..= room.x2 {
-                let idx = self.map.xy_idx(x, y);
-                self.map.tiles[idx] = TileType::Floor;
-            }
-        }
-    }
-
-    fn draw_corridor(&mut self, x1:i32, y1:i32, x2:i32, y2:i32) {
-        let mut x = x1;
-        let mut y = y1;
-    
-        while x != x2 || y != y2 {
-            if x < x2 {
-                x += 1;
-            } else if x > x2{
-                x -= 1;
-            } else if y < y2{
-                y += 1;
-            } else if y > y2{
-                y -= 1;
-            }
-    
-            let idx = self.map.xy_idx(x, y);
-            self.map.tiles[idx] = TileType::Floor;
-        }
+    fn apply_room_to_map(&mut self, room : &Rect, rng : &mut BuilderRng) {
+        let shape = self.room_shapes.roll(rng);
+        room_shapes::carve_room(&mut self.map, room, shape, rng);
     }
 }
 
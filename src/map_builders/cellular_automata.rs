@@ -4,7 +4,11 @@
 ///                                                                       ///
 /////////// ------------------------------------------------------///////////
 
+use rand::Rng;
+
 use super::MapBuilder;
+use super::metrics::Metric;
+use super::random;
 use super::Map;
 use super::map::TileType;
 
@@ -14,7 +18,7 @@ pub struct CellularAutomataBuilder {
     iterations: i32,
 }
 
-impl<'a> MapBuilder<'a> for CellularAutomataBuilder {
+impl MapBuilder for CellularAutomataBuilder {
     fn build(&mut self) {
         self.scramble();
     }
@@ -26,15 +30,24 @@ impl<'a> MapBuilder<'a> for CellularAutomataBuilder {
         }
     }
 
-    fn get_map(&self) -> Map {
-        self.map.clone()
+    fn get_map(&self) -> &Map {
+        &self.map
+    }
+
+    fn get_map_mut(&mut self) -> &mut Map {
+        &mut self.map
+    }
+
+    fn metrics(&self) -> Vec<Metric> {
+        vec![
+            Metric::text("Name", "Cellular Automata"),
+            Metric::integer("Max Iterations", 16),
+            Metric::integer("Iteration", self.iterations),
+        ]
     }
 
-    fn update_map_data(&self, map_data: &mut Vec<(&'a str, String)>) {
-        map_data.clear();
-        map_data.push(("Name", "Cellular Automata".to_string()));
-        map_data.push(("Max Iterations", format!("{}", 16)));
-        map_data.push(("Iteration", format!("{}", self.iterations)));
+    fn progress(&self) -> Option<f64> {
+        Some(self.iterations as f64 / 16.0)
     }
 
     fn notes(&self) -> &str {
@@ -53,11 +66,12 @@ impl CellularAutomataBuilder {
     pub fn scramble(&mut self) {
         self.map.tiles.clear();
         self.iterations = 0;
+        let mut rng = random::rng();
 
         for _r in 0..self.map.height {
             for _c in 0..self.map.width {
                 self.map.tiles.push(
-                    match fastrand::i32(0..2) {
+                    match rng.gen_range(0..2) {
                         0 => TileType::Floor,
                         _ => TileType::Wall
                     });
@@ -0,0 +1,273 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                             Constraints                               ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use super::analysis;
+use super::prefabs::PrefabStamper;
+use super::random;
+use super::recipe::{Generated, Recipe};
+use super::worker::Background;
+use super::MapBuilder;
+
+
+/// Requirements a map has to meet once it is built, iterated and
+/// post-processed. Any left out aren't checked.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Constraints {
+    /// The least and most of the tiles that can be floor, from 0 to 100.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub floor_percent: Option<(f64, f64)>,
+    /// Whether every floor tile has to be reachable from every other.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub single_region: bool,
+    /// Maps from builders without rooms never meet this.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_rooms: Option<usize>,
+    /// The fewest steps from the start point to the exit, the floor tile
+    /// furthest from it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_exit_distance: Option<usize>,
+    /// Maps to make before giving up.
+    pub max_attempts: usize,
+}
+
+impl Constraints {
+    pub fn new() -> Constraints {
+        Constraints { floor_percent: None, single_region: false, min_rooms: None, min_exit_distance: None, max_attempts: 100 }
+    }
+
+    /// Whether there's nothing to check, so any map will do.
+    pub fn is_empty(&self) -> bool {
+        self.floor_percent.is_none() && !self.single_region && self.min_rooms.is_none() && self.min_exit_distance.is_none()
+    }
+
+    /// How the builder's map falls short, empty if it meets every constraint.
+    pub fn failures(&self, builder: &dyn MapBuilder) -> Vec<String> {
+        let map = builder.get_map();
+        let quality = builder.quality();
+        let mut failures = Vec::new();
+
+        if let Some((min, max)) = self.floor_percent.filter(|(min, max)| !(*min..=*max).contains(&quality.floor_percent)) {
+            failures.push(format!("floor is {:.1}%, not {}-{}%", quality.floor_percent, min, max));
+        }
+        if self.single_region && quality.regions != 1 {
+            failures.push(format!("floor is in {} regions", quality.regions));
+        }
+        if let Some(min) = self.min_rooms {
+            match quality.rooms {
+                Some(rooms) if rooms < min => failures.push(format!("{rooms} rooms, fewer than {min}")),
+                None => failures.push("the builder doesn't place rooms".to_string()),
+                _ => {},
+            }
+        }
+        if let Some(min) = self.min_exit_distance {
+            let exit = analysis::start_point(map).and_then(|start| analysis::exit_point(map, start));
+            match exit {
+                Some((_, steps)) if steps < min => failures.push(format!("the exit is {steps} steps from the start, fewer than {min}")),
+                None => failures.push("there's no floor to start from".to_string()),
+                _ => {},
+            }
+        }
+        failures
+    }
+
+    /// The constraints in a few words, such as "floor 40-60%, one region".
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some((min, max)) = self.floor_percent {
+            parts.push(format!("floor {min}-{max}%"));
+        }
+        if self.single_region {
+            parts.push("one region".to_string());
+        }
+        if let Some(min) = self.min_rooms {
+            parts.push(format!("{min}+ rooms"));
+        }
+        if let Some(min) = self.min_exit_distance {
+            parts.push(format!("exit {min}+ steps away"));
+        }
+        if parts.is_empty() { "no constraints".to_string() } else { parts.join(", ") }
+    }
+}
+
+impl Default for Constraints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Makes a recipe's map from one seed after another until one meets the
+/// recipe's constraints or its attempts run out. Attempt `n` is seeded with
+/// the first seed plus `n`, the first being the recipe's own or a random one.
+pub struct Search {
+    recipe: Recipe,
+    stamper: PrefabStamper,
+    first_seed: u64,
+    /// Maps made so far.
+    pub attempts: usize,
+    /// How the last map made fell short.
+    pub failures: Vec<String>,
+}
+
+impl Search {
+    pub fn new(recipe: Recipe, stamper: PrefabStamper) -> Search {
+        let first_seed = recipe.seed.unwrap_or_else(rand::random);
+        Search { recipe, stamper, first_seed, attempts: 0, failures: Vec::new() }
+    }
+
+    pub fn recipe(&self) -> &Recipe {
+        &self.recipe
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.attempts >= self.recipe.constraints.max_attempts.max(1)
+    }
+
+    /// Makes the next map, returning it if it meets every constraint.
+    pub fn attempt(&mut self) -> Result<Option<Generated>, String> {
+        let mut recipe = self.recipe.clone();
+        recipe.seed = Some(self.first_seed.wrapping_add(self.attempts as u64));
+        let generated = recipe.generate(&mut self.stamper)?;
+        self.attempts += 1;
+
+        self.failures = self.recipe.constraints.failures(generated.builder.as_ref());
+        Ok(self.failures.is_empty().then_some(generated))
+    }
+
+    /// Makes maps until one meets every constraint, or says why the last one
+    /// didn't once the attempts run out.
+    pub fn run(&mut self) -> Result<Generated, String> {
+        while !self.is_exhausted() {
+            if let Some(generated) = self.attempt()? {
+                return Ok(generated);
+            }
+        }
+        Err(self.gave_up())
+    }
+
+    fn gave_up(&self) -> String {
+        format!("no map met the constraints in {} attempts, the last failing with: {}", self.attempts, self.failures.join("; "))
+    }
+}
+
+pub enum SearchProgress {
+    /// Sent after every map that falls short, with how it did.
+    Attempt { attempts: usize, failures: Vec<String> },
+    /// Sent once a map meets every constraint.
+    Found(Box<Found>),
+    /// Sent when the attempts run out, a map can't be made at all, or the
+    /// search panicked.
+    GaveUp(String),
+}
+
+pub struct Found {
+    pub generated: Generated,
+    /// The generator the map was made from, ready for the next one.
+    pub rng: StdRng,
+    pub attempts: usize,
+}
+
+/// Runs a search on its own thread, so slow builders don't hold up whoever
+/// is waiting on it.
+pub struct SearchWorker {
+    thread: Background<SearchProgress>,
+}
+
+impl SearchWorker {
+    pub fn spawn(search: Search) -> SearchWorker {
+        SearchWorker { thread: Background::spawn(move |cancel, sender| run_search(search, cancel, sender)) }
+    }
+
+    /// Stops the search once the map being made is done.
+    pub fn cancel(&self) {
+        self.thread.cancel();
+    }
+
+    /// Every event sent since the last call, without waiting, ending with a
+    /// `SearchProgress::GaveUp` if the thread died without saying why.
+    pub fn poll(&mut self) -> Vec<SearchProgress> {
+        self.thread.poll(SearchProgress::GaveUp)
+    }
+}
+
+fn run_search(mut search: Search, cancel: &AtomicBool, sender: &Sender<SearchProgress>) {
+    while !search.is_exhausted() {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        let event = match search.attempt() {
+            Ok(Some(generated)) => {
+                // the thread ends here, so what it is left with doesn't matter
+                let rng = random::replace(StdRng::seed_from_u64(0));
+                let _ = sender.send(SearchProgress::Found(Box::new(Found { generated, rng, attempts: search.attempts })));
+                return;
+            },
+            Ok(None) => SearchProgress::Attempt { attempts: search.attempts, failures: search.failures.clone() },
+            Err(err) => {
+                let _ = sender.send(SearchProgress::GaveUp(format!("couldn't make a map: {err}")));
+                return;
+            },
+        };
+        // the receiver going away means nobody wants the result
+        if sender.send(event).is_err() {
+            return;
+        }
+    }
+    let _ = sender.send(SearchProgress::GaveUp(search.gave_up()));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    // waits for the search to end, returning every event it sent
+    fn finish(mut worker: SearchWorker) -> Vec<SearchProgress> {
+        let start = Instant::now();
+        let mut events = Vec::new();
+        while !events.iter().any(|e| matches!(e, SearchProgress::Found(_) | SearchProgress::GaveUp(_))) {
+            assert!(start.elapsed() < Duration::from_secs(30), "the search never ended");
+            events.extend(worker.poll());
+            thread::sleep(Duration::from_millis(5));
+        }
+        events
+    }
+
+    #[test]
+    fn a_search_finds_a_map_that_meets_the_constraints() {
+        let mut recipe = Recipe::new("maze");
+        recipe.seed = Some(7);
+        recipe.constraints.single_region = true;
+
+        let events = finish(SearchWorker::spawn(Search::new(recipe, PrefabStamper::new())));
+        let Some(SearchProgress::Found(found)) = events.last() else {
+            panic!("a maze is always one region");
+        };
+        assert_eq!(found.attempts, 1);
+        assert_eq!(found.generated.seed, 7);
+    }
+
+    #[test]
+    fn a_search_gives_up_once_the_attempts_run_out() {
+        let mut recipe = Recipe::new("maze");
+        recipe.constraints.floor_percent = Some((99.0, 100.0));
+        recipe.constraints.max_attempts = 3;
+
+        let events = finish(SearchWorker::spawn(Search::new(recipe, PrefabStamper::new())));
+        let attempts = events.iter().filter(|e| matches!(e, SearchProgress::Attempt { .. })).count();
+        assert_eq!(attempts, 3);
+        assert!(matches!(events.last(), Some(SearchProgress::GaveUp(reason)) if reason.contains("3 attempts")));
+    }
+}
@@ -0,0 +1,565 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                            Corridors                                  ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use rand::Rng;
+
+use super::Map;
+use super::map::TileType;
+use super::rooms::{Corridor, Rect, RoomGraph};
+use super::settings;
+
+/// How a corridor walks from one room to the next.
+#[derive(PartialEq, Copy, Clone)]
+pub enum CorridorStyle {
+    /// Walks along x first, then along y.
+    XThenY,
+    /// Walks along x or y first, picked at random for each corridor.
+    LShaped,
+    /// Follows a Bresenham line, stepping sideways on diagonals so the
+    /// corridor stays walkable without diagonal moves.
+    Bresenham,
+    /// A drunkard's walk biased towards the target room.
+    DrunkWalk,
+}
+
+impl CorridorStyle {
+    pub const ALL: [CorridorStyle; 4] = [
+        CorridorStyle::XThenY,
+        CorridorStyle::LShaped,
+        CorridorStyle::Bresenham,
+        CorridorStyle::DrunkWalk,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CorridorStyle::XThenY => "X then Y",
+            CorridorStyle::LShaped => "L-Shaped",
+            CorridorStyle::Bresenham => "Bresenham",
+            CorridorStyle::DrunkWalk => "Drunk Walk",
+        }
+    }
+}
+
+/// Which pairs of rooms get joined by a corridor.
+#[derive(PartialEq, Copy, Clone)]
+pub enum ConnectionStrategy {
+    /// Joins each room to the next one in the order they are given.
+    Sequential,
+    /// Starting from the first room, repeatedly joins the current room to
+    /// its nearest unvisited room.
+    NearestNeighbour,
+    /// Joins the rooms along a minimum spanning tree of their centers.
+    MinimumSpanningTree,
+    /// A minimum spanning tree plus the shortest remaining pairs, adding
+    /// `extra_percent` percent as many corridors again to create loops.
+    MstWithLoops { extra_percent: u32 },
+    /// Joins every pair of rooms that share an edge in the Delaunay
+    /// triangulation of their centers.
+    Delaunay,
+}
+
+impl ConnectionStrategy {
+    pub const ALL: [ConnectionStrategy; 7] = [
+        ConnectionStrategy::Sequential,
+        ConnectionStrategy::NearestNeighbour,
+        ConnectionStrategy::MinimumSpanningTree,
+        ConnectionStrategy::MstWithLoops { extra_percent: 25 },
+        ConnectionStrategy::MstWithLoops { extra_percent: 50 },
+        ConnectionStrategy::MstWithLoops { extra_percent: 100 },
+        ConnectionStrategy::Delaunay,
+    ];
+
+    pub fn name(&self) -> String {
+        match self {
+            ConnectionStrategy::Sequential => "Sequential".to_string(),
+            ConnectionStrategy::NearestNeighbour => "Nearest Neighbour".to_string(),
+            ConnectionStrategy::MinimumSpanningTree => "Minimum Spanning Tree".to_string(),
+            ConnectionStrategy::MstWithLoops { extra_percent } => format!("MST + {extra_percent}% Loops"),
+            ConnectionStrategy::Delaunay => "Delaunay".to_string(),
+        }
+    }
+
+    /// Reads a strategy written as `name` writes it. Loops can be any
+    /// percentage, not just the ones in `ALL`.
+    pub fn parse(value: &str) -> Option<ConnectionStrategy> {
+        let value = value.trim().to_ascii_lowercase();
+        let loops = value.strip_prefix("mst + ").and_then(|rest| rest.strip_suffix("% loops"));
+        match loops {
+            Some(percent) => percent.trim().parse().ok().map(|extra_percent| ConnectionStrategy::MstWithLoops { extra_percent }),
+            None => ConnectionStrategy::ALL.into_iter().find(|strategy| strategy.name().to_ascii_lowercase() == value),
+        }
+    }
+}
+
+/// Corridor settings shared by the room-based builders.
+#[derive(Copy, Clone)]
+pub struct CorridorConfig {
+    pub style: CorridorStyle,
+    pub strategy: ConnectionStrategy,
+    pub width: i32,
+}
+
+impl CorridorConfig {
+    const MAX_WIDTH: i32 = 3;
+
+    pub fn settings(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Corridor Style", self.style.name().to_string()),
+            ("Connections", self.strategy.name()),
+            ("Corridor Width", format!("{}", self.width)),
+        ]
+    }
+
+    /// Advances the setting at `index` of `settings` to its next value.
+    pub fn cycle_setting(&mut self, index: usize) {
+        match index {
+            0 => self.style = next_in(&CorridorStyle::ALL, self.style),
+            1 => self.strategy = next_in(&ConnectionStrategy::ALL, self.strategy),
+            2 => self.width = self.width % Self::MAX_WIDTH + 1,
+            _ => {}
+        }
+    }
+
+    /// Sets the setting at `index` of `settings` from text.
+    pub fn set_setting(&mut self, index: usize, value: &str) -> Result<(), String> {
+        match index {
+            0 => self.style = settings::choice("Corridor Style", value, &CorridorStyle::ALL, CorridorStyle::name)?,
+            1 => {
+                self.strategy = ConnectionStrategy::parse(value).ok_or_else(|| {
+                    let names: Vec<String> = ConnectionStrategy::ALL.iter().map(ConnectionStrategy::name).collect();
+                    format!("'Connections' must be one of {} or 'MST + <n>% Loops', not '{}'", names.join(", "), value)
+                })?
+            },
+            2 => self.width = settings::number("Corridor Width", value, 1..=Self::MAX_WIDTH)?,
+            _ => return Err(settings::no_setting(index)),
+        }
+        Ok(())
+    }
+}
+
+impl Default for CorridorConfig {
+    fn default() -> Self {
+        CorridorConfig {
+            style: CorridorStyle::XThenY,
+            strategy: ConnectionStrategy::Sequential,
+            width: 1,
+        }
+    }
+}
+
+fn next_in<T: PartialEq + Copy>(values: &[T], current: T) -> T {
+    let idx = values.iter().position(|v| *v == current).unwrap_or(0);
+    values[(idx + 1) % values.len()]
+}
+
+/// Joins the rooms of `graph` according to `config`, carving the corridors
+/// into `map` and recording them in the graph.
+pub fn connect_rooms<R: Rng>(map: &mut Map, graph: &mut RoomGraph, config: &CorridorConfig, rng: &mut R) {
+    for (from, to) in connections(&graph.rooms, config.strategy) {
+        let start = random_floor_in(map, &graph.rooms[from], rng);
+        let end = random_floor_in(map, &graph.rooms[to], rng);
+        let path = carve_corridor(map, start, end, config.style, config.width, rng);
+        graph.corridors.push(Corridor::from_path(from, to, &path));
+    }
+}
+
+/// Picks a random floor tile inside a room carved from `room`, so that
+/// corridors reach rooms that don't fill their whole rectangle.
+pub fn random_floor_in<R: Rng>(map: &Map, room: &Rect, rng: &mut R) -> (i32, i32) {
+    let mut floors = Vec::new();
+    for y in room.y1 + 1..=room.y2 {
+        for x in room.x1 + 1..=room.x2 {
+            if map.tiles[map.xy_idx(x, y)] == TileType::Floor {
+                floors.push((x, y));
+            }
+        }
+    }
+
+    if floors.is_empty() {
+        room.center()
+    } else {
+        floors[rng.gen_range(0..floors.len())]
+    }
+}
+
+/// Returns the pairs of room indices to join.
+pub fn connections(rooms: &[Rect], strategy: ConnectionStrategy) -> Vec<(usize, usize)> {
+    if rooms.len() < 2 {
+        return Vec::new();
+    }
+
+    match strategy {
+        ConnectionStrategy::Sequential => (0..rooms.len() - 1).map(|i| (i, i + 1)).collect(),
+        ConnectionStrategy::NearestNeighbour => nearest_neighbour_chain(rooms),
+        ConnectionStrategy::MinimumSpanningTree => minimum_spanning_tree(rooms),
+        ConnectionStrategy::MstWithLoops { extra_percent } => {
+            let mut edges = minimum_spanning_tree(rooms);
+            let extra = (edges.len() * extra_percent as usize).div_ceil(100);
+
+            let mut candidates = Vec::new();
+            for a in 0..rooms.len() {
+                for b in a + 1..rooms.len() {
+                    if !edges.contains(&(a, b)) && !edges.contains(&(b, a)) {
+                        candidates.push((a, b));
+                    }
+                }
+            }
+            candidates.sort_by_key(|&(a, b)| distance_sq(&rooms[a], &rooms[b]));
+            edges.extend(candidates.into_iter().take(extra));
+            edges
+        },
+        ConnectionStrategy::Delaunay => {
+            // degenerate layouts (e.g. every center on one line) have no
+            // triangles, so the spanning tree keeps the rooms connected
+            let mut edges = delaunay_edges(rooms);
+            for (a, b) in minimum_spanning_tree(rooms) {
+                if !edges.contains(&(a.min(b), a.max(b))) {
+                    edges.push((a.min(b), a.max(b)));
+                }
+            }
+            edges
+        },
+    }
+}
+
+fn distance_sq(a: &Rect, b: &Rect) -> i32 {
+    let (ax, ay) = a.center();
+    let (bx, by) = b.center();
+    (ax - bx) * (ax - bx) + (ay - by) * (ay - by)
+}
+
+fn nearest_neighbour_chain(rooms: &[Rect]) -> Vec<(usize, usize)> {
+    let mut visited = vec![false; rooms.len()];
+    let mut edges = Vec::new();
+    let mut current = 0;
+    visited[0] = true;
+
+    while let Some(next) = (0..rooms.len())
+        .filter(|&i| !visited[i])
+        .min_by_key(|&i| distance_sq(&rooms[current], &rooms[i]))
+    {
+        visited[next] = true;
+        edges.push((current, next));
+        current = next;
+    }
+
+    edges
+}
+
+// Prim's algorithm over the complete graph of room centers
+fn minimum_spanning_tree(rooms: &[Rect]) -> Vec<(usize, usize)> {
+    let mut in_tree = vec![false; rooms.len()];
+    let mut best: Vec<(i32, usize)> = vec![(i32::MAX, 0); rooms.len()];
+    let mut edges = Vec::new();
+    best[0] = (0, 0);
+
+    for _ in 0..rooms.len() {
+        let next = (0..rooms.len())
+            .filter(|&i| !in_tree[i])
+            .min_by_key(|&i| best[i].0)
+            .expect("rooms left to add");
+        in_tree[next] = true;
+        if next != 0 {
+            edges.push((best[next].1, next));
+        }
+
+        for i in 0..rooms.len() {
+            let dist = distance_sq(&rooms[next], &rooms[i]);
+            if !in_tree[i] && dist < best[i].0 {
+                best[i] = (dist, next);
+            }
+        }
+    }
+
+    edges
+}
+
+// Bowyer-Watson triangulation of the room centers
+fn delaunay_edges(rooms: &[Rect]) -> Vec<(usize, usize)> {
+    let mut points: Vec<(f64, f64)> = rooms
+        .iter()
+        .map(|r| {
+            let (x, y) = r.center();
+            (x as f64, y as f64)
+        })
+        .collect();
+
+    // a super triangle large enough to contain every center
+    let n = points.len();
+    let (min_x, max_x, min_y, max_y) = points.iter().fold(
+        (f64::MAX, f64::MIN, f64::MAX, f64::MIN),
+        |(x0, x1, y0, y1), &(x, y)| (x0.min(x), x1.max(x), y0.min(y), y1.max(y)),
+    );
+    let span = (max_x - min_x).max(max_y - min_y).max(1.0) * 10.0;
+    let (mid_x, mid_y) = ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0);
+    points.push((mid_x - span, mid_y - span));
+    points.push((mid_x + span, mid_y - span));
+    points.push((mid_x, mid_y + span));
+
+    let mut triangles: Vec<[usize; 3]> = vec![[n, n + 1, n + 2]];
+
+    for p in 0..n {
+        let (px, py) = points[p];
+        let (bad, good): (Vec<[usize; 3]>, Vec<[usize; 3]>) = triangles
+            .into_iter()
+            .partition(|t| in_circumcircle(&points, t, px, py));
+        triangles = good;
+
+        // the boundary of the hole left by the bad triangles is every edge
+        // that belongs to exactly one of them
+        let mut boundary: Vec<(usize, usize)> = Vec::new();
+        for t in &bad {
+            for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+                let shared = bad.iter().filter(|o| o.contains(&a) && o.contains(&b)).count() > 1;
+                if !shared {
+                    boundary.push((a, b));
+                }
+            }
+        }
+
+        for (a, b) in boundary {
+            triangles.push([a, b, p]);
+        }
+    }
+
+    let mut edges = Vec::new();
+    for t in triangles.iter().filter(|t| t.iter().all(|&v| v < n)) {
+        for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+            let edge = (a.min(b), a.max(b));
+            if !edges.contains(&edge) {
+                edges.push(edge);
+            }
+        }
+    }
+    edges
+}
+
+fn in_circumcircle(points: &[(f64, f64)], t: &[usize; 3], px: f64, py: f64) -> bool {
+    let (ax, ay) = points[t[0]];
+    let (bx, by) = points[t[1]];
+    let (cx, cy) = points[t[2]];
+
+    let d = 2.0 * (ax * (by - cy) + bx * (cy - ay) + cx * (ay - by));
+    if d.abs() < f64::EPSILON {
+        return false;
+    }
+
+    let a_sq = ax * ax + ay * ay;
+    let b_sq = bx * bx + by * by;
+    let c_sq = cx * cx + cy * cy;
+    let ux = (a_sq * (by - cy) + b_sq * (cy - ay) + c_sq * (ay - by)) / d;
+    let uy = (a_sq * (cx - bx) + b_sq * (ax - cx) + c_sq * (bx - ax)) / d;
+
+    let radius_sq = (ax - ux) * (ax - ux) + (ay - uy) * (ay - uy);
+    (px - ux) * (px - ux) + (py - uy) * (py - uy) < radius_sq
+}
+
+/// Carves a corridor from `start` to `end` and returns the tiles along its
+/// center line. Wider corridors grow to the right and downwards of that
+/// line. The outermost ring of the map is never carved.
+pub fn carve_corridor<R: Rng>(
+    map: &mut Map,
+    start: (i32, i32),
+    end: (i32, i32),
+    style: CorridorStyle,
+    width: i32,
+    rng: &mut R,
+) -> Vec<(i32, i32)> {
+    let path = match style {
+        CorridorStyle::XThenY => elbow_path(start, end, true),
+        CorridorStyle::LShaped => elbow_path(start, end, rng.gen_bool(0.5)),
+        CorridorStyle::Bresenham => bresenham_path(start, end),
+        CorridorStyle::DrunkWalk => drunk_path(map, start, end, rng),
+    };
+
+    for &(x, y) in &path {
+        for dy in 0..width.max(1) {
+            for dx in 0..width.max(1) {
+                carve(map, x + dx, y + dy);
+            }
+        }
+    }
+
+    path
+}
+
+fn carve(map: &mut Map, x: i32, y: i32) {
+    if x < 1 || y < 1 || x > map.width as i32 - 2 || y > map.height as i32 - 2 {
+        return;
+    }
+    let idx = map.xy_idx(x, y);
+    map.tiles[idx] = TileType::Floor;
+}
+
+fn elbow_path(start: (i32, i32), end: (i32, i32), x_first: bool) -> Vec<(i32, i32)> {
+    let (mut x, mut y) = start;
+    let mut path = vec![(x, y)];
+
+    while (x, y) != end {
+        let move_x = x != end.0 && (x_first || y == end.1);
+        if move_x {
+            x += (end.0 - x).signum();
+        } else {
+            y += (end.1 - y).signum();
+        }
+        path.push((x, y));
+    }
+
+    path
+}
+
+fn bresenham_path(start: (i32, i32), end: (i32, i32)) -> Vec<(i32, i32)> {
+    let (mut x, mut y) = start;
+    let dx = (end.0 - x).abs();
+    let dy = -(end.1 - y).abs();
+    let sx = (end.0 - x).signum();
+    let sy = (end.1 - y).signum();
+    let mut err = dx + dy;
+    let mut path = vec![(x, y)];
+
+    while (x, y) != end {
+        let e2 = 2 * err;
+        let step_x = e2 >= dy;
+        let step_y = e2 <= dx;
+        if step_x {
+            err += dy;
+            x += sx;
+        }
+        if step_x && step_y {
+            // fill the corner so the corridor is walkable in four directions
+            path.push((x, y));
+        }
+        if step_y {
+            err += dx;
+            y += sy;
+        }
+        path.push((x, y));
+    }
+
+    path
+}
+
+fn drunk_path<R: Rng>(map: &Map, start: (i32, i32), end: (i32, i32), rng: &mut R) -> Vec<(i32, i32)> {
+    let (mut x, mut y) = start;
+    let mut path = vec![(x, y)];
+
+    while (x, y) != end {
+        // step towards the target 60% of the time, otherwise stagger
+        let (step_x, step_y) = if rng.gen_bool(0.6) {
+            if x != end.0 && (y == end.1 || rng.gen_bool(0.5)) {
+                ((end.0 - x).signum(), 0)
+            } else {
+                (0, (end.1 - y).signum())
+            }
+        } else {
+            match rng.gen_range(0..4) {
+                0 => (0, -1),
+                1 => (0, 1),
+                2 => (-1, 0),
+                _ => (1, 0),
+            }
+        };
+
+        x = (x + step_x).clamp(1, map.width as i32 - 2);
+        y = (y + step_y).clamp(1, map.height as i32 - 2);
+        path.push((x, y));
+    }
+
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 2x2 room centred on (x, y)
+    fn room_at(x: i32, y: i32) -> Rect {
+        Rect::new(x - 1, y - 1, 2, 2)
+    }
+
+    fn sorted(mut edges: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        edges.iter_mut().for_each(|e| *e = (e.0.min(e.1), e.0.max(e.1)));
+        edges.sort();
+        edges
+    }
+
+    fn is_connected(rooms: usize, edges: &[(usize, usize)]) -> bool {
+        let mut reached = vec![false; rooms];
+        let mut stack = vec![0];
+        while let Some(room) = stack.pop() {
+            if !std::mem::replace(&mut reached[room], true) {
+                stack.extend(edges.iter().filter_map(|&(a, b)| match room {
+                    r if r == a => Some(b),
+                    r if r == b => Some(a),
+                    _ => None,
+                }));
+            }
+        }
+        reached.into_iter().all(|r| r)
+    }
+
+    #[test]
+    fn the_spanning_tree_joins_the_closest_rooms() {
+        let rooms = [room_at(0, 0), room_at(30, 0), room_at(10, 0), room_at(10, 5)];
+        let edges = sorted(connections(&rooms, ConnectionStrategy::MinimumSpanningTree));
+        assert_eq!(edges, vec![(0, 2), (1, 2), (2, 3)]);
+    }
+
+    #[test]
+    fn loops_add_the_shortest_pairs_left() {
+        let rooms = [room_at(0, 0), room_at(10, 0), room_at(10, 10), room_at(0, 10)];
+        let tree = connections(&rooms, ConnectionStrategy::MinimumSpanningTree);
+        let edges = connections(&rooms, ConnectionStrategy::MstWithLoops { extra_percent: 50 });
+
+        // the three sides of the tree, then the fourth side before a diagonal
+        assert_eq!(edges.len(), 5);
+        assert_eq!(edges[..3], tree[..]);
+        let (a, b) = edges[3];
+        assert_eq!(distance_sq(&rooms[a], &rooms[b]), 100);
+        let mut unique = sorted(edges.clone());
+        unique.dedup();
+        assert_eq!(unique.len(), edges.len());
+    }
+
+    #[test]
+    fn delaunay_splits_a_square_with_a_centre_into_four_triangles() {
+        let rooms = [room_at(0, 0), room_at(20, 0), room_at(20, 20), room_at(0, 20), room_at(10, 10)];
+        let edges = sorted(connections(&rooms, ConnectionStrategy::Delaunay));
+        assert_eq!(edges, vec![(0, 1), (0, 3), (0, 4), (1, 2), (1, 4), (2, 3), (2, 4), (3, 4)]);
+    }
+
+    #[test]
+    fn delaunay_keeps_rooms_in_a_line_connected() {
+        let rooms: Vec<Rect> = (0..5).map(|i| room_at(i * 10, 4)).collect();
+        let edges = connections(&rooms, ConnectionStrategy::Delaunay);
+        assert!(is_connected(rooms.len(), &edges));
+    }
+
+    #[test]
+    fn every_strategy_connects_every_room() {
+        let rooms = [room_at(3, 4), room_at(25, 7), room_at(14, 30), room_at(40, 22), room_at(8, 18), room_at(33, 35)];
+        for strategy in ConnectionStrategy::ALL {
+            assert!(is_connected(rooms.len(), &connections(&rooms, strategy)), "{} left a room out", strategy.name());
+        }
+    }
+
+    #[test]
+    fn strategies_parse_from_their_names() {
+        for strategy in ConnectionStrategy::ALL {
+            assert!(ConnectionStrategy::parse(&strategy.name()) == Some(strategy));
+        }
+        assert!(ConnectionStrategy::parse("mst + 30% loops") == Some(ConnectionStrategy::MstWithLoops { extra_percent: 30 }));
+        assert!(ConnectionStrategy::parse("Spiral").is_none());
+    }
+
+    #[test]
+    fn straight_corridors_only_step_along_one_axis_at_a_time() {
+        let path = bresenham_path((2, 3), (17, 9));
+        assert_eq!(path.first(), Some(&(2, 3)));
+        assert_eq!(path.last(), Some(&(17, 9)));
+        assert!(path.windows(2).all(|w| (w[0].0 - w[1].0).abs() + (w[0].1 - w[1].1).abs() == 1));
+    }
+}
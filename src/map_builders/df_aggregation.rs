@@ -7,6 +7,8 @@
 use rand::Rng;
 
 use super::MapBuilder;
+use super::metrics::Metric;
+use super::random;
 use super::Map;
 use super::map::TileType;
 
@@ -16,7 +18,7 @@ pub struct DiffusionLimitedAggregationBuilder {
     iterations: i32,
 }
 
-impl<'a> MapBuilder<'a> for DiffusionLimitedAggregationBuilder {
+impl MapBuilder for DiffusionLimitedAggregationBuilder {
     fn build(&mut self) {
         self.clear();
         self.seed();
@@ -30,19 +32,29 @@ impl<'a> MapBuilder<'a> for DiffusionLimitedAggregationBuilder {
         }
     }
 
-    fn get_map(&self) -> Map {
-        self.map.clone()
+    fn get_map(&self) -> &Map {
+        &self.map
     }
 
-    fn update_map_data(&self, map_data: &mut Vec<(&'a str, String)>) {
-        map_data.clear();
-        let num_walls = self.num_walls();
-        map_data.push(("Name", "Diffusion-Limited Aggregation".to_string()));
-        map_data.push(("Max Iterations", format!("{}", 16)));
-        map_data.push(("Iteration", format!("{}", self.iterations)));
-        map_data.push(("Total Number of Walls", format!("{}", num_walls)));
-        map_data.push(("Total Empty Space", format!("{}", 1600 - num_walls)));
-        map_data.push(("% Occupied", format!("{}", num_walls as f32 / 1600_f32)));
+    fn get_map_mut(&mut self) -> &mut Map {
+        &mut self.map
+    }
+
+    fn metrics(&self) -> Vec<Metric> {
+        let num_walls = self.num_walls() as usize;
+        let num_tiles = self.map.tiles.len();
+        vec![
+            Metric::text("Name", "Diffusion-Limited Aggregation"),
+            Metric::integer("Max Iterations", 16),
+            Metric::integer("Iteration", self.iterations),
+            Metric::integer("Total Number of Walls", num_walls).with_unit("tiles"),
+            Metric::integer("Total Empty Space", num_tiles - num_walls).with_unit("tiles"),
+            Metric::percent("Occupied", num_walls, num_tiles),
+        ]
+    }
+
+    fn progress(&self) -> Option<f64> {
+        Some(self.iterations as f64 / 16.0)
     }
 
     fn notes(&self) -> &str {
@@ -60,7 +72,7 @@ impl DiffusionLimitedAggregationBuilder {
 
     pub fn clear(&mut self) {
         self.iterations = 0;
-        self.map = Map::new();
+        self.map = Map::with_size(self.map.width, self.map.height);
     }
 
     pub fn seed(&mut self) {
@@ -81,7 +93,7 @@ impl DiffusionLimitedAggregationBuilder {
     pub fn iterate(&mut self, desired_tiles: i32) {
         let mut x: usize;
         let mut y: usize;
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         let mut total_tiles = 0;
 
         while total_tiles < desired_tiles {
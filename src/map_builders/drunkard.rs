@@ -7,6 +7,8 @@
 use rand::Rng;
 
 use super::MapBuilder;
+use super::metrics::Metric;
+use super::random;
 use super::Map;
 use super::map::TileType;
 
@@ -17,7 +19,7 @@ pub struct DrunkardBuilder {
     max_steps: i32,
 }
 
-impl<'a> MapBuilder<'a> for DrunkardBuilder {
+impl MapBuilder for DrunkardBuilder {
     fn build(&mut self) {
         self.clear();
         self.seed();
@@ -30,20 +32,30 @@ impl<'a> MapBuilder<'a> for DrunkardBuilder {
         }
     }
 
-    fn get_map(&self) -> Map {
-        self.map.clone()
+    fn get_map(&self) -> &Map {
+        &self.map
     }
 
-    fn update_map_data(&self, map_data: &mut Vec<(&'a str, String)>) {
-        map_data.clear();
-        let num_walls = self.num_walls();
-        map_data.push(("Name", "Drunkard's Walk".to_string()));
-        map_data.push(("Max Iterations", format!("{}", 24)));
-        map_data.push(("Steps Per Iteration", format!("{}", self.max_steps)));
-        map_data.push(("Iteration", format!("{}", self.iterations)));
-        map_data.push(("Total Number of Walls", format!("{}", num_walls)));
-        map_data.push(("Total Empty Space", format!("{}", 1600 - num_walls)));
-        map_data.push(("% Occupied", format!("{}", num_walls as f32 / 1600_f32)));
+    fn get_map_mut(&mut self) -> &mut Map {
+        &mut self.map
+    }
+
+    fn metrics(&self) -> Vec<Metric> {
+        let num_walls = self.num_walls() as usize;
+        let num_tiles = self.map.tiles.len();
+        vec![
+            Metric::text("Name", "Drunkard's Walk"),
+            Metric::integer("Max Iterations", 24),
+            Metric::integer("Steps Per Iteration", self.max_steps),
+            Metric::integer("Iteration", self.iterations),
+            Metric::integer("Total Number of Walls", num_walls).with_unit("tiles"),
+            Metric::integer("Total Empty Space", num_tiles - num_walls).with_unit("tiles"),
+            Metric::percent("Occupied", num_walls, num_tiles),
+        ]
+    }
+
+    fn progress(&self) -> Option<f64> {
+        Some(self.iterations as f64 / 24.0)
     }
 
     fn notes(&self) -> &str {
@@ -62,7 +74,7 @@ impl DrunkardBuilder {
 
     pub fn clear(&mut self) {
         self.iterations = 0;
-        self.map = Map::new();
+        self.map = Map::with_size(self.map.width, self.map.height);
     }
 
     pub fn seed(&mut self) {
@@ -83,7 +95,7 @@ impl DrunkardBuilder {
     pub fn iterate(&mut self) {
         let mut x: usize;
         let mut y: usize;
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         const START_RADIUS: usize = 10;
 
         loop {
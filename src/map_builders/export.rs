@@ -0,0 +1,59 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                              Export                                   ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::{fs, io, path::Path};
+
+use serde::Serialize;
+
+use super::autotile::WallMasks;
+use super::map::Map;
+use super::metrics::Metric;
+use super::rooms::{Corridor, Rect, RoomGraph};
+use super::voronoi::VoronoiRegion;
+
+#[derive(Serialize)]
+struct MapExport<'a> {
+    width: usize,
+    height: usize,
+    tiles: Vec<&'a str>,
+    wall_masks: WallMasks,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rooms: Option<&'a [Rect]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    corridors: Option<&'a [Corridor]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dead_ends: Option<Vec<usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    regions: Option<&'a [VoronoiRegion]>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    metrics: &'a [Metric],
+}
+
+/// Serializes a map to JSON. Tiles are written as one string per row using
+/// the same characters as `Map::to_text`, followed by the neighbour masks
+/// of every wall for autotiling. When a room graph is given its rooms,
+/// corridors and dead-end room indices are written alongside, as are the
+/// cells of a Voronoi diagram when given. Any metrics follow at the end.
+pub fn to_json(map: &Map, graph: Option<&RoomGraph>, regions: Option<&[VoronoiRegion]>, metrics: &[Metric]) -> serde_json::Result<String> {
+    let text = map.to_text();
+    let export = MapExport {
+        width: map.width,
+        height: map.height,
+        tiles: text.lines().collect(),
+        wall_masks: WallMasks::new(map),
+        rooms: graph.map(|g| g.rooms.as_slice()),
+        corridors: graph.map(|g| g.corridors.as_slice()),
+        dead_ends: graph.map(|g| g.dead_ends()),
+        regions,
+        metrics,
+    };
+    serde_json::to_string_pretty(&export)
+}
+
+pub fn write_json<P: AsRef<Path>>(path: P, map: &Map, graph: Option<&RoomGraph>, regions: Option<&[VoronoiRegion]>, metrics: &[Metric]) -> io::Result<()> {
+    let json = to_json(map, graph, regions, metrics)?;
+    fs::write(path, json)
+}
@@ -0,0 +1,557 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                           Field of View                               ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use super::map::Map;
+
+
+/// Tiles by index that can be seen from `origin`, out to `radius` tiles
+/// away, by symmetric shadowcasting: whenever one floor tile can see another
+/// the second can see it back. Walls that can be seen are included.
+pub fn symmetric_shadowcast(map: &Map, origin: (usize, usize), radius: usize) -> Vec<bool> {
+    let mut visible = vec![false; map.tiles.len()];
+    if origin.0 >= map.width || origin.1 >= map.height {
+        return visible;
+    }
+    visible[origin.1 * map.width + origin.0] = true;
+
+    for quadrant in Quadrant::ALL {
+        let mut rows = vec![Row { depth: 1, start: Slope::new(-1, 1), end: Slope::new(1, 1) }];
+        while let Some(mut row) = rows.pop() {
+            if row.depth > radius {
+                continue;
+            }
+            // whether the last tile looked at was a wall, if there was one
+            let mut last_wall = None;
+            for column in row.columns() {
+                let tile = quadrant.transform(map, origin, row.depth, column);
+                let wall = tile.is_none_or(|(x, y)| map.is_opaque(x, y));
+                let in_range = row.depth.pow(2) + (column.unsigned_abs() as usize).pow(2) <= radius.pow(2);
+
+                if let Some((x, y)) = tile.filter(|_| in_range && (wall || row.is_symmetric(column))) {
+                    visible[y * map.width + x] = true;
+                }
+                if last_wall == Some(true) && !wall {
+                    row.start = Slope::of(row.depth, column);
+                }
+                if last_wall == Some(false) && wall {
+                    rows.push(Row { end: Slope::of(row.depth, column), ..row.next() });
+                }
+                last_wall = Some(wall);
+            }
+            if last_wall == Some(false) {
+                rows.push(row.next());
+            }
+        }
+    }
+    visible
+}
+
+/// Tiles by index that can be seen from `origin`, out to `radius` tiles
+/// away, by recursive shadowcasting. Quicker than the symmetric version, but
+/// a tile it can see doesn't always see the origin back.
+pub fn recursive_shadowcast(map: &Map, origin: (usize, usize), radius: usize) -> Vec<bool> {
+    // how each of the eight octants turns columns and rows into x and y
+    const OCTANTS: [(i64, i64, i64, i64); 8] = [
+        (1, 0, 0, 1), (0, 1, 1, 0), (0, -1, 1, 0), (-1, 0, 0, 1),
+        (-1, 0, 0, -1), (0, -1, -1, 0), (0, 1, -1, 0), (1, 0, 0, -1),
+    ];
+
+    let mut caster = Caster { map, visible: vec![false; map.tiles.len()], origin, radius, octant: OCTANTS[0] };
+    if origin.0 >= map.width || origin.1 >= map.height {
+        return caster.visible;
+    }
+    caster.visible[origin.1 * map.width + origin.0] = true;
+    for octant in OCTANTS {
+        caster.octant = octant;
+        caster.cast_light(1, 1.0, 0.0);
+    }
+    caster.visible
+}
+
+/// Tiles by index that can be seen from `origin`, out to `radius` tiles
+/// away, by precise permissive field of view: a tile is seen if a straight
+/// line runs from inside the origin's square to inside its own without
+/// crossing a wall, so a line that only meets either square at a corner
+/// doesn't count. It is symmetric, but not a superset of the others, since
+/// symmetric shadowcasting sees some tiles it doesn't.
+pub fn permissive(map: &Map, origin: (usize, usize), radius: usize) -> Vec<bool> {
+    let mut visible = vec![false; map.tiles.len()];
+    if origin.0 >= map.width || origin.1 >= map.height {
+        return visible;
+    }
+    visible[origin.1 * map.width + origin.0] = true;
+
+    // as far as the view reaches each way, stopping at the edge of the map
+    let left = origin.0.min(radius) as i64;
+    let right = (map.width - origin.0 - 1).min(radius) as i64;
+    let up = origin.1.min(radius) as i64;
+    let down = (map.height - origin.1 - 1).min(radius) as i64;
+    for (dx, dy, extent_x, extent_y) in [(1, 1, right, down), (1, -1, right, up), (-1, -1, left, up), (-1, 1, left, down)] {
+        check_quadrant(map, &mut visible, origin, radius, (dx, dy), (extent_x, extent_y));
+    }
+    visible
+}
+
+/// Whether `from` and `to` can see each other. They can if a straight line
+/// between the two, drawn from either end, crosses no opaque tile on the
+/// way; the ends themselves may be opaque.
+pub fn line_of_sight(map: &Map, from: (usize, usize), to: (usize, usize)) -> bool {
+    clear_line(map, from, to) || clear_line(map, to, from)
+}
+
+/// How much of a map can be seen from it, looking from every floor tile in
+/// turn out to a radius. Small, winding maps score low and wide open ones
+/// high.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Visibility {
+    /// Floor tiles in view from the average floor tile, itself included.
+    pub mean_visible: f64,
+    /// The same as a share of all the floor, from 0 to 100.
+    pub mean_percent: f64,
+}
+
+impl Visibility {
+    /// Looks from every floor tile by symmetric shadowcasting.
+    pub fn new(map: &Map, radius: usize) -> Visibility {
+        let floors: Vec<usize> = (0..map.tiles.len()).filter(|&i| !map.tiles[i].is_opaque()).collect();
+        if floors.is_empty() {
+            return Visibility { mean_visible: 0.0, mean_percent: 0.0 };
+        }
+
+        let total: usize = floors
+            .iter()
+            .map(|&i| {
+                let visible = symmetric_shadowcast(map, (i % map.width, i / map.width), radius);
+                floors.iter().filter(|&&j| visible[j]).count()
+            })
+            .sum();
+        let mean_visible = total as f64 / floors.len() as f64;
+        Visibility { mean_visible, mean_percent: 100.0 * mean_visible / floors.len() as f64 }
+    }
+}
+
+// the four quarters of the view, each a cone looking one way from the
+// origin
+#[derive(Copy, Clone)]
+enum Quadrant {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Quadrant {
+    const ALL: [Quadrant; 4] = [Quadrant::North, Quadrant::East, Quadrant::South, Quadrant::West];
+
+    // the tile `depth` rows out and `column` across from the origin, if it's
+    // on the map
+    fn transform(&self, map: &Map, origin: (usize, usize), depth: usize, column: i64) -> Option<(usize, usize)> {
+        let (ox, oy, depth) = (origin.0 as i64, origin.1 as i64, depth as i64);
+        let (x, y) = match self {
+            Quadrant::North => (ox + column, oy - depth),
+            Quadrant::South => (ox + column, oy + depth),
+            Quadrant::East => (ox + depth, oy + column),
+            Quadrant::West => (ox - depth, oy + column),
+        };
+        let on_map = x >= 0 && y >= 0 && (x as usize) < map.width && (y as usize) < map.height;
+        on_map.then_some((x as usize, y as usize))
+    }
+}
+
+// a fraction kept exact, with a positive denominator
+#[derive(Copy, Clone)]
+struct Slope {
+    num: i64,
+    den: i64,
+}
+
+impl Slope {
+    fn new(num: i64, den: i64) -> Slope {
+        Slope { num, den }
+    }
+
+    // the slope to the near edge of a tile
+    fn of(depth: usize, column: i64) -> Slope {
+        Slope::new(2 * column - 1, 2 * depth as i64)
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Row {
+    depth: usize,
+    start: Slope,
+    end: Slope,
+}
+
+impl Row {
+    // the columns between the slopes, rounding half way tiles outwards
+    fn columns(&self) -> std::ops::RangeInclusive<i64> {
+        let depth = self.depth as i64;
+        // floor(depth * start + 1/2) and ceil(depth * end - 1/2)
+        let first = (2 * depth * self.start.num + self.start.den).div_euclid(2 * self.start.den);
+        let last = (2 * depth * self.end.num - self.end.den + 2 * self.end.den - 1).div_euclid(2 * self.end.den);
+        first..=last
+    }
+
+    // whether the centre of the tile is inside the slopes, so the origin
+    // can be seen from it as well
+    fn is_symmetric(&self, column: i64) -> bool {
+        let depth = self.depth as i64;
+        column * self.start.den >= depth * self.start.num && column * self.end.den <= depth * self.end.num
+    }
+
+    fn next(&self) -> Row {
+        Row { depth: self.depth + 1, ..*self }
+    }
+}
+
+
+// the state of a recursive shadowcast, looking through one octant at a time
+struct Caster<'a> {
+    map: &'a Map,
+    visible: Vec<bool>,
+    origin: (usize, usize),
+    radius: usize,
+    // how the octant turns columns and rows into x and y
+    octant: (i64, i64, i64, i64),
+}
+
+impl Caster<'_> {
+    // lights the part of the octant between the `start` and `end` slopes,
+    // from `row` rows out, and carries on past any walls in the way in the
+    // gaps either side of them
+    fn cast_light(&mut self, row: usize, start: f64, end: f64) {
+        if start < end {
+            return;
+        }
+        let (xx, xy, yx, yy) = self.octant;
+        let mut start = start;
+        let mut next_start = start;
+
+        for depth in row..=self.radius {
+            let dy = -(depth as i64);
+            let mut blocked = false;
+            for dx in dy..=0 {
+                let x = self.origin.0 as i64 + dx * xx + dy * xy;
+                let y = self.origin.1 as i64 + dx * yx + dy * yy;
+                let left_slope = (dx as f64 - 0.5) / (dy as f64 + 0.5);
+                let right_slope = (dx as f64 + 0.5) / (dy as f64 - 0.5);
+                if start < right_slope {
+                    continue;
+                }
+                if end > left_slope {
+                    break;
+                }
+
+                let on_map = x >= 0 && y >= 0 && (x as usize) < self.map.width && (y as usize) < self.map.height;
+                if on_map && dx * dx + dy * dy <= (self.radius * self.radius) as i64 {
+                    self.visible[y as usize * self.map.width + x as usize] = true;
+                }
+                let opaque = !on_map || self.map.is_opaque(x as usize, y as usize);
+                if blocked {
+                    if opaque {
+                        next_start = right_slope;
+                    } else {
+                        blocked = false;
+                        start = next_start;
+                    }
+                } else if opaque && depth < self.radius {
+                    blocked = true;
+                    self.cast_light(depth + 1, start, left_slope);
+                    next_start = right_slope;
+                }
+            }
+            if blocked {
+                break;
+            }
+        }
+    }
+}
+
+// whether no opaque tile lies strictly between the ends of a line walked
+// from `from` to `to` a tile at a time
+fn clear_line(map: &Map, from: (usize, usize), to: (usize, usize)) -> bool {
+    let (mut x, mut y) = (from.0 as i64, from.1 as i64);
+    let (end_x, end_y) = (to.0 as i64, to.1 as i64);
+    let (dx, dy) = ((end_x - x).abs(), -(end_y - y).abs());
+    let (step_x, step_y) = ((end_x - x).signum(), (end_y - y).signum());
+    let mut error = dx + dy;
+
+    loop {
+        if (x, y) == (end_x, end_y) {
+            return true;
+        }
+        if (x, y) != (from.0 as i64, from.1 as i64) && map.is_opaque(x as usize, y as usize) {
+            return false;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+// a line between two corners of tiles in a quadrant of the permissive view
+#[derive(Copy, Clone)]
+struct Line {
+    start: (i64, i64),
+    end: (i64, i64),
+}
+
+impl Line {
+    // which side of the line a corner is on: positive below, negative above
+    fn side(&self, (x, y): (i64, i64)) -> i64 {
+        (self.end.1 - self.start.1) * (self.end.0 - x) - (self.end.0 - self.start.0) * (self.end.1 - y)
+    }
+
+    fn is_below(&self, point: (i64, i64)) -> bool {
+        self.side(point) > 0
+    }
+
+    fn is_below_or_on(&self, point: (i64, i64)) -> bool {
+        self.side(point) >= 0
+    }
+
+    fn is_above(&self, point: (i64, i64)) -> bool {
+        self.side(point) < 0
+    }
+
+    fn is_above_or_on(&self, point: (i64, i64)) -> bool {
+        self.side(point) <= 0
+    }
+
+    fn contains(&self, point: (i64, i64)) -> bool {
+        self.side(point) == 0
+    }
+}
+
+// the wedge of a quadrant still in view, between a shallow and a steep
+// line, with the wall corners that have bent each of them so far
+#[derive(Clone)]
+struct View {
+    shallow: Line,
+    steep: Line,
+    shallow_bumps: Vec<(i64, i64)>,
+    steep_bumps: Vec<(i64, i64)>,
+}
+
+impl View {
+    // pulls the shallow line up to pass over a wall's corner, keeping it
+    // clear of the corners already bending the steep line
+    fn bump_shallow(&mut self, corner: (i64, i64)) {
+        self.shallow.end = corner;
+        self.shallow_bumps.push(corner);
+        for &bump in self.steep_bumps.iter().rev() {
+            if self.shallow.is_above(bump) {
+                self.shallow.start = bump;
+            }
+        }
+    }
+
+    fn bump_steep(&mut self, corner: (i64, i64)) {
+        self.steep.end = corner;
+        self.steep_bumps.push(corner);
+        for &bump in self.shallow_bumps.iter().rev() {
+            if self.steep.is_below(bump) {
+                self.steep.start = bump;
+            }
+        }
+    }
+
+    // whether the lines have closed in on each other so nothing more can be
+    // seen through the view
+    fn is_closed(&self) -> bool {
+        let collinear = self.shallow.contains(self.steep.start) && self.shallow.contains(self.steep.end);
+        collinear && (self.shallow.contains((0, 1)) || self.shallow.contains((1, 0)))
+    }
+}
+
+// sweeps one quadrant of the permissive view out from the origin, a
+// diagonal of tiles at a time. `direction` turns the quadrant's x and y into
+// the map's, and `extent` is how far it reaches each way.
+fn check_quadrant(map: &Map, visible: &mut [bool], origin: (usize, usize), radius: usize, direction: (i64, i64), extent: (i64, i64)) {
+    let (extent_x, extent_y) = extent;
+    let mut views = vec![View {
+        shallow: Line { start: (0, 1), end: (extent_x, 0) },
+        steep: Line { start: (1, 0), end: (0, extent_y) },
+        shallow_bumps: Vec::new(),
+        steep_bumps: Vec::new(),
+    }];
+
+    for i in 1..=extent_x + extent_y {
+        if views.is_empty() {
+            break;
+        }
+        for j in (i - extent_x).max(0)..=i.min(extent_y) {
+            visit(map, visible, origin, radius, direction, (i - j, j), &mut views);
+        }
+    }
+}
+
+// looks at one tile of a quadrant, narrowing or splitting the view it's in
+// if it's a wall
+fn visit(map: &Map, visible: &mut [bool], origin: (usize, usize), radius: usize, direction: (i64, i64), (x, y): (i64, i64), views: &mut Vec<View>) {
+    let top_left = (x, y + 1);
+    let bottom_right = (x + 1, y);
+
+    let Some(index) = views.iter().position(|view| !view.steep.is_below_or_on(bottom_right)) else {
+        return;
+    };
+    if views[index].shallow.is_above_or_on(top_left) {
+        return;
+    }
+
+    let (map_x, map_y) = ((origin.0 as i64 + x * direction.0) as usize, (origin.1 as i64 + y * direction.1) as usize);
+    if x * x + y * y <= (radius * radius) as i64 {
+        visible[map_y * map.width + map_x] = true;
+    }
+    if !map.is_opaque(map_x, map_y) {
+        return;
+    }
+
+    let view = &mut views[index];
+    match (view.shallow.is_above(bottom_right), view.steep.is_below(top_left)) {
+        // the wall fills the view
+        (true, true) => {
+            views.remove(index);
+        },
+        (true, false) => {
+            view.bump_shallow(top_left);
+            if view.is_closed() {
+                views.remove(index);
+            }
+        },
+        (false, true) => {
+            view.bump_steep(bottom_right);
+            if view.is_closed() {
+                views.remove(index);
+            }
+        },
+        // the wall is in the middle of the view, which goes on either side
+        (false, false) => {
+            let mut shallow_side = view.clone();
+            shallow_side.bump_steep(bottom_right);
+            view.bump_shallow(top_left);
+            let steep_closed = view.is_closed();
+            let shallow_closed = shallow_side.is_closed();
+            if steep_closed {
+                views.remove(index);
+            }
+            if !shallow_closed {
+                views.insert(index, shallow_side);
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::map_builders::map::TileType;
+
+    type Fov = fn(&Map, (usize, usize), usize) -> Vec<bool>;
+    const ALL: [(&str, Fov); 3] = [("symmetric", symmetric_shadowcast), ("recursive", recursive_shadowcast), ("permissive", permissive)];
+
+    // a walled room with a pillar in the middle
+    const PILLAR: &str = "\
+#########
+#.......#
+#.......#
+#.......#
+#...#...#
+#.......#
+#.......#
+#.......#
+#########";
+
+    fn random_map(rng: &mut StdRng) -> Map {
+        let mut map = Map::with_size(16, 13);
+        map.tiles.iter_mut().for_each(|t| *t = if rng.gen_bool(0.7) { TileType::Floor } else { TileType::Wall });
+        map
+    }
+
+    fn floors(map: &Map) -> Vec<(usize, usize)> {
+        (0..map.tiles.len()).filter(|&i| map.tiles[i] == TileType::Floor).map(|i| (i % map.width, i / map.width)).collect()
+    }
+
+    #[test]
+    fn an_open_room_is_all_in_view() {
+        let room = Map::from_text("#######\n#.....#\n#.....#\n#.....#\n#######").unwrap();
+        for (name, fov) in ALL {
+            assert!(fov(&room, (1, 1), 20).into_iter().all(|v| v), "{name} missed part of the room");
+        }
+        assert_eq!(Visibility::new(&room, 20).mean_percent, 100.0);
+    }
+
+    #[test]
+    fn a_pillar_casts_a_shadow() {
+        let room = Map::from_text(PILLAR).unwrap();
+        for (name, fov) in ALL {
+            let visible = fov(&room, (4, 1), 20);
+            assert!(visible[4 * room.width + 4], "{name} didn't see the pillar");
+            assert!(!visible[7 * room.width + 4], "{name} saw through the pillar");
+            assert!(visible[7 * room.width + 1], "{name} missed a corner");
+        }
+    }
+
+    #[test]
+    fn nothing_is_seen_past_the_radius() {
+        let room = Map::from_text(PILLAR).unwrap();
+        for (name, fov) in ALL {
+            let visible = fov(&room, (1, 1), 3);
+            assert!(!visible[7 * room.width + 7], "{name} saw too far");
+            assert!(visible[3 * room.width + 1], "{name} didn't see far enough");
+        }
+    }
+
+    #[test]
+    fn symmetric_fields_of_view_are_symmetric() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..10 {
+            let map = random_map(&mut rng);
+            let floors = floors(&map);
+            for (name, fov) in [ALL[0], ALL[2]] {
+                let views: Vec<Vec<bool>> = floors.iter().map(|&tile| fov(&map, tile, 8)).collect();
+                for (a, &(ax, ay)) in floors.iter().enumerate() {
+                    for (b, &(bx, by)) in floors.iter().enumerate() {
+                        let (sees, seen) = (views[a][by * map.width + bx], views[b][ay * map.width + ax]);
+                        assert_eq!(sees, seen, "{name}: ({ax}, {ay}) and ({bx}, {by}) disagree on\n{}", map.to_text());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn line_of_sight_goes_both_ways() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let map = random_map(&mut rng);
+        let floors = floors(&map);
+        for &a in &floors {
+            for &b in &floors {
+                assert_eq!(line_of_sight(&map, a, b), line_of_sight(&map, b, a));
+            }
+        }
+    }
+
+    #[test]
+    fn line_of_sight_is_blocked_by_walls_between_but_not_at_the_ends() {
+        let room = Map::from_text(PILLAR).unwrap();
+        assert!(!line_of_sight(&room, (4, 1), (4, 7)));
+        assert!(!line_of_sight(&room, (1, 1), (7, 7)));
+        assert!(line_of_sight(&room, (1, 1), (7, 3)));
+        assert!(line_of_sight(&room, (4, 1), (4, 4)));
+        assert!(line_of_sight(&room, (0, 0), (1, 1)));
+    }
+}
@@ -5,6 +5,16 @@ pub enum TileType {
     Wall, Floor
 }
 
+impl TileType {
+    /// Whether the tile blocks sight.
+    pub fn is_opaque(&self) -> bool {
+        match self {
+            TileType::Wall => true,
+            TileType::Floor => false,
+        }
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct Map {
     pub height: usize,
@@ -15,8 +25,11 @@ pub struct Map {
 impl Map {
     /// Generates a map filled with walls
     pub fn new() -> Map {
-        let width = 39;
-        let height = 39;
+        Map::with_size(39, 39)
+    }
+
+    /// Generates a map of the given size filled with walls
+    pub fn with_size(width: usize, height: usize) -> Map {
         Map{
             tiles : vec![TileType::Wall; width * height],
             width,
@@ -32,11 +45,66 @@ impl Map {
         self.tiles.get(y * self.width + x)
     }
 
-    pub fn set(&mut self, new_tile: TileType, x: usize, y: usize) {
-        match self.tiles.get_mut(y * self.height + x) {
-            Some(tile) => *tile = new_tile,
-            None => print!("Couldn't find tile {x}, {y}")
+    /// Whether the tile at `x`, `y` blocks sight. Off the map counts as
+    /// opaque.
+    pub fn is_opaque(&self, x: usize, y: usize) -> bool {
+        x >= self.width || self.get(x, y).is_none_or(TileType::is_opaque)
+    }
+
+    /// Sets the tile at `x`, `y`, returning whether it was on the map.
+    pub fn set(&mut self, new_tile: TileType, x: usize, y: usize) -> bool {
+        if x >= self.width {
+            return false;
+        }
+        match self.tiles.get_mut(y * self.width + x) {
+            Some(tile) => {
+                *tile = new_tile;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Reads a map written by `to_text`. Every row must be the same width,
+    /// and trailing blank lines are ignored.
+    pub fn from_text(text: &str) -> Result<Map, String> {
+        let rows: Vec<&str> = text.trim_end().lines().map(|row| row.trim_end_matches('\r')).collect();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        if width == 0 {
+            return Err("map text is empty".to_string());
+        }
+
+        let mut tiles = Vec::with_capacity(width * rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                return Err(format!("row {} is {} tiles wide, expected {}", y + 1, row.chars().count(), width));
+            }
+            for (x, c) in row.chars().enumerate() {
+                tiles.push(match c {
+                    '#' => TileType::Wall,
+                    '.' => TileType::Floor,
+                    _ => return Err(format!("unknown tile '{}' at {}, {}", c, x, y)),
+                });
+            }
+        }
+
+        Ok(Map { height: rows.len(), width, tiles })
+    }
+
+    /// Writes the map as plain text, one row per line, with `#` for walls
+    /// and `.` for floors.
+    pub fn to_text(&self) -> String {
+        let mut text = String::with_capacity((self.width + 1) * self.height);
+        for row in self.tiles.chunks(self.width) {
+            for tile in row {
+                text.push(match tile {
+                    TileType::Floor => '.',
+                    TileType::Wall => '#',
+                });
+            }
+            text.push('\n');
         }
+        text
     }
 }
 
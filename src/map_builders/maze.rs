@@ -0,0 +1,584 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                               Mazes                                   ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use super::MapBuilder;
+use super::metrics::Metric;
+use super::random;
+use super::settings;
+use super::Map;
+use super::map::TileType;
+
+const BRAID_PERCENTAGES: [u32; 5] = [0, 25, 50, 75, 100];
+const CARVES_PER_ITERATION: [usize; 4] = [1, 5, 20, 100];
+
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum MazeAlgorithm {
+    RecursiveBacktracker,
+    Prim,
+    Kruskal,
+    Eller,
+    Wilson,
+}
+
+impl MazeAlgorithm {
+    pub const ALL: [MazeAlgorithm; 5] = [
+        MazeAlgorithm::RecursiveBacktracker,
+        MazeAlgorithm::Prim,
+        MazeAlgorithm::Kruskal,
+        MazeAlgorithm::Eller,
+        MazeAlgorithm::Wilson,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MazeAlgorithm::RecursiveBacktracker => "Recursive Backtracker",
+            MazeAlgorithm::Prim => "Prim's",
+            MazeAlgorithm::Kruskal => "Kruskal's",
+            MazeAlgorithm::Eller => "Eller's",
+            MazeAlgorithm::Wilson => "Wilson's",
+        }
+    }
+}
+
+/// Maze cells sit on the odd tiles of the map and the even tiles between
+/// two cells are the walls that get knocked through. Every generator
+/// writes the order it opens tiles into `carves`, which `iterate` then
+/// replays onto the map a few at a time.
+pub struct MazeBuilder {
+    map : Map,
+    algorithm: MazeAlgorithm,
+    braid_percent: u32,
+    carves_per_iteration: usize,
+    carves: Vec<(usize, usize)>,
+    carved: usize,
+    dead_ends_removed: usize,
+}
+
+impl MapBuilder for MazeBuilder {
+    fn build(&mut self) {
+        self.clear();
+        self.generate();
+    }
+
+    fn iterate(&mut self) {
+        let end = usize::min(self.carved + self.carves_per_iteration, self.carves.len());
+        for i in self.carved..end {
+            let (x, y) = self.carves[i];
+            self.map.set(TileType::Floor, x, y);
+        }
+        self.carved = end;
+    }
+
+    fn get_map(&self) -> &Map {
+        &self.map
+    }
+
+    fn get_map_mut(&mut self) -> &mut Map {
+        &mut self.map
+    }
+
+    fn metrics(&self) -> Vec<Metric> {
+        vec![
+            Metric::text("Name", "Maze"),
+            Metric::text("Algorithm", self.algorithm.name()),
+            Metric::integer("Tiles Carved", self.carved).with_unit("tiles"),
+            Metric::percent("Carved", self.carved, self.carves.len()),
+            Metric::integer("Dead Ends Removed", self.dead_ends_removed),
+        ]
+    }
+
+    fn progress(&self) -> Option<f64> {
+        Some(self.carved as f64 / self.carves.len().max(1) as f64)
+    }
+
+    fn notes(&self) -> &str {
+        "Building plans the whole maze, and each iteration carves the next \
+        few tiles in the order the algorithm opened them. Braiding knocks \
+        through a wall at the end of the chosen share of dead ends once the \
+        maze is done."
+    }
+
+    fn settings(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Algorithm", self.algorithm.name().to_string()),
+            ("Braid", format!("{}%", self.braid_percent)),
+            ("Carves Per Iteration", format!("{}", self.carves_per_iteration)),
+        ]
+    }
+
+    fn cycle_setting(&mut self, index: usize) {
+        match index {
+            0 => {
+                let idx = MazeAlgorithm::ALL.iter().position(|a| *a == self.algorithm).unwrap_or(0);
+                self.algorithm = MazeAlgorithm::ALL[(idx + 1) % MazeAlgorithm::ALL.len()];
+            },
+            1 => {
+                let idx = BRAID_PERCENTAGES.iter().position(|p| *p == self.braid_percent).map_or(0, |i| i + 1);
+                self.braid_percent = BRAID_PERCENTAGES[idx % BRAID_PERCENTAGES.len()];
+            },
+            2 => {
+                let idx = CARVES_PER_ITERATION.iter().position(|c| *c == self.carves_per_iteration).map_or(0, |i| i + 1);
+                self.carves_per_iteration = CARVES_PER_ITERATION[idx % CARVES_PER_ITERATION.len()];
+            },
+            _ => {}
+        }
+    }
+
+    fn set_setting(&mut self, index: usize, value: &str) -> Result<(), String> {
+        match index {
+            0 => self.algorithm = settings::choice("Algorithm", value, &MazeAlgorithm::ALL, MazeAlgorithm::name)?,
+            1 => self.braid_percent = settings::percent("Braid", value)?,
+            2 => self.carves_per_iteration = settings::at_least("Carves Per Iteration", value, 1)?,
+            _ => return Err(settings::no_setting(index)),
+        }
+        Ok(())
+    }
+}
+
+impl MazeBuilder {
+    pub fn new() -> MazeBuilder {
+        MazeBuilder {
+            map : Map::new(),
+            algorithm: MazeAlgorithm::RecursiveBacktracker,
+            braid_percent: 0,
+            carves_per_iteration: 5,
+            carves: Vec::new(),
+            carved: 0,
+            dead_ends_removed: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.map = Map::with_size(self.map.width, self.map.height);
+        self.carves.clear();
+        self.carved = 0;
+        self.dead_ends_removed = 0;
+    }
+
+    pub fn set_algorithm(&mut self, algorithm: MazeAlgorithm) {
+        self.algorithm = algorithm;
+    }
+
+    /// Sets the percentage of dead ends, from 0 to 100, opened up after the
+    /// maze is generated.
+    pub fn set_braid(&mut self, percent: u32) {
+        self.braid_percent = percent.min(100);
+    }
+
+    pub fn set_carves_per_iteration(&mut self, carves: usize) {
+        self.carves_per_iteration = carves.max(1);
+    }
+
+    /// True once every planned tile has been carved.
+    pub fn is_finished(&self) -> bool {
+        self.carved == self.carves.len()
+    }
+
+    /// Plans the maze with the selected algorithm, then braids it. Nothing
+    /// is carved until `iterate` is called.
+    pub fn generate(&mut self) {
+        let mut rng = random::rng();
+        let mut grid = MazeGrid::new((self.map.width - 1) / 2, (self.map.height - 1) / 2);
+
+        match self.algorithm {
+            MazeAlgorithm::RecursiveBacktracker => grid.recursive_backtracker(&mut rng),
+            MazeAlgorithm::Prim => grid.prim(&mut rng),
+            MazeAlgorithm::Kruskal => grid.kruskal(&mut rng),
+            MazeAlgorithm::Eller => grid.eller(&mut rng),
+            MazeAlgorithm::Wilson => grid.wilson(&mut rng),
+        }
+        self.dead_ends_removed = grid.braid(self.braid_percent, &mut rng);
+
+        self.carves = grid.carves;
+    }
+}
+
+impl Default for MazeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+// Cell (cx, cy) is tile (2cx + 1, 2cy + 1). Passages are tracked per cell
+// as open walls to the east and south.
+struct MazeGrid {
+    width: usize,
+    height: usize,
+    east: Vec<bool>,
+    south: Vec<bool>,
+    carves: Vec<(usize, usize)>,
+}
+
+impl MazeGrid {
+    fn new(width: usize, height: usize) -> MazeGrid {
+        MazeGrid {
+            width,
+            height,
+            east: vec![false; width * height],
+            south: vec![false; width * height],
+            carves: Vec::new(),
+        }
+    }
+
+    fn idx(&self, cx: usize, cy: usize) -> usize {
+        cy * self.width + cx
+    }
+
+    fn open_cell(&mut self, cx: usize, cy: usize) {
+        self.carves.push((2 * cx + 1, 2 * cy + 1));
+    }
+
+    // opens the wall between two orthogonally adjacent cells
+    fn link(&mut self, a: (usize, usize), b: (usize, usize)) {
+        let (first, second) = if (a.1, a.0) < (b.1, b.0) { (a, b) } else { (b, a) };
+        let idx = self.idx(first.0, first.1);
+        if second.0 > first.0 {
+            self.east[idx] = true;
+        } else {
+            self.south[idx] = true;
+        }
+        self.carves.push((first.0 + second.0 + 1, first.1 + second.1 + 1));
+    }
+
+    fn linked(&self, a: (usize, usize), b: (usize, usize)) -> bool {
+        let (first, second) = if (a.1, a.0) < (b.1, b.0) { (a, b) } else { (b, a) };
+        let idx = self.idx(first.0, first.1);
+        if second.0 > first.0 { self.east[idx] } else { self.south[idx] }
+    }
+
+    fn neighbours(&self, (cx, cy): (usize, usize)) -> Vec<(usize, usize)> {
+        let mut neighbours = Vec::with_capacity(4);
+        if cx > 0 { neighbours.push((cx - 1, cy)); }
+        if cy > 0 { neighbours.push((cx, cy - 1)); }
+        if cx + 1 < self.width { neighbours.push((cx + 1, cy)); }
+        if cy + 1 < self.height { neighbours.push((cx, cy + 1)); }
+        neighbours
+    }
+
+    fn random_cell<R: Rng>(&self, rng: &mut R) -> (usize, usize) {
+        (rng.gen_range(0..self.width), rng.gen_range(0..self.height))
+    }
+
+    fn recursive_backtracker<R: Rng>(&mut self, rng: &mut R) {
+        let mut visited = vec![false; self.width * self.height];
+        let start = self.random_cell(rng);
+        let mut stack = vec![start];
+        visited[self.idx(start.0, start.1)] = true;
+        self.open_cell(start.0, start.1);
+
+        while let Some(&current) = stack.last() {
+            let unvisited: Vec<(usize, usize)> = self.neighbours(current)
+                .into_iter()
+                .filter(|&(x, y)| !visited[self.idx(x, y)])
+                .collect();
+
+            match unvisited.choose(rng) {
+                Some(&next) => {
+                    visited[self.idx(next.0, next.1)] = true;
+                    self.link(current, next);
+                    self.open_cell(next.0, next.1);
+                    stack.push(next);
+                },
+                None => { stack.pop(); },
+            }
+        }
+    }
+
+    // randomized Prim's: grow from one cell, joining a random frontier cell
+    // to a random visited neighbour each step
+    fn prim<R: Rng>(&mut self, rng: &mut R) {
+        let mut visited = vec![false; self.width * self.height];
+        let mut in_frontier = vec![false; self.width * self.height];
+        let mut frontier = Vec::new();
+
+        let start = self.random_cell(rng);
+        visited[self.idx(start.0, start.1)] = true;
+        self.open_cell(start.0, start.1);
+        for n in self.neighbours(start) {
+            in_frontier[self.idx(n.0, n.1)] = true;
+            frontier.push(n);
+        }
+
+        while !frontier.is_empty() {
+            let cell = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+            let visited_neighbours: Vec<(usize, usize)> = self.neighbours(cell)
+                .into_iter()
+                .filter(|&(x, y)| visited[self.idx(x, y)])
+                .collect();
+            let &into = visited_neighbours.choose(rng).expect("frontier cells touch the maze");
+
+            visited[self.idx(cell.0, cell.1)] = true;
+            self.link(into, cell);
+            self.open_cell(cell.0, cell.1);
+
+            for n in self.neighbours(cell) {
+                let idx = self.idx(n.0, n.1);
+                if !visited[idx] && !in_frontier[idx] {
+                    in_frontier[idx] = true;
+                    frontier.push(n);
+                }
+            }
+        }
+    }
+
+    // randomized Kruskal's: knock through walls in random order whenever the
+    // cells on either side aren't joined yet
+    fn kruskal<R: Rng>(&mut self, rng: &mut R) {
+        let mut sets = DisjointSet::new(self.width * self.height);
+        let mut walls = Vec::new();
+        for cy in 0..self.height {
+            for cx in 0..self.width {
+                if cx + 1 < self.width { walls.push(((cx, cy), (cx + 1, cy))); }
+                if cy + 1 < self.height { walls.push(((cx, cy), (cx, cy + 1))); }
+            }
+        }
+        walls.shuffle(rng);
+
+        let mut opened = vec![false; self.width * self.height];
+        for (a, b) in walls {
+            let (ia, ib) = (self.idx(a.0, a.1), self.idx(b.0, b.1));
+            if sets.union(ia, ib) {
+                for (cell, idx) in [(a, ia), (b, ib)] {
+                    if !opened[idx] {
+                        opened[idx] = true;
+                        self.open_cell(cell.0, cell.1);
+                    }
+                }
+                self.link(a, b);
+            }
+        }
+
+        // a one cell maze has no walls to knock through
+        if !opened.iter().any(|o| *o) {
+            self.open_cell(0, 0);
+        }
+    }
+
+    // Eller's: works one row at a time, only remembering which set each cell
+    // of the current row belongs to
+    fn eller<R: Rng>(&mut self, rng: &mut R) {
+        let mut next_set = self.width;
+        let mut row_sets: Vec<usize> = (0..self.width).collect();
+
+        for cy in 0..self.height {
+            for cx in 0..self.width {
+                self.open_cell(cx, cy);
+            }
+
+            // join neighbours in different sets at random, always on the last row
+            let last_row = cy + 1 == self.height;
+            for cx in 0..self.width.saturating_sub(1) {
+                if row_sets[cx] != row_sets[cx + 1] && (last_row || rng.gen_bool(0.5)) {
+                    let merged = row_sets[cx + 1];
+                    let into = row_sets[cx];
+                    for set in row_sets.iter_mut() {
+                        if *set == merged { *set = into; }
+                    }
+                    self.link((cx, cy), (cx + 1, cy));
+                }
+            }
+
+            if last_row {
+                break;
+            }
+
+            // each set drops at least one passage down to the next row
+            let mut next_row: Vec<Option<usize>> = vec![None; self.width];
+            let mut sets: Vec<usize> = row_sets.clone();
+            sets.sort_unstable();
+            sets.dedup();
+            for set in sets {
+                let mut members: Vec<usize> = (0..self.width).filter(|&cx| row_sets[cx] == set).collect();
+                members.shuffle(rng);
+                let drops = rng.gen_range(1..=members.len());
+                for &cx in &members[..drops] {
+                    next_row[cx] = Some(set);
+                    self.link((cx, cy), (cx, cy + 1));
+                }
+            }
+
+            row_sets = next_row
+                .into_iter()
+                .map(|set| set.unwrap_or_else(|| {
+                    next_set += 1;
+                    next_set
+                }))
+                .collect();
+        }
+    }
+
+    // Wilson's: loop-erased random walks from unvisited cells until they hit
+    // the maze, which gives an unbiased spanning tree
+    fn wilson<R: Rng>(&mut self, rng: &mut R) {
+        let mut in_maze = vec![false; self.width * self.height];
+        let first = self.random_cell(rng);
+        in_maze[self.idx(first.0, first.1)] = true;
+        self.open_cell(first.0, first.1);
+
+        let mut remaining: Vec<(usize, usize)> = (0..self.height)
+            .flat_map(|cy| (0..self.width).map(move |cx| (cx, cy)))
+            .filter(|&c| c != first)
+            .collect();
+        remaining.shuffle(rng);
+
+        // direction each cell last left by during the current walk
+        let mut exits: Vec<Option<(usize, usize)>> = vec![None; self.width * self.height];
+
+        for start in remaining {
+            if in_maze[self.idx(start.0, start.1)] {
+                continue;
+            }
+
+            let mut current = start;
+            while !in_maze[self.idx(current.0, current.1)] {
+                let &next = self.neighbours(current).choose(rng).expect("cells have neighbours");
+                let idx = self.idx(current.0, current.1);
+                exits[idx] = Some(next);
+                current = next;
+            }
+
+            // retrace the walk, which skips any loops it made
+            let mut current = start;
+            while !in_maze[self.idx(current.0, current.1)] {
+                let idx = self.idx(current.0, current.1);
+                in_maze[idx] = true;
+                self.open_cell(current.0, current.1);
+                let next = exits[idx].expect("walked cells have an exit");
+                self.link(current, next);
+                current = next;
+            }
+        }
+    }
+
+    // opens one more wall from `percent` percent of the dead ends, preferring
+    // a neighbour that is itself a dead end
+    fn braid<R: Rng>(&mut self, percent: u32, rng: &mut R) -> usize {
+        let mut dead_ends: Vec<(usize, usize)> = (0..self.height)
+            .flat_map(|cy| (0..self.width).map(move |cx| (cx, cy)))
+            .filter(|&c| self.passages(c) == 1)
+            .collect();
+        dead_ends.shuffle(rng);
+
+        let target = dead_ends.len() * percent as usize / 100;
+        let mut removed = 0;
+        for cell in dead_ends {
+            if removed == target {
+                break;
+            }
+            // an earlier braid may already have opened this one up
+            if self.passages(cell) != 1 {
+                continue;
+            }
+
+            let closed: Vec<(usize, usize)> = self.neighbours(cell)
+                .into_iter()
+                .filter(|&n| !self.linked(cell, n))
+                .collect();
+            let best = closed.iter().copied().find(|&n| self.passages(n) == 1)
+                .or_else(|| closed.choose(rng).copied());
+
+            if let Some(n) = best {
+                self.link(cell, n);
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    fn passages(&self, cell: (usize, usize)) -> usize {
+        self.neighbours(cell).into_iter().filter(|&n| self.linked(cell, n)).count()
+    }
+}
+
+
+struct DisjointSet {
+    parents: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> DisjointSet {
+        DisjointSet { parents: (0..size).collect() }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        // point everything on the way straight at the root
+        let mut i = i;
+        while self.parents[i] != root {
+            let next = self.parents[i];
+            self.parents[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    // returns false if a and b were already in the same set
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (ra, rb) = (self.find(a), self.find(b));
+        if ra == rb {
+            return false;
+        }
+        self.parents[rb] = ra;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_builders::analysis::{self, Regions};
+
+    fn carved(algorithm: MazeAlgorithm, braid: u32, seed: u64) -> Map {
+        random::seed(seed);
+        let mut builder = MazeBuilder::new();
+        builder.set_algorithm(algorithm);
+        builder.set_braid(braid);
+        builder.build();
+        while !builder.is_finished() {
+            builder.iterate();
+        }
+        builder.get_map().clone()
+    }
+
+    #[test]
+    fn every_algorithm_makes_a_perfect_maze() {
+        for algorithm in MazeAlgorithm::ALL {
+            for seed in 0..3 {
+                let map = carved(algorithm, 0, seed);
+                assert_eq!(Regions::new(&map).count(), 1, "{}", algorithm.name());
+
+                // cells sit on odd tiles and passages between them, so a maze
+                // with no loops has one more cell than passages
+                let floors: Vec<(usize, usize)> = (0..map.tiles.len())
+                    .filter(|&i| map.tiles[i] == TileType::Floor)
+                    .map(|i| (i % map.width, i / map.width))
+                    .collect();
+                let cells = floors.iter().filter(|(x, y)| x % 2 == 1 && y % 2 == 1).count();
+                let passages = floors.len() - cells;
+                assert_eq!(cells, ((map.width - 1) / 2) * ((map.height - 1) / 2), "{}", algorithm.name());
+                assert_eq!(cells - passages, 1, "{}", algorithm.name());
+            }
+        }
+    }
+
+    #[test]
+    fn braiding_everything_leaves_no_dead_ends() {
+        for algorithm in MazeAlgorithm::ALL {
+            assert!(!analysis::dead_ends(&carved(algorithm, 0, 4)).is_empty());
+            let map = carved(algorithm, 100, 4);
+            assert!(analysis::dead_ends(&map).is_empty(), "{}", algorithm.name());
+            assert_eq!(Regions::new(&map).count(), 1);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                              Metrics                                  ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Serialize, Serializer};
+
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", content = "value")]
+pub enum MetricValue {
+    Integer(i64),
+    Float(f64),
+    /// From 0 to 100.
+    Percent(f64),
+    /// Serialized in microseconds.
+    #[serde(serialize_with = "micros")]
+    Duration(Duration),
+    Text(String),
+    Flag(bool),
+}
+
+/// A named value a builder reports about its current map.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Metric {
+    pub name: &'static str,
+    #[serde(flatten)]
+    pub value: MetricValue,
+    pub unit: Option<&'static str>,
+}
+
+impl Metric {
+    pub fn integer(name: &'static str, value: impl TryInto<i64>) -> Metric {
+        let value = value.try_into().unwrap_or(i64::MAX);
+        Metric { name, value: MetricValue::Integer(value), unit: None }
+    }
+
+    pub fn float(name: &'static str, value: f64) -> Metric {
+        Metric { name, value: MetricValue::Float(value), unit: None }
+    }
+
+    /// A share of `whole`, reported as a percentage.
+    pub fn percent(name: &'static str, part: usize, whole: usize) -> Metric {
+        let value = if whole == 0 { 0.0 } else { 100.0 * part as f64 / whole as f64 };
+        Metric { name, value: MetricValue::Percent(value), unit: None }
+    }
+
+    pub fn duration(name: &'static str, value: Duration) -> Metric {
+        Metric { name, value: MetricValue::Duration(value), unit: None }
+    }
+
+    pub fn text(name: &'static str, value: impl Into<String>) -> Metric {
+        Metric { name, value: MetricValue::Text(value.into()), unit: None }
+    }
+
+    pub fn flag(name: &'static str, value: bool) -> Metric {
+        Metric { name, value: MetricValue::Flag(value), unit: None }
+    }
+
+    pub fn with_unit(mut self, unit: &'static str) -> Metric {
+        self.unit = Some(unit);
+        self
+    }
+
+    /// The value as a number, for sorting and aggregating. Durations are in
+    /// microseconds and flags are 0 or 1.
+    pub fn as_f64(&self) -> Option<f64> {
+        match &self.value {
+            MetricValue::Integer(v) => Some(*v as f64),
+            MetricValue::Float(v) | MetricValue::Percent(v) => Some(*v),
+            MetricValue::Duration(v) => Some(v.as_secs_f64() * 1_000_000.0),
+            MetricValue::Flag(v) => Some(if *v { 1.0 } else { 0.0 }),
+            MetricValue::Text(_) => None,
+        }
+    }
+}
+
+fn micros<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_micros())
+}
+
+impl fmt::Display for MetricValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetricValue::Integer(v) => write!(f, "{}", v),
+            MetricValue::Float(v) => write!(f, "{:.2}", v),
+            MetricValue::Percent(v) => write!(f, "{:.1}%", v),
+            MetricValue::Duration(v) if v.as_millis() < 10 => write!(f, "{} µs", v.as_micros()),
+            MetricValue::Duration(v) => write!(f, "{} ms", v.as_millis()),
+            MetricValue::Text(v) => write!(f, "{}", v),
+            MetricValue::Flag(v) => write!(f, "{}", if *v { "yes" } else { "no" }),
+        }
+    }
+}
+
+/// Writes the value followed by its unit, if it has one.
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.unit {
+            Some(unit) => write!(f, "{} {}", self.value, unit),
+            None => write!(f, "{}", self.value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_display_with_their_units() {
+        assert_eq!(Metric::integer("Rooms", 12).with_unit("rooms").to_string(), "12 rooms");
+        assert_eq!(Metric::float("Threshold", 0.256).to_string(), "0.26");
+        assert_eq!(Metric::percent("Floor", 1, 3).to_string(), "33.3%");
+        assert_eq!(Metric::duration("Time", Duration::from_micros(950)).to_string(), "950 µs");
+        assert_eq!(Metric::duration("Time", Duration::from_millis(42)).to_string(), "42 ms");
+        assert_eq!(Metric::flag("Finished", true).to_string(), "yes");
+    }
+
+    #[test]
+    fn a_share_of_nothing_is_zero_percent() {
+        assert_eq!(Metric::percent("Floor", 0, 0).as_f64(), Some(0.0));
+    }
+
+    #[test]
+    fn numbers_are_read_back_for_aggregating() {
+        assert_eq!(Metric::integer("Rooms", 7).as_f64(), Some(7.0));
+        assert_eq!(Metric::duration("Time", Duration::from_millis(2)).as_f64(), Some(2000.0));
+        assert_eq!(Metric::flag("Finished", false).as_f64(), Some(0.0));
+        assert_eq!(Metric::text("Name", "BSP").as_f64(), None);
+    }
+
+    #[test]
+    fn metrics_serialize_with_their_type() {
+        let json = serde_json::to_value(Metric::duration("Time", Duration::from_millis(3)).with_unit("µs")).unwrap();
+        assert_eq!(json, serde_json::json!({ "name": "Time", "type": "Duration", "value": 3000, "unit": "µs" }));
+    }
+}
@@ -3,26 +3,134 @@ pub mod bsp_dungeon;
 pub mod cellular_automata;
 pub mod df_aggregation;
 pub mod drunkard;
+pub mod simple_map;
+pub mod noise_cave;
+pub mod maze;
+pub mod wfc;
+pub mod voronoi;
+pub mod rooms;
+pub mod corridors;
+pub mod room_shapes;
+pub mod export;
+pub mod autotile;
+pub mod analysis;
+pub mod fov;
+pub mod prefabs;
+pub mod pipeline;
+pub mod registry;
+pub mod settings;
+pub mod metrics;
+pub mod quality;
+pub mod worker;
+pub mod random;
+pub mod benchmark;
+pub mod recipe;
+pub mod constraints;
 
 use std::time::{Instant, Duration};
 
 use self::map::Map;
+use self::metrics::Metric;
+use self::prefabs::Placement;
+use self::quality::Quality;
+use self::rooms::RoomGraph;
+use self::voronoi::VoronoiRegion;
 
-pub trait MapBuilder<'a> {
+const MIN_RUN_LIMIT: usize = 500;
+
+pub trait MapBuilder: Send {
     fn build(&mut self);
-    fn get_map(&self) -> Map;
-    fn update_map_data(&self, map_data: &mut Vec<(&'a str, String)>);
+    fn get_map(&self) -> &Map;
+    /// The map itself, for post-processing a finished build in place.
+    fn get_map_mut(&mut self) -> &mut Map;
+    /// Figures describing the current map and the builder's progress.
+    fn metrics(&self) -> Vec<Metric>;
     fn iterate(&mut self);
     fn notes(&self) -> &str;
+
+    /// How far an iterative build has got, from 0 to 1, if the builder
+    /// knows. At 1 the map is finished and runs stop iterating, though
+    /// builders whose iterations only explore their settings may still
+    /// step on when asked.
+    fn progress(&self) -> Option<f64> {
+        None
+    }
+
+    /// For builders made of a chain of named steps, the name of the one
+    /// `build` runs if `building`, or the next call to `iterate` otherwise.
+    fn running_step(&self, _building: bool) -> Option<String> {
+        None
+    }
+
+    /// Rooms and corridors for builders that place them.
+    fn room_graph(&self) -> Option<&RoomGraph> {
+        None
+    }
+
+    /// The cells of a Voronoi diagram for builders that divide the map into
+    /// them.
+    fn regions(&self) -> Option<&[VoronoiRegion]> {
+        None
+    }
+
+    /// Names and current values of the settings `cycle_setting` can change.
+    fn settings(&self) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+
+    /// Advances the setting at `index` of `settings` to its next value. It
+    /// takes effect on the next build.
+    fn cycle_setting(&mut self, _index: usize) {}
+
+    /// Sets the setting at `index` of `settings` from text written the way
+    /// `settings` shows it, as in a recipe. It takes effect on the next build.
+    fn set_setting(&mut self, index: usize, _value: &str) -> Result<(), String> {
+        Err(settings::no_setting(index))
+    }
+
+    /// Changes the size of the maps made from the next build on.
+    fn resize(&mut self, width: usize, height: usize) {
+        *self.get_map_mut() = Map::with_size(width, height);
+    }
+
+    /// Prefabs stamped by the builder itself, with their markers.
+    fn placements(&self) -> &[Placement] {
+        &[]
+    }
+
+    /// Offers a map for builders that learn from examples. Returns whether
+    /// the builder took it.
+    fn learn_from(&mut self, _sample: &Map, _name: &str) -> bool {
+        false
+    }
+
+    /// Figures measuring the current map, the same for every builder.
+    fn quality(&self) -> Quality {
+        Quality::new(self.get_map(), self.room_graph())
+    }
+
+    /// The builder's own metrics followed by those of the map's quality.
+    fn report(&self) -> Vec<Metric> {
+        let mut metrics = self.metrics();
+        metrics.extend(self.quality().metrics());
+        metrics
+    }
+}
+
+/// The most iterations a run to completion makes after the build, for
+/// builders whose progress doesn't reach 1 by then. One per tile of `map`,
+/// so even a builder that opens a tile at a time can finish.
+pub fn run_limit(map: &Map) -> usize {
+    (map.width * map.height).max(MIN_RUN_LIMIT)
 }
 
-pub fn rebuild<'a, T: MapBuilder<'a>>(builder: &mut T) -> Duration {
+pub fn rebuild<T: MapBuilder + ?Sized>(builder: &mut T) -> Duration {
     let start = Instant::now();
     builder.build();
     start.elapsed()
 }
 
-pub fn iterate<'a, T: MapBuilder<'a>>(builder: &mut T) -> Duration {
+pub fn iterate<T: MapBuilder + ?Sized>(builder: &mut T) -> Duration {
     let start = Instant::now();
     builder.iterate();
     start.elapsed()
@@ -0,0 +1,423 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                           Noise Caves                                 ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use bracket_noise::prelude::{FastNoise, FractalType, NoiseType};
+use rand::Rng;
+
+use super::MapBuilder;
+use super::metrics::Metric;
+use super::random;
+use super::settings;
+use super::Map;
+use super::map::TileType;
+
+const MAX_THRESHOLD: f32 = 0.5;
+const THRESHOLD_STEP: f32 = 0.05;
+const MAX_OCTAVES: i32 = 8;
+const MIN_FREQUENCY: f32 = 0.001;
+const MAX_FREQUENCY: f32 = 1.0;
+const MAX_LACUNARITY: f32 = 4.0;
+
+const FREQUENCIES: [f32; 5] = [0.04, 0.06, 0.08, 0.12, 0.16];
+const LACUNARITIES: [f32; 4] = [1.5, 2.0, 2.5, 3.0];
+const GAINS: [f32; 4] = [0.3, 0.5, 0.7, 0.9];
+const THRESHOLDS: [f32; 5] = [-0.2, -0.1, 0.0, 0.1, 0.2];
+
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum NoiseKind {
+    Perlin,
+    Simplex,
+    /// The gap between the distances to the nearest and second nearest
+    /// cell points, which is smallest along the cell borders and so
+    /// opens a network of tunnels.
+    Cellular,
+}
+
+impl NoiseKind {
+    pub const ALL: [NoiseKind; 3] = [NoiseKind::Perlin, NoiseKind::Simplex, NoiseKind::Cellular];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoiseKind::Perlin => "Perlin",
+            NoiseKind::Simplex => "Simplex",
+            NoiseKind::Cellular => "Cellular",
+        }
+    }
+}
+
+/// Which parameter each iteration steps.
+#[derive(PartialEq, Copy, Clone)]
+pub enum NoiseStep {
+    Threshold,
+    Octaves,
+}
+
+impl NoiseStep {
+    pub const ALL: [NoiseStep; 2] = [NoiseStep::Threshold, NoiseStep::Octaves];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoiseStep::Threshold => "Threshold",
+            NoiseStep::Octaves => "Octaves",
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct NoiseParams {
+    pub kind: NoiseKind,
+    pub frequency: f32,
+    /// Fractal octaves, unused by cellular noise.
+    pub octaves: i32,
+    pub lacunarity: f32,
+    pub gain: f32,
+    /// Tiles whose noise value is below the threshold become floor. Noise
+    /// values fall roughly between -1 and 1.
+    pub threshold: f32,
+}
+
+impl Default for NoiseParams {
+    fn default() -> Self {
+        NoiseParams {
+            kind: NoiseKind::Perlin,
+            frequency: 0.08,
+            octaves: 3,
+            lacunarity: 2.0,
+            gain: 0.5,
+            threshold: 0.0,
+        }
+    }
+}
+
+
+pub struct NoiseCaveBuilder {
+    map : Map,
+    // the parameters as set, and the copy of them each build starts from
+    // and iterating steps
+    params: NoiseParams,
+    current: NoiseParams,
+    step: NoiseStep,
+    // fixed seed, or None to pick a new one on every build
+    fixed_seed: Option<u64>,
+    seed: u64,
+    iterations: i32,
+}
+
+impl MapBuilder for NoiseCaveBuilder {
+    fn build(&mut self) {
+        self.iterations = 0;
+        self.current = self.params;
+        self.seed = self.fixed_seed.unwrap_or_else(|| random::rng().gen());
+        self.generate();
+    }
+
+    fn iterate(&mut self) {
+        let stepped = match self.step {
+            NoiseStep::Threshold if self.current.threshold + THRESHOLD_STEP <= MAX_THRESHOLD + 1e-4 => {
+                // rounded so repeated steps don't drift
+                self.current.threshold = ((self.current.threshold + THRESHOLD_STEP) * 100.0).round() / 100.0;
+                true
+            },
+            NoiseStep::Octaves if self.current.octaves < MAX_OCTAVES => {
+                self.current.octaves += 1;
+                true
+            },
+            _ => false,
+        };
+
+        if stepped {
+            self.iterations += 1;
+            self.generate();
+        }
+    }
+
+    fn get_map(&self) -> &Map {
+        &self.map
+    }
+
+    fn get_map_mut(&mut self) -> &mut Map {
+        &mut self.map
+    }
+
+    fn metrics(&self) -> Vec<Metric> {
+        let num_floors = self.map.tiles.iter().filter(|t| **t == TileType::Floor).count();
+        vec![
+            Metric::text("Name", "Noise Caves"),
+            Metric::text("Seed", self.seed.to_string()),
+            Metric::integer("Iteration", self.iterations),
+            Metric::float("Threshold", self.current.threshold as f64),
+            Metric::integer("Octaves", self.current.octaves),
+            Metric::percent("Floor", num_floors, self.map.tiles.len()),
+        ]
+    }
+
+    // the map is finished as soon as it is built, stepping the threshold or
+    // octaves is only there to explore them, so run-to-completion callers
+    // keep the parameters as set
+    fn progress(&self) -> Option<f64> {
+        Some(1.0)
+    }
+
+    fn notes(&self) -> &str {
+        "Each iteration regenerates the same noise field with the threshold or \
+        octave count stepped up, so the effect of that parameter can be seen. \
+        Recipes, pipelines and benchmarks keep the map as first built."
+    }
+
+    fn settings(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Noise", self.params.kind.name().to_string()),
+            ("Frequency", format!("{:.2}", self.params.frequency)),
+            ("Octaves", format!("{}", self.params.octaves)),
+            ("Lacunarity", format!("{:.1}", self.params.lacunarity)),
+            ("Gain", format!("{:.1}", self.params.gain)),
+            ("Threshold", format!("{:.2}", self.params.threshold)),
+            ("Iterate Steps", self.step.name().to_string()),
+        ]
+    }
+
+    fn cycle_setting(&mut self, index: usize) {
+        let params = &mut self.params;
+        match index {
+            0 => {
+                let idx = NoiseKind::ALL.iter().position(|k| *k == params.kind).unwrap_or(0);
+                params.kind = NoiseKind::ALL[(idx + 1) % NoiseKind::ALL.len()];
+            },
+            1 => params.frequency = next_value(&FREQUENCIES, params.frequency),
+            2 => params.octaves = params.octaves % MAX_OCTAVES + 1,
+            3 => params.lacunarity = next_value(&LACUNARITIES, params.lacunarity),
+            4 => params.gain = next_value(&GAINS, params.gain),
+            5 => params.threshold = next_value(&THRESHOLDS, params.threshold),
+            6 => {
+                self.step = match self.step {
+                    NoiseStep::Threshold => NoiseStep::Octaves,
+                    NoiseStep::Octaves => NoiseStep::Threshold,
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn set_setting(&mut self, index: usize, value: &str) -> Result<(), String> {
+        let params = &mut self.params;
+        match index {
+            0 => params.kind = settings::choice("Noise", value, &NoiseKind::ALL, NoiseKind::name)?,
+            1 => params.frequency = settings::number("Frequency", value, MIN_FREQUENCY..=MAX_FREQUENCY)?,
+            2 => params.octaves = settings::number("Octaves", value, 1..=MAX_OCTAVES)?,
+            3 => params.lacunarity = settings::number("Lacunarity", value, 1.0..=MAX_LACUNARITY)?,
+            4 => params.gain = settings::number("Gain", value, 0.0..=1.0)?,
+            5 => params.threshold = settings::number("Threshold", value, -1.0..=1.0)?,
+            6 => self.step = settings::choice("Iterate Steps", value, &NoiseStep::ALL, NoiseStep::name)?,
+            _ => return Err(settings::no_setting(index)),
+        }
+        Ok(())
+    }
+}
+
+// the first value greater than `current`, wrapping to the start of the list
+fn next_value(values: &[f32], current: f32) -> f32 {
+    values.iter().copied().find(|v| *v > current + 1e-4).unwrap_or(values[0])
+}
+
+impl NoiseCaveBuilder {
+    pub fn new() -> NoiseCaveBuilder {
+        NoiseCaveBuilder {
+            map : Map::new(),
+            params: NoiseParams::default(),
+            current: NoiseParams::default(),
+            step: NoiseStep::Threshold,
+            fixed_seed: None,
+            seed: 0,
+            iterations: 0,
+        }
+    }
+
+    pub fn params(&self) -> NoiseParams {
+        self.params
+    }
+
+    /// Sets the noise parameters used from the next build on.
+    pub fn set_params(&mut self, params: NoiseParams) {
+        self.params = params;
+    }
+
+    /// Sets which parameter `iterate` steps.
+    pub fn set_step(&mut self, step: NoiseStep) {
+        self.step = step;
+    }
+
+    /// Uses `seed` for every build, or a new random seed each build if `None`.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.fixed_seed = seed;
+    }
+
+    /// Samples the noise at every tile and thresholds it into floors and
+    /// walls, keeping the outer edge of the map solid.
+    pub fn generate(&mut self) {
+        let noise = self.noise();
+        let (width, height) = (self.map.width, self.map.height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let edge = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+                let value = match self.current.kind {
+                    NoiseKind::Cellular => self.cellular(&noise, x as f32, y as f32),
+                    _ => noise.get_noise(x as f32, y as f32),
+                };
+                let tile = if !edge && value < self.current.threshold {
+                    TileType::Floor
+                } else {
+                    TileType::Wall
+                };
+                self.map.set(tile, x, y);
+            }
+        }
+    }
+
+    fn noise(&self) -> FastNoise {
+        let mut noise = FastNoise::seeded(self.seed);
+        noise.set_frequency(self.current.frequency);
+        noise.set_fractal_type(FractalType::FBM);
+        noise.set_fractal_octaves(self.current.octaves);
+        noise.set_fractal_lacunarity(self.current.lacunarity);
+        noise.set_fractal_gain(self.current.gain);
+
+        noise.set_noise_type(match self.current.kind {
+            NoiseKind::Perlin => NoiseType::PerlinFractal,
+            NoiseKind::Simplex => NoiseType::SimplexFractal,
+            NoiseKind::Cellular => NoiseType::WhiteNoise,
+        });
+        noise
+    }
+
+    // bracket-noise 0.8.7 indexes out of bounds for every cellular return
+    // type that compares the two nearest distances, Distance2Sub included,
+    // so the gap is worked out here with its white noise jittering one
+    // point per cell
+    fn cellular(&self, noise: &FastNoise, x: f32, y: f32) -> f32 {
+        let (x, y) = (x * self.current.frequency, y * self.current.frequency);
+        let (cell_x, cell_y) = (x.floor(), y.floor());
+        let mut nearest = [f32::MAX; 2];
+
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (cx, cy) = (cell_x + dx as f32, cell_y + dy as f32);
+                // white noise is sampled at the frequency scaled coordinates
+                let scale = 1.0 / self.current.frequency;
+                let jitter_x = noise.get_noise(cx * scale, cy * scale) * 0.45;
+                let jitter_y = noise.get_noise(cy * scale, cx * scale) * 0.45;
+                let (px, py) = (cx + 0.5 + jitter_x, cy + 0.5 + jitter_y);
+                let distance = ((px - x).powi(2) + (py - y).powi(2)).sqrt();
+
+                if distance < nearest[0] {
+                    nearest = [distance, nearest[0]];
+                } else if distance < nearest[1] {
+                    nearest[1] = distance;
+                }
+            }
+        }
+
+        // rescale the gap, which runs from 0 to about 1, so that a threshold
+        // of 0 opens roughly a third of the map like the other noise types
+        ((nearest[1] - nearest[0] - 0.15) * 4.0).clamp(-1.0, 1.0)
+    }
+}
+
+impl Default for NoiseCaveBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bracket_noise::prelude::CellularReturnType;
+
+    use super::*;
+
+    fn floors(kind: NoiseKind, threshold: f32) -> Vec<bool> {
+        let mut builder = NoiseCaveBuilder::new();
+        builder.set_params(NoiseParams { kind, threshold, ..NoiseParams::default() });
+        builder.set_seed(Some(11));
+        builder.build();
+        builder.get_map().tiles.iter().map(|t| *t == TileType::Floor).collect()
+    }
+
+    #[test]
+    fn every_kind_opens_a_cave_inside_a_solid_edge() {
+        for kind in NoiseKind::ALL {
+            let mut builder = NoiseCaveBuilder::new();
+            builder.set_params(NoiseParams { kind, ..NoiseParams::default() });
+            builder.set_seed(Some(11));
+            builder.build();
+            let map = builder.get_map();
+            let floor = map.tiles.iter().filter(|t| **t == TileType::Floor).count();
+            assert!(floor > 0 && floor < map.tiles.len(), "{}", kind.name());
+            for x in 0..map.width {
+                assert!(map.get(x, 0) == Some(&TileType::Wall) && map.get(x, map.height - 1) == Some(&TileType::Wall));
+            }
+            for y in 0..map.height {
+                assert!(map.get(0, y) == Some(&TileType::Wall) && map.get(map.width - 1, y) == Some(&TileType::Wall));
+            }
+
+            // the same seed gives the same cave
+            let text = map.to_text();
+            builder.build();
+            assert_eq!(builder.get_map().to_text(), text, "{}", kind.name());
+        }
+    }
+
+    #[test]
+    fn the_kinds_make_different_caves() {
+        let caves: Vec<Vec<bool>> = NoiseKind::ALL.iter().map(|&kind| floors(kind, 0.0)).collect();
+        assert_ne!(caves[0], caves[1]);
+        assert_ne!(caves[0], caves[2]);
+        assert_ne!(caves[1], caves[2]);
+    }
+
+    #[test]
+    fn raising_the_threshold_only_opens_more_floor() {
+        for kind in NoiseKind::ALL {
+            let none = floors(kind, -1.0);
+            assert!(none.iter().all(|f| !f), "{}", kind.name());
+
+            let mut previous = none;
+            for threshold in THRESHOLDS {
+                let current = floors(kind, threshold);
+                assert!(previous.iter().zip(&current).all(|(before, after)| !before || *after), "{} at {}", kind.name(), threshold);
+                assert!(current.iter().filter(|f| **f).count() >= previous.iter().filter(|f| **f).count());
+                previous = current;
+            }
+        }
+    }
+
+    #[test]
+    fn cellular_values_stay_between_minus_one_and_one() {
+        let mut builder = NoiseCaveBuilder::new();
+        builder.set_params(NoiseParams { kind: NoiseKind::Cellular, ..NoiseParams::default() });
+        builder.set_seed(Some(4));
+        builder.build();
+        let noise = builder.noise();
+        for y in 0..40 {
+            for x in 0..40 {
+                let value = builder.cellular(&noise, x as f32, y as f32);
+                assert!((-1.0..=1.0).contains(&value));
+            }
+        }
+    }
+
+    // when this stops panicking, `cellular` can give way to bracket-noise's
+    // own cellular noise
+    #[test]
+    #[should_panic]
+    fn bracket_noise_cannot_subtract_cellular_distances() {
+        let mut noise = FastNoise::seeded(1);
+        noise.set_noise_type(NoiseType::Cellular);
+        noise.set_cellular_return_type(CellularReturnType::Distance2Sub);
+        noise.get_noise(1.0, 1.0);
+    }
+}
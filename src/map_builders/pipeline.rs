@@ -0,0 +1,536 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                             Pipelines                                 ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::{fs, io, path::Path};
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use super::MapBuilder;
+use super::analysis::Regions;
+use super::metrics::Metric;
+use super::random;
+use super::settings;
+use super::Map;
+use super::map::TileType;
+use super::prefabs::{Placement, PlacementMode, Prefab, PrefabStamper};
+use super::bsp_dungeon::BSPDungeonBuilder;
+use super::registry;
+use super::run_limit;
+
+const EROSION_STEPS: usize = 200;
+const PIPELINE_ID: &str = "pipeline";
+
+
+/// A step that reworks a finished map.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Modifier {
+    /// Passes of the 4-5 rule: a tile with five or more wall neighbours
+    /// becomes a wall and one with three or fewer becomes a floor.
+    CaSmoothing { passes: usize },
+    /// Fills in every floor that can't reach the largest open area.
+    CullUnreachable,
+    /// Particles wander in from the rock and stick next to the first floor
+    /// they meet, eating away at the walls.
+    DlaErosion { particles: usize },
+    /// Drunkards set off from random floors, carving as they stagger.
+    DrunkardWidening { diggers: usize, steps: usize },
+    StampPrefabs { mode: PlacementMode, count: usize },
+    /// Walls in the outer ring of the map.
+    EnforceBorder,
+}
+
+impl Modifier {
+    pub fn name(&self) -> String {
+        match self {
+            Modifier::CaSmoothing { passes } => format!("CA Smoothing x{}", passes),
+            Modifier::CullUnreachable => "Cull Unreachable".to_string(),
+            Modifier::DlaErosion { particles } => format!("DLA Erosion ({})", particles),
+            Modifier::DrunkardWidening { diggers, steps } => format!("Drunkard Widening ({} x {})", diggers, steps),
+            Modifier::StampPrefabs { mode, count } => format!("Stamp {} Prefabs ({})", count, mode.name()),
+            Modifier::EnforceBorder => "Enforce Border".to_string(),
+        }
+    }
+
+    /// Applies the modifier to `map`, returning any prefabs it stamped.
+    pub fn apply<R: Rng>(&self, map: &mut Map, stamper: &mut PrefabStamper, rng: &mut R) -> Vec<Placement> {
+        match *self {
+            Modifier::CaSmoothing { passes } => (0..passes).for_each(|_| smooth(map)),
+            Modifier::CullUnreachable => cull_unreachable(map),
+            Modifier::DlaErosion { particles } => (0..particles).for_each(|_| erode(map, rng)),
+            Modifier::DrunkardWidening { diggers, steps } => (0..diggers).for_each(|_| stagger(map, steps, rng)),
+            Modifier::StampPrefabs { mode, count } => {
+                stamper.mode = mode;
+                stamper.max_stamps = count;
+                return stamper.stamp(map, rng);
+            },
+            Modifier::EnforceBorder => enforce_border(map),
+        }
+        Vec::new()
+    }
+}
+
+/// A pipeline as written in a config file.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PipelineConfig {
+    pub name: String,
+    /// The id of a builder in the registry, other than a pipeline.
+    pub initial: String,
+    /// The most times the initial builder is iterated after it is built. It
+    /// stops sooner once its progress reaches 1. Left out, it is the run
+    /// limit of the map.
+    #[serde(default, alias = "iterations", skip_serializing_if = "Option::is_none")]
+    pub max_iterations: Option<usize>,
+    #[serde(default)]
+    pub modifiers: Vec<Modifier>,
+}
+
+impl PipelineConfig {
+    pub fn from_json(text: &str) -> serde_json::Result<PipelineConfig> {
+        serde_json::from_str(text)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<PipelineConfig> {
+        let text = fs::read_to_string(path)?;
+        PipelineConfig::from_json(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn built_in() -> Vec<PipelineConfig> {
+        vec![
+            PipelineConfig {
+                name: "BSP Caves".to_string(),
+                initial: "bsp".to_string(),
+                max_iterations: None,
+                // widened first, since smoothing fills in narrow corridors
+                modifiers: vec![
+                    Modifier::DrunkardWidening { diggers: 40, steps: 60 },
+                    Modifier::CaSmoothing { passes: 2 },
+                    Modifier::CullUnreachable,
+                ],
+            },
+            PipelineConfig {
+                name: "Eroded Maze".to_string(),
+                initial: "maze".to_string(),
+                max_iterations: None,
+                modifiers: vec![Modifier::DlaErosion { particles: 150 }, Modifier::EnforceBorder],
+            },
+            PipelineConfig {
+                name: "Drunken Vaults".to_string(),
+                initial: "drunkard".to_string(),
+                max_iterations: None,
+                modifiers: vec![
+                    Modifier::DrunkardWidening { diggers: 8, steps: 40 },
+                    Modifier::StampPrefabs { mode: PlacementMode::OverwriteWalls, count: 3 },
+                    Modifier::EnforceBorder,
+                    Modifier::CullUnreachable,
+                ],
+            },
+        ]
+    }
+}
+
+/// Runs an initial builder, then a chain of modifiers over its map. Building
+/// runs the initial builder until it finishes, and each iteration applies
+/// the next modifier.
+pub struct PipelineBuilder {
+    map : Map,
+    name: String,
+    initial_name: String,
+    initial: Box<dyn MapBuilder>,
+    max_iterations: Option<usize>,
+    // times the initial builder was iterated in the last build
+    initial_iterations: usize,
+    modifiers: Vec<Modifier>,
+    stamper: PrefabStamper,
+    placements: Vec<Placement>,
+    // steps run so far, counting the initial builder as the first
+    steps_run: usize,
+    presets: Vec<PipelineConfig>,
+    preset_index: usize,
+}
+
+impl MapBuilder for PipelineBuilder {
+    fn build(&mut self) {
+        self.placements.clear();
+        self.initial.build();
+        self.initial_iterations = 0;
+        let max_iterations = self.max_iterations.unwrap_or_else(|| run_limit(self.initial.get_map()));
+        while self.initial_iterations < max_iterations && !self.initial.progress().is_some_and(|p| p >= 1.0) {
+            self.initial.iterate();
+            self.initial_iterations += 1;
+        }
+        self.map = self.initial.get_map().clone();
+        self.steps_run = 1;
+    }
+
+    fn iterate(&mut self) {
+        // nothing runs until the pipeline is built
+        let next = self.steps_run.checked_sub(1).and_then(|i| self.modifiers.get(i));
+        if let Some(modifier) = next {
+            let mut rng = random::rng();
+            let placements = modifier.apply(&mut self.map, &mut self.stamper, &mut rng);
+            self.placements.extend(placements);
+            self.steps_run += 1;
+        }
+    }
+
+    fn get_map(&self) -> &Map {
+        &self.map
+    }
+
+    fn get_map_mut(&mut self) -> &mut Map {
+        &mut self.map
+    }
+
+    fn metrics(&self) -> Vec<Metric> {
+        let names = self.step_names();
+        let current = match self.steps_run {
+            0 => "Not built".to_string(),
+            n => names[n - 1].clone(),
+        };
+        let next = names.get(self.steps_run).cloned().unwrap_or_else(|| "Finished".to_string());
+
+        vec![
+            Metric::text("Name", self.name.clone()),
+            Metric::integer("Step", self.steps_run),
+            Metric::integer("Total Steps", names.len()),
+            Metric::text("Last Step", current),
+            Metric::text("Next Step", next),
+            Metric::text("Steps", names.join(" -> ")),
+            Metric::integer("Initial Iterations", self.initial_iterations),
+            Metric::integer("Prefabs Stamped", self.placements.len()),
+        ]
+    }
+
+    fn progress(&self) -> Option<f64> {
+        Some(self.steps_run as f64 / (self.modifiers.len() + 1) as f64)
+    }
+
+    fn running_step(&self, building: bool) -> Option<String> {
+        let names = self.step_names();
+        match (building, self.steps_run) {
+            (true, _) => names.into_iter().next(),
+            // iterating does nothing until the pipeline is built
+            (false, 0) => None,
+            (false, n) => names.into_iter().nth(n),
+        }
+    }
+
+    fn notes(&self) -> &str {
+        "Building runs the pipeline's first builder to completion, then each \
+        iteration applies the next modifier in the chain."
+    }
+
+    fn settings(&self) -> Vec<(&'static str, String)> {
+        vec![("Pipeline", self.name.clone())]
+    }
+
+    fn cycle_setting(&mut self, index: usize) {
+        if index == 0 && !self.presets.is_empty() {
+            self.preset_index = (self.preset_index + 1) % self.presets.len();
+            let config = self.presets[self.preset_index].clone();
+            // presets are checked when they are added
+            let _ = self.apply_config(&config);
+        }
+    }
+
+    fn set_setting(&mut self, index: usize, value: &str) -> Result<(), String> {
+        if index != 0 {
+            return Err(settings::no_setting(index));
+        }
+        let config = settings::choice("Pipeline", value, &self.presets, |config| config.name.clone())?;
+        self.preset_index = self.presets.iter().position(|preset| preset.name == config.name).unwrap_or(0);
+        self.apply_config(&config)
+    }
+
+    fn resize(&mut self, width: usize, height: usize) {
+        self.map = Map::with_size(width, height);
+        self.initial.resize(width, height);
+    }
+
+    fn placements(&self) -> &[Placement] {
+        &self.placements
+    }
+}
+
+impl PipelineBuilder {
+    pub fn new() -> PipelineBuilder {
+        let presets = PipelineConfig::built_in();
+        let mut pipeline = PipelineBuilder::from_config(&presets[0]).expect("built in pipelines are valid");
+        pipeline.presets = presets;
+        pipeline
+    }
+
+    /// Starts a pipeline in code. `initial_name` is only used for display.
+    /// The initial builder is iterated until it finishes, at most
+    /// `max_iterations` times, or the run limit of its map if `None`.
+    pub fn with_initial(name: &str, initial_name: &str, initial: Box<dyn MapBuilder>, max_iterations: Option<usize>) -> PipelineBuilder {
+        PipelineBuilder {
+            map : Map::new(),
+            name: name.to_string(),
+            initial_name: initial_name.to_string(),
+            initial,
+            max_iterations,
+            initial_iterations: 0,
+            modifiers: Vec::new(),
+            stamper: PrefabStamper::new(),
+            placements: Vec::new(),
+            steps_run: 0,
+            presets: Vec::new(),
+            preset_index: 0,
+        }
+    }
+
+    /// Adds a modifier to the end of the chain.
+    pub fn then(mut self, modifier: Modifier) -> PipelineBuilder {
+        self.modifiers.push(modifier);
+        self
+    }
+
+    pub fn from_config(config: &PipelineConfig) -> Result<PipelineBuilder, String> {
+        let mut pipeline = PipelineBuilder::with_initial(&config.name, &config.initial, Box::new(BSPDungeonBuilder::new()), None);
+        pipeline.apply_config(config)?;
+        Ok(pipeline)
+    }
+
+    /// Adds a pipeline to the ones `cycle_setting` steps through.
+    pub fn add_preset(&mut self, config: PipelineConfig) -> Result<(), String> {
+        create_initial(&config.initial)?;
+        self.presets.push(config);
+        Ok(())
+    }
+
+    /// Adds every `.json` pipeline in `dir` as a preset.
+    pub fn load_presets(&mut self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let mut paths: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let config = PipelineConfig::load(&path)?;
+            self.add_preset(config).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        Ok(())
+    }
+
+    /// Sets the prefabs used by `StampPrefabs` steps.
+    pub fn set_prefabs(&mut self, prefabs: Vec<Prefab>) {
+        self.stamper.set_prefabs(prefabs);
+    }
+
+    /// The display names of every step, starting with the initial builder.
+    pub fn step_names(&self) -> Vec<String> {
+        let mut names = vec![self.initial_name.clone()];
+        names.extend(self.modifiers.iter().map(|m| m.name()));
+        names
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.steps_run == self.modifiers.len() + 1
+    }
+
+    fn apply_config(&mut self, config: &PipelineConfig) -> Result<(), String> {
+        self.initial = create_initial(&config.initial)?;
+        self.initial.resize(self.map.width, self.map.height);
+        self.name = config.name.clone();
+        self.initial_name = config.initial.clone();
+        self.max_iterations = config.max_iterations;
+        self.modifiers = config.modifiers.clone();
+        self.steps_run = 0;
+        Ok(())
+    }
+}
+
+// any builder in the registry but a pipeline, which would only nest
+fn create_initial(id: &str) -> Result<Box<dyn MapBuilder>, String> {
+    (id != PIPELINE_ID).then(|| registry::create(id)).flatten().ok_or_else(|| {
+        let ids: Vec<&str> = registry::ids().into_iter().filter(|&id| id != PIPELINE_ID).collect();
+        format!("unknown first builder '{}', expected one of {}", id, ids.join(", "))
+    })
+}
+
+fn smooth(map: &mut Map) {
+    let mut tiles = map.tiles.clone();
+    for y in 1..map.height - 1 {
+        for x in 1..map.width - 1 {
+            let walls = (-1..=1)
+                .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                .filter(|&(dx, dy)| (dx, dy) != (0, 0))
+                .filter(|&(dx, dy)| {
+                    let (nx, ny) = ((x as i32 + dx) as usize, (y as i32 + dy) as usize);
+                    map.tiles[ny * map.width + nx] == TileType::Wall
+                })
+                .count();
+            if walls >= 5 {
+                tiles[y * map.width + x] = TileType::Wall;
+            } else if walls <= 3 {
+                tiles[y * map.width + x] = TileType::Floor;
+            }
+        }
+    }
+    map.tiles = tiles;
+}
+
+fn cull_unreachable(map: &mut Map) {
+    let regions = Regions::new(map);
+    let largest = regions.largest();
+    for (tile, label) in map.tiles.iter_mut().zip(regions.labels) {
+        if label.is_some() && label != largest {
+            *tile = TileType::Wall;
+        }
+    }
+}
+
+fn erode<R: Rng>(map: &mut Map, rng: &mut R) {
+    let (width, height) = (map.width, map.height);
+    let (mut x, mut y) = (rng.gen_range(1..width - 1), rng.gen_range(1..height - 1));
+    if map.get(x, y) == Some(&TileType::Floor) {
+        return;
+    }
+
+    for _ in 0..EROSION_STEPS {
+        let (nx, ny) = step(x, y, width, height, rng);
+        if map.get(nx, ny) == Some(&TileType::Floor) {
+            map.set(TileType::Floor, x, y);
+            return;
+        }
+        (x, y) = (nx, ny);
+    }
+}
+
+fn stagger<R: Rng>(map: &mut Map, steps: usize, rng: &mut R) {
+    let (width, height) = (map.width, map.height);
+    let floors: Vec<usize> = (0..map.tiles.len())
+        .filter(|&i| map.tiles[i] == TileType::Floor)
+        .filter(|&i| (1..width - 1).contains(&(i % width)) && (1..height - 1).contains(&(i / width)))
+        .collect();
+    let Some(&start) = floors.choose(rng) else {
+        return;
+    };
+
+    let (mut x, mut y) = (start % width, start / width);
+    for _ in 0..steps {
+        (x, y) = step(x, y, width, height, rng);
+        map.set(TileType::Floor, x, y);
+    }
+}
+
+// a random step in one of the four directions, kept off the outer ring
+fn step<R: Rng>(x: usize, y: usize, width: usize, height: usize, rng: &mut R) -> (usize, usize) {
+    let (nx, ny) = match rng.gen_range(0..4) {
+        0 => (x, y - 1),
+        1 => (x, y + 1),
+        2 => (x - 1, y),
+        _ => (x + 1, y),
+    };
+    (nx.clamp(1, width - 2), ny.clamp(1, height - 2))
+}
+
+fn enforce_border(map: &mut Map) {
+    for x in 0..map.width {
+        map.set(TileType::Wall, x, 0);
+        map.set(TileType::Wall, x, map.height - 1);
+    }
+    for y in 0..map.height {
+        map.set(TileType::Wall, 0, y);
+        map.set(TileType::Wall, map.width - 1, y);
+    }
+}
+
+impl Default for PipelineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_ROOMS: &str = "\
+##########
+#...######
+#...###..#
+#...###..#
+##########";
+
+    fn apply(modifier: Modifier, map: &mut Map) {
+        modifier.apply(map, &mut PrefabStamper::new(), &mut random::rng());
+    }
+
+    #[test]
+    fn modifiers_run_in_order_one_per_iteration() {
+        random::seed(9);
+        let mut pipeline = PipelineBuilder::with_initial("Test", "bsp", registry::create("bsp").unwrap(), None)
+            .then(Modifier::EnforceBorder)
+            .then(Modifier::CaSmoothing { passes: 1 })
+            .then(Modifier::CullUnreachable);
+        pipeline.build();
+        assert_eq!(pipeline.step_names(), vec!["bsp", "Enforce Border", "CA Smoothing x1", "Cull Unreachable"]);
+
+        let mut expected = pipeline.get_map().clone();
+        for modifier in [Modifier::EnforceBorder, Modifier::CaSmoothing { passes: 1 }, Modifier::CullUnreachable] {
+            assert_eq!(pipeline.running_step(false), Some(modifier.name()));
+            pipeline.iterate();
+            apply(modifier, &mut expected);
+            assert_eq!(pipeline.get_map().to_text(), expected.to_text());
+        }
+        assert!(pipeline.is_finished());
+        assert_eq!(pipeline.progress(), Some(1.0));
+
+        // a finished pipeline leaves the map alone
+        pipeline.iterate();
+        assert_eq!(pipeline.get_map().to_text(), expected.to_text());
+    }
+
+    #[test]
+    fn culling_leaves_only_the_largest_region() {
+        let mut map = Map::from_text(TWO_ROOMS).unwrap();
+        assert_eq!(Regions::new(&map).count(), 2);
+        apply(Modifier::CullUnreachable, &mut map);
+
+        let regions = Regions::new(&map);
+        assert_eq!(regions.count(), 1);
+        assert_eq!(regions.sizes, vec![9]);
+        assert!(map.get(2, 2) == Some(&TileType::Floor));
+        assert!(map.get(7, 2) == Some(&TileType::Wall));
+    }
+
+    #[test]
+    fn enforcing_the_border_walls_only_the_outer_ring() {
+        let mut map = Map::from_text(&[".....", ".....", ".....", "....."].join("\n")).unwrap();
+        apply(Modifier::EnforceBorder, &mut map);
+        assert_eq!(map.to_text().trim_end(), ["#####", "#...#", "#...#", "#####"].join("\n"));
+    }
+
+    #[test]
+    fn configs_round_trip_through_json() {
+        for config in PipelineConfig::built_in() {
+            let json = serde_json::to_string(&config).unwrap();
+            let read = PipelineConfig::from_json(&json).unwrap();
+            assert_eq!(serde_json::to_string(&read).unwrap(), json);
+            assert_eq!(read.modifiers.len(), config.modifiers.len());
+        }
+
+        let config = PipelineConfig::from_json(r#"{"name": "Short", "initial": "maze", "iterations": 5}"#).unwrap();
+        assert_eq!(config.max_iterations, Some(5));
+        assert!(config.modifiers.is_empty());
+    }
+
+    #[test]
+    fn a_pipeline_cannot_start_with_a_pipeline() {
+        let error = create_initial(PIPELINE_ID).err().expect("pipelines are refused");
+        assert!(error.contains("unknown first builder 'pipeline'"));
+        assert!(!error.contains("pipeline,") && !error.ends_with("pipeline"));
+        assert!(create_initial("bsp").is_ok());
+
+        let config = PipelineConfig { name: "Nested".to_string(), initial: PIPELINE_ID.to_string(), max_iterations: None, modifiers: Vec::new() };
+        assert!(PipelineBuilder::from_config(&config).is_err());
+    }
+}
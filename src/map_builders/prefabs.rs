@@ -0,0 +1,377 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                              Prefabs                                  ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::{fs, io, path::Path};
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use super::Map;
+use super::map::TileType;
+
+const DEFAULT_MAX_STAMPS: usize = 3;
+
+const BUILT_IN: [(&str, &str); 4] = [
+    ("vault", include_str!("../../prefabs/vault.txt")),
+    ("treasury", include_str!("../../prefabs/treasury.txt")),
+    ("shrine", include_str!("../../prefabs/shrine.txt")),
+    ("ambush", include_str!("../../prefabs/ambush.txt")),
+];
+
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum PrefabTile {
+    Wall,
+    Floor,
+    /// Leaves the map tile underneath as it is.
+    Any,
+    Spawn,
+    Loot,
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum MarkerKind {
+    Spawn,
+    Loot,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Marker {
+    pub kind: MarkerKind,
+    pub x: usize,
+    pub y: usize,
+}
+
+/// A hand-authored room template. In the text format `#` is a wall, `.` a
+/// floor, `S` a spawn and `$` loot, both of which are floors in the map,
+/// and `?` or a space leaves the map tile alone. Rows of spaces count, but
+/// empty lines before the first row and after the last don't.
+#[derive(Clone)]
+pub struct Prefab {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub tiles: Vec<PrefabTile>,
+}
+
+impl Prefab {
+    pub fn from_text(name: &str, text: &str) -> Result<Prefab, String> {
+        // only empty lines around the rows are dropped, since a row of
+        // spaces is a row of tiles left alone
+        let mut lines: Vec<&str> = text.lines().map(|l| l.trim_end_matches('\r')).collect();
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        let first = lines.iter().position(|l| !l.is_empty()).unwrap_or(lines.len());
+        let lines = &lines[first..];
+        let width = lines.iter().map(|l| l.chars().count()).max().ok_or("prefab is empty")?;
+
+        let mut tiles = Vec::with_capacity(width * lines.len());
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                tiles.push(match c {
+                    '#' => PrefabTile::Wall,
+                    '.' => PrefabTile::Floor,
+                    '?' | ' ' => PrefabTile::Any,
+                    'S' => PrefabTile::Spawn,
+                    '$' => PrefabTile::Loot,
+                    _ => return Err(format!("unknown tile '{}' at {}, {}", c, x, y)),
+                });
+            }
+            // short rows are padded, since trailing spaces are easy to lose
+            tiles.resize(width * (y + 1), PrefabTile::Any);
+        }
+
+        Ok(Prefab { name: name.to_string(), width, height: lines.len(), tiles })
+    }
+
+    /// Loads a prefab from a text file, named after the file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Prefab> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let name = path.file_stem().map_or("prefab".into(), |s| s.to_string_lossy());
+        Prefab::from_text(&name, &text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn built_in() -> Vec<Prefab> {
+        BUILT_IN
+            .iter()
+            .map(|(name, text)| Prefab::from_text(name, text).expect("built in prefabs are valid"))
+            .collect()
+    }
+
+    fn get(&self, x: usize, y: usize) -> PrefabTile {
+        self.tiles[y * self.width + x]
+    }
+}
+
+/// Where prefabs are allowed to go.
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum PlacementMode {
+    /// Vaults cut into solid rock. Every tile the prefab sets has to be a
+    /// wall, and one of its floors has to touch a floor of the map so it
+    /// can be reached.
+    OverwriteWalls,
+    /// Set-pieces dropped into open space. Every tile the prefab sets has
+    /// to be a floor already.
+    OverlapFloor,
+}
+
+impl PlacementMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlacementMode::OverwriteWalls => "Overwrite Walls",
+            PlacementMode::OverlapFloor => "Overlap Floor",
+        }
+    }
+}
+
+/// A prefab stamped into a map, with its markers in map coordinates.
+#[derive(Clone)]
+pub struct Placement {
+    pub name: String,
+    pub x: usize,
+    pub y: usize,
+    pub markers: Vec<Marker>,
+}
+
+/// Stamps prefabs into a finished map. Runs after any builder.
+#[derive(Clone)]
+pub struct PrefabStamper {
+    prefabs: Vec<Prefab>,
+    pub mode: PlacementMode,
+    pub max_stamps: usize,
+}
+
+impl PrefabStamper {
+    pub fn new() -> PrefabStamper {
+        PrefabStamper {
+            prefabs: Prefab::built_in(),
+            mode: PlacementMode::OverwriteWalls,
+            max_stamps: DEFAULT_MAX_STAMPS,
+        }
+    }
+
+    pub fn prefabs(&self) -> &[Prefab] {
+        &self.prefabs
+    }
+
+    pub fn set_prefabs(&mut self, prefabs: Vec<Prefab>) {
+        self.prefabs = prefabs;
+    }
+
+    /// Replaces the prefabs with every `.txt` file in `dir`.
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let mut paths: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+            .collect();
+        paths.sort();
+
+        self.prefabs = paths.iter().map(Prefab::load).collect::<io::Result<_>>()?;
+        Ok(())
+    }
+
+    /// Stamps up to `max_stamps` prefabs where they fit, never overlapping
+    /// each other. Returns what was placed.
+    pub fn stamp<R: Rng>(&self, map: &mut Map, rng: &mut R) -> Vec<Placement> {
+        let mut placements = Vec::new();
+        let mut taken = vec![false; map.tiles.len()];
+
+        let mut order: Vec<&Prefab> = self.prefabs.iter().collect();
+        for _ in 0..self.max_stamps {
+            order.shuffle(rng);
+            let placed = order.iter().find_map(|prefab| {
+                let spots = self.spots(map, prefab, &taken);
+                spots.choose(rng).map(|&(x, y)| (*prefab, x, y))
+            });
+
+            let Some((prefab, x, y)) = placed else {
+                break;
+            };
+            for py in 0..prefab.height {
+                for px in 0..prefab.width {
+                    taken[(y + py) * map.width + x + px] = true;
+                }
+            }
+            placements.push(apply(map, prefab, x, y));
+        }
+        placements
+    }
+
+    // every top left corner the prefab fits at
+    fn spots(&self, map: &Map, prefab: &Prefab, taken: &[bool]) -> Vec<(usize, usize)> {
+        let mut spots = Vec::new();
+        if prefab.width + 2 > map.width || prefab.height + 2 > map.height {
+            return spots;
+        }
+
+        // the outer ring of the map is never touched
+        for y in 1..map.height - prefab.height {
+            for x in 1..map.width - prefab.width {
+                if self.fits(map, prefab, taken, x, y) {
+                    spots.push((x, y));
+                }
+            }
+        }
+        spots
+    }
+
+    fn fits(&self, map: &Map, prefab: &Prefab, taken: &[bool], x: usize, y: usize) -> bool {
+        let wanted = match self.mode {
+            PlacementMode::OverwriteWalls => TileType::Wall,
+            PlacementMode::OverlapFloor => TileType::Floor,
+        };
+
+        let mut reachable = self.mode == PlacementMode::OverlapFloor;
+        for py in 0..prefab.height {
+            for px in 0..prefab.width {
+                let idx = (y + py) * map.width + x + px;
+                if taken[idx] {
+                    return false;
+                }
+                let tile = prefab.get(px, py);
+                if tile == PrefabTile::Any {
+                    continue;
+                }
+                if map.tiles[idx] != wanted {
+                    return false;
+                }
+                if tile != PrefabTile::Wall && !reachable {
+                    reachable = touches_floor(map, prefab, x, y, px, py);
+                }
+            }
+        }
+        reachable
+    }
+}
+
+// whether a floor of the prefab sits next to a map floor the prefab won't
+// cover
+fn touches_floor(map: &Map, prefab: &Prefab, x: usize, y: usize, px: usize, py: usize) -> bool {
+    let neighbours = [(px as i32 - 1, py as i32), (px as i32 + 1, py as i32), (px as i32, py as i32 - 1), (px as i32, py as i32 + 1)];
+    neighbours.iter().any(|&(nx, ny)| {
+        let inside = nx >= 0 && ny >= 0 && (nx as usize) < prefab.width && (ny as usize) < prefab.height;
+        let covered = inside && prefab.get(nx as usize, ny as usize) != PrefabTile::Any;
+        let (mx, my) = ((x as i32 + nx) as usize, (y as i32 + ny) as usize);
+        !covered && map.get(mx, my) == Some(&TileType::Floor)
+    })
+}
+
+fn apply(map: &mut Map, prefab: &Prefab, x: usize, y: usize) -> Placement {
+    let mut markers = Vec::new();
+    for py in 0..prefab.height {
+        for px in 0..prefab.width {
+            let (mx, my) = (x + px, y + py);
+            match prefab.get(px, py) {
+                PrefabTile::Any => continue,
+                PrefabTile::Wall => {
+                    map.set(TileType::Wall, mx, my);
+                },
+                PrefabTile::Floor => {
+                    map.set(TileType::Floor, mx, my);
+                },
+                PrefabTile::Spawn => {
+                    map.set(TileType::Floor, mx, my);
+                    markers.push(Marker { kind: MarkerKind::Spawn, x: mx, y: my });
+                },
+                PrefabTile::Loot => {
+                    map.set(TileType::Floor, mx, my);
+                    markers.push(Marker { kind: MarkerKind::Loot, x: mx, y: my });
+                },
+            }
+        }
+    }
+    Placement { name: prefab.name.clone(), x, y, markers }
+}
+
+impl Default for PrefabStamper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    const ONE_FLOOR: &str = "\
+#####
+#.###
+#####
+#####
+#####";
+
+    fn stamper(mode: PlacementMode, prefab: &str) -> PrefabStamper {
+        let mut stamper = PrefabStamper::new();
+        stamper.set_prefabs(vec![Prefab::from_text("test", prefab).unwrap()]);
+        stamper.mode = mode;
+        stamper
+    }
+
+    fn spots(mode: PlacementMode, prefab: &str) -> Vec<(usize, usize)> {
+        let map = Map::from_text(ONE_FLOOR).unwrap();
+        let stamper = stamper(mode, prefab);
+        stamper.spots(&map, &stamper.prefabs[0], &vec![false; map.tiles.len()])
+    }
+
+    #[test]
+    fn text_is_read_tile_by_tile() {
+        let prefab = Prefab::from_text("test", "#.\nS$\n? ").unwrap();
+        assert_eq!((prefab.width, prefab.height), (2, 3));
+        assert!(prefab.tiles == [
+            PrefabTile::Wall, PrefabTile::Floor,
+            PrefabTile::Spawn, PrefabTile::Loot,
+            PrefabTile::Any, PrefabTile::Any,
+        ]);
+        assert!(Prefab::from_text("test", "#x").err().is_some_and(|e| e.contains("unknown tile 'x' at 1, 0")));
+        assert!(Prefab::from_text("test", "\n\n").is_err());
+    }
+
+    #[test]
+    fn rows_of_spaces_are_kept_but_blank_lines_around_are_not() {
+        let prefab = Prefab::from_text("test", "\n##\n  \n#\n\n").unwrap();
+        assert_eq!((prefab.width, prefab.height), (2, 3));
+        assert!(prefab.tiles[2..4].iter().all(|t| *t == PrefabTile::Any));
+        // the short last row is padded
+        assert!(prefab.get(1, 2) == PrefabTile::Any);
+    }
+
+    #[test]
+    fn vaults_go_into_rock_beside_a_floor() {
+        // walls off the outer ring that touch the one floor
+        assert_eq!(spots(PlacementMode::OverwriteWalls, "."), vec![(2, 1), (1, 2)]);
+        // a prefab of walls alone has no floor to be reached from
+        assert!(spots(PlacementMode::OverwriteWalls, "#").is_empty());
+    }
+
+    #[test]
+    fn set_pieces_go_onto_floor() {
+        assert_eq!(spots(PlacementMode::OverlapFloor, "."), vec![(1, 1)]);
+        assert!(spots(PlacementMode::OverlapFloor, ".#").is_empty());
+        // tiles left alone can sit over anything
+        assert_eq!(spots(PlacementMode::OverlapFloor, ".?"), vec![(1, 1)]);
+        assert_eq!(spots(PlacementMode::OverlapFloor, ". "), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn spawns_and_loot_become_floor_with_markers() {
+        let mut map = Map::from_text(ONE_FLOOR).unwrap();
+        let mut stamper = stamper(PlacementMode::OverwriteWalls, "S$");
+        stamper.max_stamps = 1;
+        let placements = stamper.stamp(&mut map, &mut StdRng::seed_from_u64(1));
+
+        assert_eq!(placements.len(), 1);
+        let Placement { x, y, markers, .. } = &placements[0];
+        let kinds: Vec<(MarkerKind, usize, usize)> = markers.iter().map(|m| (m.kind, m.x, m.y)).collect();
+        assert_eq!(kinds, vec![(MarkerKind::Spawn, *x, *y), (MarkerKind::Loot, x + 1, *y)]);
+        assert!(map.get(*x, *y) == Some(&TileType::Floor) && map.get(x + 1, *y) == Some(&TileType::Floor));
+    }
+}
@@ -0,0 +1,275 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                             Map Quality                               ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::Serialize;
+
+use super::analysis::{self, Regions};
+use super::map::{Map, TileType};
+use super::metrics::{Metric, MetricValue};
+use super::rooms::RoomGraph;
+
+// floor tiles path lengths are measured from, picked at random
+const PATH_SAMPLES: usize = 16;
+
+/// The smallest room area counted in each bucket of the room size
+/// histogram. The last bucket has no upper bound.
+pub const ROOM_SIZE_BUCKETS: [usize; 5] = [0, 16, 36, 64, 100];
+
+
+/// Figures for how a map plays, measured the same way whichever builder
+/// made it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Quality {
+    /// Share of the tiles that are floor, from 0 to 100.
+    pub floor_percent: f64,
+    pub regions: usize,
+    /// Share of the floor in the largest region, from 0 to 100.
+    pub largest_region_percent: f64,
+    /// The shorter of the runs of floor across each floor tile, side to
+    /// side or up and down, averaged over the floor. A one tile corridor
+    /// counts 1 along its straight stretches, and a room its shorter side.
+    pub mean_corridor_width: f64,
+    pub dead_ends: usize,
+    /// Walls clear of the map edge with floor all the way round, counting
+    /// walls that touch at a corner as one. Each is a loop the floor can be
+    /// walked around, though open squares of floor are not counted.
+    pub enclosed_pillars: usize,
+    /// `None` for builders without a room graph.
+    pub rooms: Option<usize>,
+    /// Rooms with an area in each bucket of `ROOM_SIZE_BUCKETS`.
+    pub room_sizes: Option<Vec<usize>>,
+    /// Steps between random floor tiles that can reach each other.
+    pub mean_path_length: f64,
+    /// How near the floor is to a single square: 1 for a square, and lower
+    /// the more edge the floor has for its area.
+    pub compactness: f64,
+}
+
+impl Quality {
+    pub fn new(map: &Map, graph: Option<&RoomGraph>) -> Quality {
+        let floors = map.tiles.iter().filter(|tile| **tile == TileType::Floor).count();
+        let regions = Regions::new(map);
+        let largest = regions.largest().map_or(0, |label| regions.sizes[label]);
+        let perimeter = floor_perimeter(map);
+
+        Quality {
+            floor_percent: percent(floors, map.tiles.len()),
+            regions: regions.count(),
+            largest_region_percent: percent(largest, floors),
+            mean_corridor_width: mean_corridor_width(map, floors),
+            dead_ends: analysis::dead_ends(map).len(),
+            enclosed_pillars: enclosed_pillars(map),
+            rooms: graph.map(|graph| graph.rooms.len()),
+            room_sizes: graph.map(room_sizes),
+            mean_path_length: mean_path_length(map),
+            compactness: if perimeter == 0 { 0.0 } else { 16.0 * floors as f64 / (perimeter * perimeter) as f64 },
+        }
+    }
+
+    /// The figures as metrics, leaving out the room ones for maps without
+    /// rooms.
+    pub fn metrics(&self) -> Vec<Metric> {
+        let share = |name, value| Metric { name, value: MetricValue::Percent(value), unit: None };
+        let mut metrics = vec![
+            share("Floor Ratio", self.floor_percent),
+            Metric::integer("Regions", self.regions),
+            share("Largest Region", self.largest_region_percent),
+            Metric::float("Mean Corridor Width", self.mean_corridor_width).with_unit("tiles"),
+            Metric::integer("Dead Ends", self.dead_ends),
+            Metric::integer("Enclosed Pillars", self.enclosed_pillars),
+        ];
+        if let Some(rooms) = self.rooms {
+            metrics.push(Metric::integer("Rooms", rooms));
+        }
+        if let Some(sizes) = &self.room_sizes {
+            metrics.push(Metric::text("Room Sizes", room_size_text(sizes)));
+        }
+        metrics.push(Metric::float("Mean Path Length", self.mean_path_length).with_unit("steps"));
+        metrics.push(Metric::float("Compactness", self.compactness));
+        metrics
+    }
+}
+
+fn percent(part: usize, whole: usize) -> f64 {
+    if whole == 0 { 0.0 } else { 100.0 * part as f64 / whole as f64 }
+}
+
+// the length of the run of floor across every tile, along rows if `across`
+// and down columns otherwise
+fn runs(map: &Map, across: bool) -> Vec<usize> {
+    let mut lengths = vec![0; map.tiles.len()];
+    let (lines, length) = if across { (map.height, map.width) } else { (map.width, map.height) };
+    let idx = |line: usize, i: usize| if across { line * map.width + i } else { i * map.width + line };
+
+    for line in 0..lines {
+        let mut start = 0;
+        for i in 0..=length {
+            if i < length && map.tiles[idx(line, i)] == TileType::Floor {
+                continue;
+            }
+            for j in start..i {
+                lengths[idx(line, j)] = i - start;
+            }
+            start = i + 1;
+        }
+    }
+    lengths
+}
+
+fn mean_corridor_width(map: &Map, floors: usize) -> f64 {
+    if floors == 0 {
+        return 0.0;
+    }
+    let widths: usize = runs(map, true).into_iter().zip(runs(map, false)).map(|(across, down)| across.min(down)).sum();
+    widths as f64 / floors as f64
+}
+
+// walls joined in any of the eight directions that don't reach the edge of
+// the map, and so have floor all the way round them
+fn enclosed_pillars(map: &Map) -> usize {
+    let mut seen = vec![false; map.tiles.len()];
+    let mut pillars = 0;
+
+    for start in 0..map.tiles.len() {
+        if seen[start] || map.tiles[start] != TileType::Wall {
+            continue;
+        }
+        seen[start] = true;
+        let mut stack = vec![start];
+        let mut enclosed = true;
+        while let Some(idx) = stack.pop() {
+            let (x, y) = (idx % map.width, idx / map.width);
+            if x == 0 || y == 0 || x + 1 == map.width || y + 1 == map.height {
+                enclosed = false;
+            }
+            for ny in y.saturating_sub(1)..(y + 2).min(map.height) {
+                for nx in x.saturating_sub(1)..(x + 2).min(map.width) {
+                    let next = ny * map.width + nx;
+                    if !seen[next] && map.tiles[next] == TileType::Wall {
+                        seen[next] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+        if enclosed {
+            pillars += 1;
+        }
+    }
+    pillars
+}
+
+fn room_sizes(graph: &RoomGraph) -> Vec<usize> {
+    let mut counts = vec![0; ROOM_SIZE_BUCKETS.len()];
+    for room in &graph.rooms {
+        let area = ((room.x2 - room.x1).max(0) * (room.y2 - room.y1).max(0)) as usize;
+        let bucket = ROOM_SIZE_BUCKETS.iter().rposition(|&least| area >= least).unwrap_or(0);
+        counts[bucket] += 1;
+    }
+    counts
+}
+
+// each bucket's range of areas and how many rooms fall in it
+fn room_size_text(counts: &[usize]) -> String {
+    let mut buckets = Vec::new();
+    for (i, count) in counts.iter().enumerate() {
+        let range = match ROOM_SIZE_BUCKETS.get(i + 1) {
+            Some(next) => format!("{}-{}", ROOM_SIZE_BUCKETS[i], next - 1),
+            None => format!("{}+", ROOM_SIZE_BUCKETS[i]),
+        };
+        buckets.push(format!("{range}: {count}"));
+    }
+    buckets.join(", ")
+}
+
+// the average distance from a few floor tiles, picked the same way every
+// time so a map always scores the same, to everywhere they can reach
+fn mean_path_length(map: &Map) -> f64 {
+    let floors: Vec<usize> = (0..map.tiles.len()).filter(|&i| map.tiles[i] == TileType::Floor).collect();
+    let mut rng = StdRng::seed_from_u64(0);
+    let (mut total, mut paths) = (0, 0);
+
+    for &from in floors.choose_multiple(&mut rng, PATH_SAMPLES) {
+        let distances = analysis::dijkstra_map(map, (from % map.width, from / map.width));
+        for distance in distances.into_iter().flatten().filter(|&d| d > 0) {
+            total += distance;
+            paths += 1;
+        }
+    }
+    if paths == 0 { 0.0 } else { total as f64 / paths as f64 }
+}
+
+// edges between floor and wall, with the edge of the map counting as wall
+fn floor_perimeter(map: &Map) -> usize {
+    let mut perimeter = 0;
+    for y in 0..map.height {
+        for x in 0..map.width {
+            if map.tiles[y * map.width + x] != TileType::Floor {
+                continue;
+            }
+            let open = |nx: Option<usize>, ny: Option<usize>| match (nx, ny) {
+                (Some(nx), Some(ny)) if nx < map.width && ny < map.height => map.tiles[ny * map.width + nx] == TileType::Floor,
+                _ => false,
+            };
+            let neighbours = [
+                open(x.checked_sub(1), Some(y)),
+                open(Some(x + 1), Some(y)),
+                open(Some(x), y.checked_sub(1)),
+                open(Some(x), Some(y + 1)),
+            ];
+            perimeter += neighbours.iter().filter(|open| !**open).count();
+        }
+    }
+    perimeter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(text: &str) -> Map {
+        Map::from_text(text).unwrap()
+    }
+
+    #[test]
+    fn each_pillar_in_the_floor_is_counted() {
+        assert_eq!(enclosed_pillars(&map("#####\n#...#\n#...#\n#...#\n#####")), 0);
+        assert_eq!(enclosed_pillars(&map("#####\n#...#\n#.#.#\n#...#\n#####")), 1);
+        assert_eq!(enclosed_pillars(&map("#######\n#.....#\n#.#.#.#\n#.....#\n#######")), 2);
+    }
+
+    #[test]
+    fn walls_touching_at_a_corner_are_one_pillar() {
+        assert_eq!(enclosed_pillars(&map("######\n#....#\n#.#..#\n#..#.#\n#....#\n######")), 1);
+    }
+
+    #[test]
+    fn walls_joined_to_the_edge_are_not_pillars() {
+        assert_eq!(enclosed_pillars(&map("#####\n#...#\n#.###\n#...#\n#####")), 0);
+    }
+
+    #[test]
+    fn an_open_square_is_compact_and_wide() {
+        let quality = Quality::new(&map("..........\n".repeat(10).as_str()), None);
+        assert_eq!(quality.floor_percent, 100.0);
+        assert_eq!(quality.compactness, 1.0);
+        assert_eq!(quality.mean_corridor_width, 10.0);
+        assert_eq!(quality.rooms, None);
+    }
+
+    #[test]
+    fn a_corridor_is_one_tile_wide_with_two_dead_ends() {
+        let quality = Quality::new(&map("#######\n#.....#\n#######"), None);
+        assert_eq!(quality.mean_corridor_width, 1.0);
+        assert_eq!(quality.dead_ends, 2);
+        assert_eq!((quality.regions, quality.largest_region_percent), (1, 100.0));
+        // every pair of the five tiles, averaged over both directions
+        assert_eq!(quality.mean_path_length, 2.0);
+    }
+}
@@ -0,0 +1,55 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                               Random                                  ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::cell::RefCell;
+
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Seeds the generator every builder on this thread draws from, so the same
+/// seed gives the same maps. Until then it is seeded from the OS.
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+/// Swaps in a generator for this thread, returning the one it replaces.
+/// Handing the generator along with a builder keeps its stream going when
+/// the builder moves to another thread.
+pub fn replace(generator: StdRng) -> StdRng {
+    RNG.with(|rng| std::mem::replace(&mut *rng.borrow_mut(), generator))
+}
+
+/// A handle to this thread's builder generator. Builders take every random
+/// choice from it, so seeding it replays their maps exactly.
+pub fn rng() -> BuilderRng {
+    BuilderRng
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BuilderRng;
+
+impl RngCore for BuilderRng {
+    fn next_u32(&mut self) -> u32 {
+        RNG.with(|rng| rng.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        RNG.with(|rng| rng.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        RNG.with(|rng| rng.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        RNG.with(|rng| rng.borrow_mut().try_fill_bytes(dest))
+    }
+}
@@ -0,0 +1,317 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                              Recipes                                  ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::MapBuilder;
+use super::Map;
+use super::autotile;
+use super::constraints::Constraints;
+use super::export;
+use super::pipeline::Modifier;
+use super::prefabs::{Placement, PrefabStamper};
+use super::random;
+use super::registry;
+use super::run_limit;
+
+// the smallest map every builder can work with. The drunkard starts its
+// walks ten tiles in from the edge and DLA always adds the same number of
+// tiles, which runs out of room on anything smaller.
+const MIN_SIZE: usize = 29;
+
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Text,
+    /// Text with walls drawn in box-drawing lines, for looking at rather
+    /// than reading back.
+    Autotiled,
+}
+
+/// A file a recipe writes its map to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportTarget {
+    pub path: String,
+    /// Left out, it is text for `.txt` files and JSON for anything else.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ExportFormat>,
+}
+
+impl ExportTarget {
+    pub fn format(&self) -> ExportFormat {
+        match self.format {
+            Some(format) => format,
+            None if self.path.ends_with(".txt") => ExportFormat::Text,
+            None => ExportFormat::Json,
+        }
+    }
+}
+
+/// Everything needed to make a map again, as written in a TOML or RON file.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Recipe {
+    /// The id of a builder in the registry.
+    pub algorithm: String,
+    #[serde(default = "default_width")]
+    pub width: usize,
+    #[serde(default = "default_height")]
+    pub height: usize,
+    /// Left out, every run picks a new seed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Iterations after the build. Left out, the builder iterates until it
+    /// is finished.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<usize>,
+    /// Values of the builder's settings, by the names and in the form the
+    /// TUI shows them. Any value the setting allows works, not just the ones
+    /// cycling through it reaches.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_processing: Vec<Modifier>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub export: Vec<ExportTarget>,
+    /// What a map has to meet when made with a `Search`.
+    #[serde(default, skip_serializing_if = "Constraints::is_empty")]
+    pub constraints: Constraints,
+}
+
+/// A map made from a recipe, with the builder that made it.
+pub struct Generated {
+    pub builder: Box<dyn MapBuilder>,
+    pub seed: u64,
+    pub iterations: usize,
+    /// Prefabs stamped by the post-processing.
+    pub placements: Vec<Placement>,
+    pub duration: Duration,
+}
+
+impl Recipe {
+    pub fn new(algorithm: &str) -> Recipe {
+        Recipe {
+            algorithm: algorithm.to_string(),
+            width: default_width(),
+            height: default_height(),
+            seed: None,
+            iterations: None,
+            params: BTreeMap::new(),
+            post_processing: Vec::new(),
+            export: Vec::new(),
+            constraints: Constraints::new(),
+        }
+    }
+
+    /// Describes `builder` as it stands, so it can be built the same way
+    /// again from `seed`.
+    pub fn from_builder(algorithm: &str, builder: &dyn MapBuilder, seed: u64, iterations: usize) -> Recipe {
+        let map = builder.get_map();
+        Recipe {
+            width: map.width,
+            height: map.height,
+            seed: Some(seed),
+            iterations: Some(iterations),
+            params: builder.settings().into_iter().map(|(name, value)| (name.to_string(), value)).collect(),
+            ..Recipe::new(algorithm)
+        }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Recipe, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    pub fn from_ron(text: &str) -> Result<Recipe, String> {
+        ron::from_str(text).map_err(|e| e.to_string())
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| e.to_string())
+    }
+
+    /// Reads a `.toml` or `.ron` recipe, picking the format by extension.
+    pub fn load(path: impl AsRef<Path>) -> Result<Recipe, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+        let recipe = match is_ron(path) {
+            true => Recipe::from_ron(&text),
+            false => Recipe::from_toml(&text),
+        };
+        recipe.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Writes the recipe as RON for `.ron` paths and TOML for anything else.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let text = if is_ron(path) { self.to_ron()? } else { self.to_toml()? };
+        fs::write(path, text).map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+    }
+
+    /// A fresh builder sized and set up as the recipe says, not yet built.
+    pub fn builder(&self) -> Result<Box<dyn MapBuilder>, String> {
+        let mut builder = registry::create(&self.algorithm)
+            .ok_or_else(|| format!("unknown algorithm '{}', expected one of {}", self.algorithm, registry::ids().join(", ")))?;
+
+        if self.width < MIN_SIZE || self.height < MIN_SIZE {
+            return Err(format!("maps must be at least {MIN_SIZE}x{MIN_SIZE}, not {}x{}", self.width, self.height));
+        }
+        builder.resize(self.width, self.height);
+
+        for (name, value) in &self.params {
+            set_param(builder.as_mut(), name, value)?;
+        }
+        Ok(builder)
+    }
+
+    /// Builds, iterates and post-processes a map on this thread.
+    pub fn generate(&self, stamper: &mut PrefabStamper) -> Result<Generated, String> {
+        let mut builder = self.builder()?;
+        let seed = self.seed.unwrap_or_else(rand::random);
+        random::seed(seed);
+
+        let start = Instant::now();
+        builder.build();
+        let iterations = match self.iterations {
+            Some(count) => {
+                (0..count).for_each(|_| builder.iterate());
+                count
+            },
+            None => {
+                let limit = run_limit(builder.get_map());
+                let mut count = 0;
+                while count < limit && !builder.progress().is_some_and(|p| p >= 1.0) {
+                    builder.iterate();
+                    count += 1;
+                }
+                count
+            },
+        };
+
+        let mut placements = Vec::new();
+        let mut rng = random::rng();
+        for modifier in &self.post_processing {
+            placements.extend(modifier.apply(builder.get_map_mut(), stamper, &mut rng));
+        }
+        let duration = start.elapsed();
+
+        Ok(Generated { builder, seed, iterations, placements, duration })
+    }
+
+    /// Writes the map of `builder` to every export target.
+    pub fn write_exports(&self, builder: &dyn MapBuilder) -> Result<(), String> {
+        for target in &self.export {
+            let written = match target.format() {
+                ExportFormat::Json => export::write_json(&target.path, builder.get_map(), builder.room_graph(), builder.regions(), &builder.report()),
+                ExportFormat::Text => fs::write(&target.path, builder.get_map().to_text()),
+                ExportFormat::Autotiled => fs::write(&target.path, autotile::to_text(builder.get_map())),
+            };
+            written.map_err(|e| format!("couldn't export {}: {}", target.path, e))?;
+        }
+        Ok(())
+    }
+}
+
+// sets the named setting, matching the name the way the TUI shows it
+fn set_param(builder: &mut dyn MapBuilder, name: &str, value: &str) -> Result<(), String> {
+    let settings = builder.settings();
+    let index = settings.iter().position(|(n, _)| n.eq_ignore_ascii_case(name)).ok_or_else(|| {
+        let names: Vec<&str> = settings.iter().map(|(n, _)| *n).collect();
+        format!("unknown setting '{}', expected one of {}", name, names.join(", "))
+    })?;
+    builder.set_setting(index, value)
+}
+
+fn is_ron(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "ron")
+}
+
+fn default_width() -> usize {
+    Map::new().width
+}
+
+fn default_height() -> usize {
+    Map::new().height
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a builder with every setting moved on from its default, described as
+    // a recipe
+    fn changed_recipe(id: &str) -> Recipe {
+        let mut builder = registry::create(id).expect("id is in the registry");
+        for index in 0..builder.settings().len() {
+            builder.cycle_setting(index);
+        }
+        Recipe::from_builder(id, builder.as_ref(), 9, 3)
+    }
+
+    #[test]
+    fn every_builder_reads_back_its_settings_from_toml_and_ron() {
+        for id in registry::ids() {
+            let recipe = changed_recipe(id);
+            let written = [(recipe.to_toml().unwrap(), Recipe::from_toml as fn(&str) -> Result<Recipe, String>), (recipe.to_ron().unwrap(), Recipe::from_ron)];
+            for (text, read) in written {
+                let read = read(&text).unwrap();
+                assert_eq!((read.seed, read.iterations), (Some(9), Some(3)));
+                assert_eq!(read.params, recipe.params, "{id} changed its settings on the way through");
+
+                let settings: BTreeMap<String, String> = read.builder().unwrap().settings().into_iter().map(|(n, v)| (n.to_string(), v)).collect();
+                assert_eq!(settings, recipe.params, "{id} didn't take its settings back");
+            }
+        }
+    }
+
+    #[test]
+    fn the_same_seed_makes_the_same_map() {
+        let mut recipe = Recipe::from_toml(include_str!("../../recipes/dungeon.toml")).unwrap();
+        recipe.export.clear();
+        let mut stamper = PrefabStamper::new();
+        let first = recipe.generate(&mut stamper).unwrap();
+        let second = recipe.generate(&mut stamper).unwrap();
+        assert_eq!(first.builder.get_map().to_text(), second.builder.get_map().to_text());
+    }
+
+    #[test]
+    fn the_example_recipes_read() {
+        assert_eq!(Recipe::from_ron(include_str!("../../recipes/caves.ron")).unwrap().algorithm, "voronoi");
+        assert_eq!(Recipe::from_toml(include_str!("../../recipes/dungeon.toml")).unwrap().export.len(), 2);
+    }
+
+    #[test]
+    fn bad_params_say_what_was_expected() {
+        let mut recipe = Recipe::new("maze");
+        recipe.params.insert("Colour".to_string(), "Blue".to_string());
+        let err = recipe.builder().err().unwrap();
+        assert!(err.contains("unknown setting 'Colour'") && err.contains("Algorithm"), "{err}");
+
+        let mut recipe = Recipe::new("maze");
+        recipe.params.insert("braid".to_string(), "120%".to_string());
+        let err = recipe.builder().err().unwrap();
+        assert!(err.contains("percentage from 0 to 100"), "{err}");
+    }
+
+    #[test]
+    fn unknown_fields_and_tiny_maps_are_refused() {
+        assert!(Recipe::from_toml("algorithm = \"bsp\"\nsize = 40").is_err());
+
+        let mut recipe = Recipe::new("bsp");
+        recipe.width = 10;
+        assert!(recipe.builder().err().unwrap().contains("at least"));
+    }
+}
@@ -0,0 +1,117 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                             Registry                                  ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use super::MapBuilder;
+use super::{bsp_dungeon::BSPDungeonBuilder, cellular_automata::CellularAutomataBuilder, df_aggregation::DiffusionLimitedAggregationBuilder, drunkard::DrunkardBuilder, maze::MazeBuilder, noise_cave::NoiseCaveBuilder, pipeline::PipelineBuilder, simple_map::SimpleMapBuilder, voronoi::VoronoiBuilder, wfc::WaveFunctionCollapseBuilder};
+
+/// Everything needed to list and create a builder.
+#[derive(Copy, Clone)]
+pub struct BuilderInfo {
+    /// Used to name the builder in config files.
+    pub id: &'static str,
+    /// Shown on the builder's tab.
+    pub name: &'static str,
+    /// A short label for charts.
+    pub label: &'static str,
+    /// The key that selects the builder in the TUI, if it has one. Any
+    /// builder can be reached by stepping through the tabs.
+    pub key: Option<char>,
+    pub create: fn() -> Box<dyn MapBuilder>,
+}
+
+/// Every builder in the crate, in tab order.
+pub const BUILT_IN: &[BuilderInfo] = &[
+    BuilderInfo { id: "bsp", name: "BSP", label: "HUE", key: Some('1'), create: || Box::new(BSPDungeonBuilder::new()) },
+    BuilderInfo { id: "cellular", name: "Cellular", label: "CELL", key: Some('2'), create: || Box::new(CellularAutomataBuilder::new()) },
+    BuilderInfo { id: "drunkard", name: "Drunkard", label: "DRU", key: Some('3'), create: || Box::new(DrunkardBuilder::new()) },
+    BuilderInfo { id: "dla", name: "DFA", label: "DFA", key: Some('4'), create: || Box::new(DiffusionLimitedAggregationBuilder::new()) },
+    BuilderInfo { id: "simple", name: "Simple", label: "SIM", key: Some('5'), create: || Box::new(SimpleMapBuilder::new()) },
+    BuilderInfo { id: "noise", name: "Noise", label: "NOI", key: Some('6'), create: || Box::new(NoiseCaveBuilder::new()) },
+    BuilderInfo { id: "maze", name: "Maze", label: "MAZ", key: Some('7'), create: || Box::new(MazeBuilder::new()) },
+    BuilderInfo { id: "wfc", name: "WFC", label: "WFC", key: Some('8'), create: || Box::new(WaveFunctionCollapseBuilder::new()) },
+    BuilderInfo { id: "voronoi", name: "Voronoi", label: "VOR", key: Some('9'), create: || Box::new(VoronoiBuilder::new()) },
+    BuilderInfo { id: "pipeline", name: "Pipeline", label: "PIPE", key: Some('0'), create: || Box::new(PipelineBuilder::new()) },
+];
+
+/// Makes a fresh builder from the id of a built in one.
+pub fn create(id: &str) -> Option<Box<dyn MapBuilder>> {
+    BUILT_IN.iter().find(|info| info.id == id).map(|info| (info.create)())
+}
+
+/// The ids of every built in builder.
+pub fn ids() -> Vec<&'static str> {
+    BUILT_IN.iter().map(|info| info.id).collect()
+}
+
+
+pub struct RegisteredBuilder {
+    pub info: BuilderInfo,
+    pub builder: Box<dyn MapBuilder>,
+}
+
+/// A list of live builders, each with a name, chart label and key.
+pub struct BuilderRegistry {
+    entries: Vec<RegisteredBuilder>,
+}
+
+impl BuilderRegistry {
+    pub fn new() -> BuilderRegistry {
+        BuilderRegistry { entries: Vec::new() }
+    }
+
+    /// A registry holding one of each built in builder.
+    pub fn built_in() -> BuilderRegistry {
+        let mut registry = BuilderRegistry::new();
+        for &info in BUILT_IN {
+            registry.register(info);
+        }
+        registry
+    }
+
+    /// Adds a new builder made with `info.create`. Keys should be unique,
+    /// since only the first builder with a key can be selected by it.
+    pub fn register(&mut self, info: BuilderInfo) {
+        self.entries.push(RegisteredBuilder { info, builder: (info.create)() });
+    }
+
+    pub fn entries(&self) -> &[RegisteredBuilder] {
+        &self.entries
+    }
+
+    pub fn entries_mut(&mut self) -> &mut [RegisteredBuilder] {
+        &mut self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&RegisteredBuilder> {
+        self.entries.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut RegisteredBuilder> {
+        self.entries.get_mut(index)
+    }
+
+    pub fn index_of(&self, id: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.info.id == id)
+    }
+
+    pub fn index_of_key(&self, key: char) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.info.key == Some(key))
+    }
+}
+
+impl Default for BuilderRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
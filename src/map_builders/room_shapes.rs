@@ -0,0 +1,327 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                           Room Shapes                                 ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use rand::Rng;
+
+use super::Map;
+use super::map::TileType;
+use super::rooms::Rect;
+
+/// The outline carved inside a room's rectangle.
+#[derive(PartialEq, Copy, Clone)]
+pub enum RoomShape {
+    Rectangle,
+    Circle,
+    Ellipse,
+    /// A rectangle with one corner quadrant left solid.
+    LShape,
+    /// Two bands crossing through the middle of the rectangle.
+    Cross,
+    /// A rectangle with a grid of single-tile pillars.
+    PillaredHall,
+    /// A few cellular automata passes run inside the rectangle.
+    CaveBlob,
+}
+
+impl RoomShape {
+    pub const ALL: [RoomShape; 7] = [
+        RoomShape::Rectangle,
+        RoomShape::Circle,
+        RoomShape::Ellipse,
+        RoomShape::LShape,
+        RoomShape::Cross,
+        RoomShape::PillaredHall,
+        RoomShape::CaveBlob,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RoomShape::Rectangle => "Rectangle",
+            RoomShape::Circle => "Circle",
+            RoomShape::Ellipse => "Ellipse",
+            RoomShape::LShape => "L-Shape",
+            RoomShape::Cross => "Cross",
+            RoomShape::PillaredHall => "Pillared Hall",
+            RoomShape::CaveBlob => "Cave Blob",
+        }
+    }
+}
+
+/// Weighted shapes a room-based builder rolls from for each room it places.
+#[derive(Clone)]
+pub struct RoomShapeTable {
+    pub name: String,
+    weights: Vec<(RoomShape, u32)>,
+}
+
+impl RoomShapeTable {
+    /// An empty table. Rolling from it always gives rectangles.
+    pub fn new(name: &str) -> RoomShapeTable {
+        RoomShapeTable {
+            name: name.to_string(),
+            weights: Vec::new(),
+        }
+    }
+
+    /// Adds `shape` with the given weight, or replaces its weight if it is
+    /// already in the table.
+    pub fn with(mut self, shape: RoomShape, weight: u32) -> RoomShapeTable {
+        self.weights.retain(|(s, _)| *s != shape);
+        if weight > 0 {
+            self.weights.push((shape, weight));
+        }
+        self
+    }
+
+    pub fn rectangles() -> RoomShapeTable {
+        RoomShapeTable::new("Rectangles").with(RoomShape::Rectangle, 1)
+    }
+
+    pub fn mixed() -> RoomShapeTable {
+        RoomShape::ALL
+            .iter()
+            .fold(RoomShapeTable::new("Mixed"), |table, &shape| table.with(shape, 1))
+    }
+
+    pub fn organic() -> RoomShapeTable {
+        RoomShapeTable::new("Organic")
+            .with(RoomShape::Circle, 2)
+            .with(RoomShape::Ellipse, 2)
+            .with(RoomShape::CaveBlob, 3)
+    }
+
+    pub fn architectural() -> RoomShapeTable {
+        RoomShapeTable::new("Architectural")
+            .with(RoomShape::Rectangle, 3)
+            .with(RoomShape::LShape, 2)
+            .with(RoomShape::Cross, 2)
+            .with(RoomShape::PillaredHall, 1)
+    }
+
+    pub fn presets() -> Vec<RoomShapeTable> {
+        vec![
+            RoomShapeTable::rectangles(),
+            RoomShapeTable::mixed(),
+            RoomShapeTable::organic(),
+            RoomShapeTable::architectural(),
+        ]
+    }
+
+    /// The preset after this one, by name. Custom tables go back to the
+    /// first preset.
+    pub fn next_preset(&self) -> RoomShapeTable {
+        let presets = RoomShapeTable::presets();
+        let idx = presets.iter().position(|p| p.name == self.name).map_or(0, |i| i + 1);
+        presets[idx % presets.len()].clone()
+    }
+
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> RoomShape {
+        let total: u32 = self.weights.iter().map(|(_, w)| w).sum();
+        if total == 0 {
+            return RoomShape::Rectangle;
+        }
+
+        let mut roll = rng.gen_range(0..total);
+        for &(shape, weight) in &self.weights {
+            if roll < weight {
+                return shape;
+            }
+            roll -= weight;
+        }
+        RoomShape::Rectangle
+    }
+}
+
+impl Default for RoomShapeTable {
+    fn default() -> Self {
+        Self::rectangles()
+    }
+}
+
+/// Carves `shape` into the floor area of `room`, which spans
+/// `x1 + 1 ..= x2` and `y1 + 1 ..= y2`. Every shape keeps its floor in one
+/// connected piece.
+pub fn carve_room<R: Rng>(map: &mut Map, room: &Rect, shape: RoomShape, rng: &mut R) {
+    let (x0, y0) = (room.x1 + 1, room.y1 + 1);
+    let w = room.x2 - room.x1;
+    let h = room.y2 - room.y1;
+    let mask = shape_mask(shape, w, h, rng);
+
+    for dy in 0..h {
+        for dx in 0..w {
+            if mask[(dy * w + dx) as usize] {
+                let idx = map.xy_idx(x0 + dx, y0 + dy);
+                map.tiles[idx] = TileType::Floor;
+            }
+        }
+    }
+}
+
+// a w * h grid, true where the room has floor
+fn shape_mask<R: Rng>(shape: RoomShape, w: i32, h: i32, rng: &mut R) -> Vec<bool> {
+    let mut mask = vec![true; (w * h).max(0) as usize];
+    if w < 3 || h < 3 {
+        return mask;
+    }
+
+    // centers and radii measured in half tiles so even sizes stay symmetric
+    let (cx2, cy2) = (w - 1, h - 1);
+    let inside = |dx: i32, dy: i32, rx2: i32, ry2: i32| {
+        let nx = (2 * dx - cx2) as f32 / rx2.max(1) as f32;
+        let ny = (2 * dy - cy2) as f32 / ry2.max(1) as f32;
+        nx * nx + ny * ny <= 1.0
+    };
+
+    match shape {
+        RoomShape::Rectangle => {},
+        RoomShape::Circle => {
+            let r2 = w.min(h);
+            fill_mask(&mut mask, w, h, |dx, dy| inside(dx, dy, r2, r2));
+        },
+        RoomShape::Ellipse => {
+            fill_mask(&mut mask, w, h, |dx, dy| inside(dx, dy, w, h));
+        },
+        RoomShape::LShape => {
+            // leave one corner solid, never reaching the middle tile
+            let (cut_w, cut_h) = ((w - 1) / 2, (h - 1) / 2);
+            let right = rng.gen_bool(0.5);
+            let bottom = rng.gen_bool(0.5);
+            fill_mask(&mut mask, w, h, |dx, dy| {
+                let in_x = if right { dx >= w - cut_w } else { dx < cut_w };
+                let in_y = if bottom { dy >= h - cut_h } else { dy < cut_h };
+                !(in_x && in_y)
+            });
+        },
+        RoomShape::Cross => {
+            let (band_w, band_h) = ((w / 3).max(1), (h / 3).max(1));
+            let (left, top) = ((w - band_w) / 2, (h - band_h) / 2);
+            fill_mask(&mut mask, w, h, |dx, dy| {
+                (dx >= left && dx < left + band_w) || (dy >= top && dy < top + band_h)
+            });
+        },
+        RoomShape::PillaredHall => {
+            if w >= 5 && h >= 5 {
+                // pillars sit on alternate tiles, away from the walls and
+                // the middle of the room
+                fill_mask(&mut mask, w, h, |dx, dy| {
+                    let ring = dx == 0 || dy == 0 || dx == w - 1 || dy == h - 1;
+                    let middle = dx == w / 2 || dy == h / 2;
+                    ring || middle || dx % 2 == 0 || dy % 2 == 0
+                });
+            }
+        },
+        RoomShape::CaveBlob => {
+            fill_mask(&mut mask, w, h, |_, _| rng.gen_bool(0.6));
+            for _ in 0..4 {
+                mask = smooth_mask(&mask, w, h);
+            }
+            mask[((h / 2) * w + w / 2) as usize] = true;
+            keep_connected(&mut mask, w, h, (w / 2, h / 2));
+        },
+    }
+
+    mask
+}
+
+fn fill_mask<F: FnMut(i32, i32) -> bool>(mask: &mut [bool], w: i32, h: i32, mut floor: F) {
+    for dy in 0..h {
+        for dx in 0..w {
+            mask[(dy * w + dx) as usize] = floor(dx, dy);
+        }
+    }
+}
+
+// one cellular automata pass, treating everything outside the room as wall
+fn smooth_mask(mask: &[bool], w: i32, h: i32) -> Vec<bool> {
+    let mut next = mask.to_vec();
+    for dy in 0..h {
+        for dx in 0..w {
+            let mut walls = 0;
+            for ny in dy - 1..=dy + 1 {
+                for nx in dx - 1..=dx + 1 {
+                    if (nx, ny) == (dx, dy) { continue; }
+                    if nx < 0 || ny < 0 || nx >= w || ny >= h || !mask[(ny * w + nx) as usize] {
+                        walls += 1;
+                    }
+                }
+            }
+            next[(dy * w + dx) as usize] = walls < 5;
+        }
+    }
+    next
+}
+
+// clears every floor tile that can't be reached from `start`
+fn keep_connected(mask: &mut [bool], w: i32, h: i32, start: (i32, i32)) {
+    let mut reached = vec![false; mask.len()];
+    let mut open = vec![start];
+    reached[(start.1 * w + start.0) as usize] = true;
+
+    while let Some((x, y)) = open.pop() {
+        for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            if nx < 0 || ny < 0 || nx >= w || ny >= h { continue; }
+            let idx = (ny * w + nx) as usize;
+            if mask[idx] && !reached[idx] {
+                reached[idx] = true;
+                open.push((nx, ny));
+            }
+        }
+    }
+
+    for (tile, reached) in mask.iter_mut().zip(reached) {
+        *tile &= reached;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::map_builders::analysis::Regions;
+
+    #[test]
+    fn shapes_carve_only_inside_their_room() {
+        let mut rng = StdRng::seed_from_u64(6);
+        for shape in RoomShape::ALL {
+            for (w, h) in [(3, 3), (6, 4), (9, 9), (12, 7)] {
+                let room = Rect::new(5, 4, w, h);
+                let mut map = Map::with_size(30, 20);
+                carve_room(&mut map, &room, shape, &mut rng);
+
+                let floors: Vec<(i32, i32)> = (0..map.tiles.len())
+                    .filter(|&i| map.tiles[i] == TileType::Floor)
+                    .map(|i| ((i % map.width) as i32, (i / map.width) as i32))
+                    .collect();
+                assert!(!floors.is_empty(), "{} {}x{}", shape.name(), w, h);
+                assert!(
+                    floors.iter().all(|&(x, y)| x > room.x1 && x <= room.x2 && y > room.y1 && y <= room.y2),
+                    "{} {}x{}", shape.name(), w, h
+                );
+                assert_eq!(Regions::new(&map).count(), 1, "{} {}x{}", shape.name(), w, h);
+            }
+        }
+    }
+
+    #[test]
+    fn shapes_with_no_weight_are_never_rolled() {
+        let table = RoomShapeTable::new("Test")
+            .with(RoomShape::Circle, 3)
+            .with(RoomShape::Cross, 0)
+            .with(RoomShape::Ellipse, 1)
+            .with(RoomShape::Circle, 0);
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            assert!(table.roll(&mut rng) == RoomShape::Ellipse);
+        }
+
+        // and an empty table falls back to rectangles
+        assert!(RoomShapeTable::new("Empty").roll(&mut rng) == RoomShape::Rectangle);
+        let organic = RoomShapeTable::organic();
+        assert!((0..1000).all(|_| organic.roll(&mut rng) != RoomShape::Rectangle));
+    }
+}
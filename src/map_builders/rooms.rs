@@ -0,0 +1,148 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                         Rooms & Corridors                             ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use serde::Serialize;
+
+#[derive(Default, Copy, Clone, Serialize)]
+pub struct Rect {
+    pub x1 : i32,
+    pub x2 : i32,
+    pub y1 : i32,
+    pub y2 : i32
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Rect {
+        Rect{x1: x, y1: y, x2: x + w, y2: y + h}
+    }
+
+    // Returns true if this overlaps with other
+    pub fn intersect(&self, other:&Rect) -> bool {
+        self.x1 <= other.x2 && self.x2 >= other.x1 && self.y1 <= other.y2 && self.y2 >= other.y1
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        ((self.x1 + self.x2)/2, (self.y1 + self.y2)/2)
+    }
+}
+
+/// A corridor carved between two rooms. `from` and `to` index into the
+/// rooms of the owning `RoomGraph`, and `points` holds the polyline the
+/// corridor follows, from its start tile to its end tile.
+#[derive(Clone, Serialize)]
+pub struct Corridor {
+    pub from: usize,
+    pub to: usize,
+    pub points: Vec<(i32, i32)>,
+}
+
+impl Corridor {
+    /// Builds a corridor from every tile it walks over, keeping only the
+    /// tiles where the direction changes.
+    pub fn from_path(from: usize, to: usize, path: &[(i32, i32)]) -> Corridor {
+        let mut points: Vec<(i32, i32)> = Vec::new();
+        for &point in path {
+            if points.len() >= 2 {
+                let (ax, ay) = points[points.len() - 2];
+                let (bx, by) = points[points.len() - 1];
+                let collinear = (bx - ax) * (point.1 - by) == (by - ay) * (point.0 - bx);
+                let same_direction = (bx - ax) * (point.0 - bx) + (by - ay) * (point.1 - by) > 0;
+                if collinear && same_direction {
+                    points.pop();
+                }
+            }
+            if points.last() != Some(&point) {
+                points.push(point);
+            }
+        }
+        Corridor { from, to, points }
+    }
+}
+
+/// The rooms a builder placed and the corridors joining them.
+#[derive(Default, Clone, Serialize)]
+pub struct RoomGraph {
+    pub rooms: Vec<Rect>,
+    pub corridors: Vec<Corridor>,
+}
+
+impl RoomGraph {
+    pub fn clear(&mut self) {
+        self.rooms.clear();
+        self.corridors.clear();
+    }
+
+    /// Indices of the rooms that share a corridor with `room`.
+    pub fn neighbours(&self, room: usize) -> Vec<usize> {
+        let mut neighbours: Vec<usize> = self.corridors
+            .iter()
+            .filter_map(|c| {
+                if c.from == room { Some(c.to) }
+                else if c.to == room { Some(c.from) }
+                else { None }
+            })
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    /// Indices of the rooms reachable through exactly one corridor.
+    pub fn dead_ends(&self) -> Vec<usize> {
+        (0..self.rooms.len())
+            .filter(|&room| self.neighbours(room).len() == 1)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corridor(from: usize, to: usize) -> Corridor {
+        Corridor { from, to, points: Vec::new() }
+    }
+
+    fn graph(rooms: usize, corridors: &[(usize, usize)]) -> RoomGraph {
+        RoomGraph {
+            rooms: (0..rooms as i32).map(|i| Rect::new(i * 10, 0, 4, 4)).collect(),
+            corridors: corridors.iter().map(|&(a, b)| corridor(a, b)).collect(),
+        }
+    }
+
+    #[test]
+    fn neighbours_are_found_from_either_end_once_each() {
+        let graph = graph(4, &[(0, 1), (2, 0), (1, 0), (1, 2)]);
+        assert_eq!(graph.neighbours(0), vec![1, 2]);
+        assert_eq!(graph.neighbours(1), vec![0, 2]);
+        assert_eq!(graph.neighbours(2), vec![0, 1]);
+        assert!(graph.neighbours(3).is_empty());
+    }
+
+    #[test]
+    fn dead_ends_have_one_neighbour() {
+        // a doubled corridor still leads to only one room, and a room with
+        // none is not a dead end
+        let graph = graph(5, &[(0, 1), (1, 2), (2, 3), (1, 0)]);
+        assert_eq!(graph.dead_ends(), vec![0, 3]);
+        assert!(RoomGraph::default().dead_ends().is_empty());
+    }
+
+    #[test]
+    fn paths_keep_only_their_corners() {
+        let path = [(0, 0), (1, 0), (2, 0), (2, 1), (2, 2), (3, 2)];
+        let corridor = Corridor::from_path(0, 1, &path);
+        assert_eq!((corridor.from, corridor.to), (0, 1));
+        assert_eq!(corridor.points, vec![(0, 0), (2, 0), (2, 2), (3, 2)]);
+
+        // diagonal runs collapse too, repeated tiles are dropped and a path
+        // that doubles back keeps the tile it turned at
+        assert_eq!(Corridor::from_path(0, 1, &[(0, 0), (1, 1), (2, 2)]).points, vec![(0, 0), (2, 2)]);
+        assert_eq!(Corridor::from_path(0, 1, &[(0, 0), (0, 0), (0, 1)]).points, vec![(0, 0), (0, 1)]);
+        assert_eq!(Corridor::from_path(0, 1, &[(0, 0), (2, 0), (1, 0)]).points, vec![(0, 0), (2, 0), (1, 0)]);
+        assert!(Corridor::from_path(0, 1, &[]).points.is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                              Settings                                 ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+
+/// Reads a number for the setting `name`, which has to fall in `range`.
+pub fn number<T: FromStr + PartialOrd + Display>(name: &str, value: &str, range: RangeInclusive<T>) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|number| range.contains(number))
+        .ok_or_else(|| format!("'{}' must be a number from {} to {}, not '{}'", name, range.start(), range.end(), value))
+}
+
+/// Reads a whole number for the setting `name` of at least `min`.
+pub fn at_least(name: &str, value: &str, min: usize) -> Result<usize, String> {
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|number| *number >= min)
+        .ok_or_else(|| format!("'{}' must be a whole number of at least {}, not '{}'", name, min, value))
+}
+
+/// Reads a percentage for the setting `name`, with or without the `%`.
+pub fn percent(name: &str, value: &str) -> Result<u32, String> {
+    number(name, value.trim().trim_end_matches('%'), 0..=100)
+        .map_err(|_| format!("'{}' must be a percentage from 0 to 100, not '{}'", name, value))
+}
+
+/// Picks the one of `values` that `label` names as `value`, ignoring case.
+pub fn choice<T: Clone, S: AsRef<str>>(name: &str, value: &str, values: &[T], label: impl Fn(&T) -> S) -> Result<T, String> {
+    let value = value.trim();
+    values.iter().find(|v| label(v).as_ref().eq_ignore_ascii_case(value)).cloned().ok_or_else(|| {
+        let labels: Vec<S> = values.iter().map(label).collect();
+        let labels: Vec<&str> = labels.iter().map(AsRef::as_ref).collect();
+        format!("'{}' must be one of {}, not '{}'", name, labels.join(", "), value)
+    })
+}
+
+/// The error for a setting index past the end of a builder's settings.
+pub fn no_setting(index: usize) -> String {
+    format!("there's no setting {}", index)
+}
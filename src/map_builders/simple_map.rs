@@ -0,0 +1,221 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                       Rooms and Corridors                             ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use rand::Rng;
+
+use super::MapBuilder;
+use super::metrics::Metric;
+use super::random;
+use super::Map;
+use super::corridors::{self, ConnectionStrategy, CorridorConfig};
+use super::room_shapes::{self, RoomShapeTable};
+use super::rooms::{Corridor, Rect, RoomGraph};
+use super::settings;
+
+const MAX_ROOMS: usize = 12;
+const MIN_SIZE: i32 = 4;
+const MAX_SIZE: i32 = 9;
+const PLACEMENT_ATTEMPTS: i32 = 100;
+
+
+pub struct SimpleMapBuilder {
+    map : Map,
+    graph: RoomGraph,
+    corridor_config: CorridorConfig,
+    room_shapes: RoomShapeTable,
+    // set once no more rooms fit, or MAX_ROOMS have been placed
+    finished: bool,
+}
+
+impl MapBuilder for SimpleMapBuilder {
+    fn build(&mut self) {
+        self.clear();
+        self.place_room();
+    }
+
+    fn iterate(&mut self) {
+        if !self.finished {
+            self.place_room();
+        }
+    }
+
+    fn get_map(&self) -> &Map {
+        &self.map
+    }
+
+    fn get_map_mut(&mut self) -> &mut Map {
+        &mut self.map
+    }
+
+    fn metrics(&self) -> Vec<Metric> {
+        vec![
+            Metric::text("Name", "Simple Rooms and Corridors"),
+            Metric::integer("Max Rooms", MAX_ROOMS),
+            Metric::integer("Number of Rooms", self.graph.rooms.len()),
+            Metric::integer("Number of Corridors", self.graph.corridors.len()),
+            Metric::flag("Finished", self.finished),
+        ]
+    }
+
+    fn progress(&self) -> Option<f64> {
+        if self.finished {
+            Some(1.0)
+        } else {
+            Some(self.graph.rooms.len() as f64 / MAX_ROOMS as f64)
+        }
+    }
+
+    fn notes(&self) -> &str {
+        "Each iteration places one more room where it doesn't overlap the others. \
+        Sequential connections join each room to the one before it as it is placed; \
+        other strategies join every room once the last one is in."
+    }
+
+    fn room_graph(&self) -> Option<&RoomGraph> {
+        Some(&self.graph)
+    }
+
+    fn settings(&self) -> Vec<(&'static str, String)> {
+        let mut settings = vec![("Room Shapes", self.room_shapes.name.clone())];
+        settings.extend(self.corridor_config.settings());
+        settings
+    }
+
+    fn cycle_setting(&mut self, index: usize) {
+        match index {
+            0 => self.room_shapes = self.room_shapes.next_preset(),
+            _ => self.corridor_config.cycle_setting(index - 1),
+        }
+    }
+
+    fn set_setting(&mut self, index: usize, value: &str) -> Result<(), String> {
+        match index {
+            0 => self.room_shapes = settings::choice("Room Shapes", value, &RoomShapeTable::presets(), |table| table.name.clone())?,
+            _ => self.corridor_config.set_setting(index - 1, value)?,
+        }
+        Ok(())
+    }
+}
+
+impl SimpleMapBuilder {
+    pub fn new() -> SimpleMapBuilder {
+        SimpleMapBuilder {
+            map : Map::new(),
+            graph: RoomGraph::default(),
+            corridor_config: CorridorConfig::default(),
+            room_shapes: RoomShapeTable::default(),
+            finished: false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.map = Map::with_size(self.map.width, self.map.height);
+        self.graph.clear();
+        self.finished = false;
+    }
+
+    /// Sets how rooms are joined on the next build.
+    pub fn set_corridor_config(&mut self, config: CorridorConfig) {
+        self.corridor_config = config;
+    }
+
+    /// Sets the shapes rooms are rolled from on the next build.
+    pub fn set_room_shapes(&mut self, shapes: RoomShapeTable) {
+        self.room_shapes = shapes;
+    }
+
+    /// Tries random spots until a room fits without touching any other room,
+    /// then carves it. Once no room fits, or the last room is placed, the
+    /// build is finished.
+    pub fn place_room(&mut self) {
+        let mut rng = random::rng();
+
+        let mut placed = false;
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            let w = rng.gen_range(MIN_SIZE..=MAX_SIZE);
+            let h = rng.gen_range(MIN_SIZE..=MAX_SIZE);
+            let x = rng.gen_range(1..self.map.width as i32 - w - 1);
+            let y = rng.gen_range(1..self.map.height as i32 - h - 1);
+            let candidate = Rect::new(x, y, w, h);
+
+            if self.graph.rooms.iter().all(|room| !room.intersect(&candidate)) {
+                let shape = self.room_shapes.roll(&mut rng);
+                room_shapes::carve_room(&mut self.map, &candidate, shape, &mut rng);
+                self.graph.rooms.push(candidate);
+                placed = true;
+                break;
+            }
+        }
+
+        let sequential = self.corridor_config.strategy == ConnectionStrategy::Sequential;
+        if placed && sequential && self.graph.rooms.len() > 1 {
+            let to = self.graph.rooms.len() - 1;
+            let from = to - 1;
+            let start = corridors::random_floor_in(&self.map, &self.graph.rooms[from], &mut rng);
+            let end = corridors::random_floor_in(&self.map, &self.graph.rooms[to], &mut rng);
+            let config = self.corridor_config;
+            let path = corridors::carve_corridor(&mut self.map, start, end, config.style, config.width, &mut rng);
+            self.graph.corridors.push(Corridor::from_path(from, to, &path));
+        }
+
+        if !placed || self.graph.rooms.len() == MAX_ROOMS {
+            self.finished = true;
+            if !sequential {
+                corridors::connect_rooms(&mut self.map, &mut self.graph, &self.corridor_config, &mut rng);
+            }
+        }
+    }
+}
+
+impl Default for SimpleMapBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_builders::analysis::Regions;
+    use crate::map_builders::corridors::CorridorStyle;
+
+    fn finished(config: CorridorConfig, shapes: RoomShapeTable, seed: u64) -> SimpleMapBuilder {
+        random::seed(seed);
+        let mut builder = SimpleMapBuilder::new();
+        builder.set_corridor_config(config);
+        builder.set_room_shapes(shapes);
+        builder.build();
+        while !builder.finished {
+            builder.iterate();
+        }
+        builder
+    }
+
+    #[test]
+    fn rooms_never_overlap() {
+        for seed in 0..10 {
+            let builder = finished(CorridorConfig::default(), RoomShapeTable::mixed(), seed);
+            let rooms = &builder.graph.rooms;
+            assert!(rooms.len() > 1);
+            for (i, a) in rooms.iter().enumerate() {
+                assert!(rooms[i + 1..].iter().all(|b| !a.intersect(b)), "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn every_room_is_connected() {
+        for strategy in ConnectionStrategy::ALL {
+            for style in CorridorStyle::ALL {
+                for shapes in [RoomShapeTable::rectangles(), RoomShapeTable::mixed()] {
+                    let config = CorridorConfig { style, strategy, ..CorridorConfig::default() };
+                    let builder = finished(config, shapes, 3);
+                    assert_eq!(Regions::new(builder.get_map()).count(), 1, "{} {}", strategy.name(), style.name());
+                }
+            }
+        }
+    }
+}
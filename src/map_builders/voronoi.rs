@@ -0,0 +1,497 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                         Voronoi Regions                               ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::Serialize;
+
+use super::MapBuilder;
+use super::analysis::Regions;
+use super::metrics::Metric;
+use super::random;
+use super::settings;
+use super::Map;
+use super::map::TileType;
+
+const SEED_COUNTS: [usize; 4] = [8, 16, 24, 32];
+const MERGE_CHANCES: [u32; 4] = [0, 25, 50, 75];
+
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum DistanceMetric {
+    Euclidean,
+    Manhattan,
+    Chebyshev,
+}
+
+impl DistanceMetric {
+    pub const ALL: [DistanceMetric; 3] = [DistanceMetric::Euclidean, DistanceMetric::Manhattan, DistanceMetric::Chebyshev];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DistanceMetric::Euclidean => "Euclidean",
+            DistanceMetric::Manhattan => "Manhattan",
+            DistanceMetric::Chebyshev => "Chebyshev",
+        }
+    }
+
+    // squared for euclidean, which orders the same and stays an integer
+    fn distance(&self, a: (usize, usize), b: (usize, usize)) -> usize {
+        let dx = a.0.abs_diff(b.0);
+        let dy = a.1.abs_diff(b.1);
+        match self {
+            DistanceMetric::Euclidean => dx * dx + dy * dy,
+            DistanceMetric::Manhattan => dx + dy,
+            DistanceMetric::Chebyshev => dx.max(dy),
+        }
+    }
+}
+
+/// One cell of the diagram. Merged regions keep their index but give all
+/// their tiles to the region in `merged_into`.
+#[derive(Clone, Serialize)]
+pub struct VoronoiRegion {
+    pub seed: (usize, usize),
+    /// The floor tiles of the region.
+    pub tiles: Vec<(usize, usize)>,
+    /// Regions that share a wall with this one.
+    pub neighbours: Vec<usize>,
+    pub merged_into: Option<usize>,
+}
+
+/// An opening knocked through the wall between two regions.
+#[derive(Copy, Clone)]
+pub struct Doorway {
+    pub regions: (usize, usize),
+    pub position: (usize, usize),
+}
+
+
+pub struct VoronoiBuilder {
+    map : Map,
+    num_seeds: usize,
+    metric: DistanceMetric,
+    merge_chance: u32,
+    // the region each tile is closest to
+    owners: Vec<usize>,
+    regions: Vec<VoronoiRegion>,
+    doorways: Vec<Doorway>,
+    // separate floor areas once the regions were laid out, before any joins
+    start_areas: usize,
+}
+
+impl MapBuilder for VoronoiBuilder {
+    fn build(&mut self) {
+        self.clear();
+        self.scatter_seeds();
+        self.start_areas = self.separate_areas();
+    }
+
+    fn iterate(&mut self) {
+        self.connect_next();
+    }
+
+    fn get_map(&self) -> &Map {
+        &self.map
+    }
+
+    fn get_map_mut(&mut self) -> &mut Map {
+        &mut self.map
+    }
+
+    fn metrics(&self) -> Vec<Metric> {
+        let live = self.regions.iter().filter(|r| r.merged_into.is_none()).count();
+        let areas = self.separate_areas();
+        vec![
+            Metric::text("Name", "Voronoi Regions"),
+            Metric::integer("Seeds", self.regions.len()),
+            Metric::integer("Regions", live),
+            Metric::integer("Doorways", self.doorways.len()),
+            Metric::integer("Separate Areas", areas),
+        ]
+    }
+
+    fn progress(&self) -> Option<f64> {
+        // every join removes one of the areas there were after the build,
+        // until one is left
+        if self.start_areas <= 1 {
+            return Some(1.0);
+        }
+        let joined = self.start_areas.saturating_sub(self.separate_areas().max(1));
+        Some((joined as f64 / (self.start_areas - 1) as f64).clamp(0.0, 1.0))
+    }
+
+    fn regions(&self) -> Option<&[VoronoiRegion]> {
+        Some(&self.regions)
+    }
+
+    fn notes(&self) -> &str {
+        "Each iteration joins two floor areas that can't reach each other yet, \
+        either with a doorway or, at the merge chance, by removing the whole \
+        wall between their regions."
+    }
+
+    fn settings(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Seeds", format!("{}", self.num_seeds)),
+            ("Distance", self.metric.name().to_string()),
+            ("Merge Chance", format!("{}%", self.merge_chance)),
+        ]
+    }
+
+    fn cycle_setting(&mut self, index: usize) {
+        match index {
+            0 => {
+                let idx = SEED_COUNTS.iter().position(|n| *n == self.num_seeds).map_or(0, |i| i + 1);
+                self.num_seeds = SEED_COUNTS[idx % SEED_COUNTS.len()];
+            },
+            1 => {
+                let idx = DistanceMetric::ALL.iter().position(|m| *m == self.metric).unwrap_or(0);
+                self.metric = DistanceMetric::ALL[(idx + 1) % DistanceMetric::ALL.len()];
+            },
+            2 => {
+                let idx = MERGE_CHANCES.iter().position(|c| *c == self.merge_chance).map_or(0, |i| i + 1);
+                self.merge_chance = MERGE_CHANCES[idx % MERGE_CHANCES.len()];
+            },
+            _ => {}
+        }
+    }
+
+    fn set_setting(&mut self, index: usize, value: &str) -> Result<(), String> {
+        match index {
+            0 => self.num_seeds = settings::at_least("Seeds", value, 1)?,
+            1 => self.metric = settings::choice("Distance", value, &DistanceMetric::ALL, DistanceMetric::name)?,
+            2 => self.merge_chance = settings::percent("Merge Chance", value)?,
+            _ => return Err(settings::no_setting(index)),
+        }
+        Ok(())
+    }
+}
+
+impl VoronoiBuilder {
+    pub fn new() -> VoronoiBuilder {
+        VoronoiBuilder {
+            map : Map::new(),
+            num_seeds: 16,
+            metric: DistanceMetric::Euclidean,
+            merge_chance: 0,
+            owners: Vec::new(),
+            regions: Vec::new(),
+            doorways: Vec::new(),
+            start_areas: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.map = Map::with_size(self.map.width, self.map.height);
+        self.owners.clear();
+        self.regions.clear();
+        self.doorways.clear();
+        self.start_areas = 0;
+    }
+
+    pub fn set_num_seeds(&mut self, seeds: usize) {
+        self.num_seeds = seeds.max(1);
+    }
+
+    pub fn set_metric(&mut self, metric: DistanceMetric) {
+        self.metric = metric;
+    }
+
+    /// Sets the percentage chance, from 0 to 100, that `iterate` merges two
+    /// regions instead of opening a doorway between them.
+    pub fn set_merge_chance(&mut self, percent: u32) {
+        self.merge_chance = percent.min(100);
+    }
+
+    pub fn doorways(&self) -> &[Doorway] {
+        &self.doorways
+    }
+
+    /// The region a tile belongs to, after merges. Walls belong to the
+    /// region they were closest to.
+    pub fn region_at(&self, x: usize, y: usize) -> Option<usize> {
+        let owner = *self.owners.get(y * self.map.width + x)?;
+        Some(self.live_region(owner))
+    }
+
+    /// Scatters the seeds, gives every tile to its nearest seed and walls
+    /// off the borders between regions.
+    pub fn scatter_seeds(&mut self) {
+        let mut rng = random::rng();
+        let (width, height) = (self.map.width, self.map.height);
+
+        let mut seeds: Vec<(usize, usize)> = Vec::new();
+        while seeds.len() < self.num_seeds.min((width - 2) * (height - 2)) {
+            let seed = (rng.gen_range(1..width - 1), rng.gen_range(1..height - 1));
+            if !seeds.contains(&seed) {
+                seeds.push(seed);
+            }
+        }
+
+        self.owners = (0..width * height)
+            .map(|i| {
+                let tile = (i % width, i / width);
+                (0..seeds.len())
+                    .min_by_key(|&s| self.metric.distance(tile, seeds[s]))
+                    .expect("at least one seed")
+            })
+            .collect();
+
+        self.regions = seeds
+            .into_iter()
+            .map(|seed| VoronoiRegion { seed, tiles: Vec::new(), neighbours: Vec::new(), merged_into: None })
+            .collect();
+        self.draw_walls();
+    }
+
+    /// Opens a doorway in the wall between two regions that touch. Returns
+    /// where it was placed, or `None` if they don't share a wall.
+    pub fn add_doorway(&mut self, a: usize, b: usize) -> Option<(usize, usize)> {
+        let (a, b) = (self.live_region(a), self.live_region(b));
+        let mut rng = random::rng();
+        let width = self.map.width;
+
+        // walls with a floor of each region on either side of them
+        let candidates: Vec<(usize, usize)> = (0..self.map.tiles.len())
+            .filter(|&i| self.map.tiles[i] == TileType::Wall)
+            .map(|i| (i % width, i / width))
+            .filter(|&(x, y)| {
+                if x == 0 || y == 0 || x == width - 1 || y == self.map.height - 1 {
+                    return false;
+                }
+                let floor_of = |nx: usize, ny: usize| {
+                    let idx = ny * width + nx;
+                    (self.map.tiles[idx] == TileType::Floor).then(|| self.live_region(self.owners[idx]))
+                };
+                [(floor_of(x - 1, y), floor_of(x + 1, y)), (floor_of(x, y - 1), floor_of(x, y + 1))]
+                    .iter()
+                    .any(|&pair| pair == (Some(a), Some(b)) || pair == (Some(b), Some(a)))
+            })
+            .collect();
+
+        let &position = candidates.choose(&mut rng)?;
+        self.map.set(TileType::Floor, position.0, position.1);
+        self.doorways.push(Doorway { regions: (a, b), position });
+        self.collect_regions();
+        Some(position)
+    }
+
+    /// Gives every tile of region `b` to region `a` and removes the wall
+    /// between them.
+    pub fn merge_regions(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.live_region(a), self.live_region(b));
+        if a == b {
+            return;
+        }
+        self.regions[b].merged_into = Some(a);
+        self.draw_walls();
+    }
+
+    /// True once every floor tile can reach every other.
+    pub fn is_connected(&self) -> bool {
+        self.floor_components().iter().all(|c| *c <= 0)
+    }
+
+    // joins two floor areas that can't reach each other yet. A region's
+    // floor can be split by the walls of its neighbours, so this works on
+    // the floor itself rather than on region labels
+    fn connect_next(&mut self) {
+        let mut rng = random::rng();
+        let components = self.floor_components();
+        let width = self.map.width;
+
+        // walls with floors from two different areas either side of them.
+        // The edge stays closed, and every tile off it has all four
+        // neighbours on the map
+        let mut candidates = Vec::new();
+        for y in 1..self.map.height.saturating_sub(1) {
+            for x in 1..width.saturating_sub(1) {
+                let idx = y * width + x;
+                if self.map.tiles[idx] != TileType::Wall {
+                    continue;
+                }
+                for (before, after) in [(idx - 1, idx + 1), (idx - width, idx + width)] {
+                    let (c1, c2) = (components[before], components[after]);
+                    if c1 >= 0 && c2 >= 0 && c1 != c2 {
+                        candidates.push((x, y, before, after));
+                    }
+                }
+            }
+        }
+
+        if let Some(&(x, y, before, after)) = candidates.choose(&mut rng) {
+            let a = self.live_region(self.owners[before]);
+            let b = self.live_region(self.owners[after]);
+            if a != b && rng.gen_range(0..100) < self.merge_chance {
+                self.merge_regions(a, b);
+            }
+            // a merge leaves the wall standing when it belongs to a third region
+            if self.map.get(x, y) == Some(&TileType::Wall) {
+                self.map.set(TileType::Floor, x, y);
+                self.doorways.push(Doorway { regions: (a, b), position: (x, y) });
+                self.collect_regions();
+            }
+        } else if components.iter().any(|c| *c > 0) {
+            // the areas only touch diagonally, so no single tile joins them;
+            // merge a stray area's region into a neighbour instead
+            let idx = components.iter().position(|c| *c > 0).expect("a stray area");
+            let region = self.live_region(self.owners[idx]);
+            if let Some(&other) = self.regions[region].neighbours.first() {
+                self.merge_regions(other, region);
+            }
+        }
+    }
+
+    // floor areas that can't reach each other
+    fn separate_areas(&self) -> usize {
+        self.floor_components().into_iter().max().map_or(0, |c| c as usize + 1)
+    }
+
+    // labels each floor tile with the area it belongs to, counting from 0
+    // for the largest, and each wall with -1
+    fn floor_components(&self) -> Vec<i32> {
+        let regions = Regions::new(&self.map);
+
+        // relabel so the largest area is 0
+        let mut order: Vec<usize> = (0..regions.count()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(regions.sizes[i]));
+        let mut rank = vec![0; regions.count()];
+        for (r, &i) in order.iter().enumerate() {
+            rank[i] = r as i32;
+        }
+        regions.labels.iter().map(|l| l.map_or(-1, |l| rank[l])).collect()
+    }
+
+    fn live_region(&self, mut region: usize) -> usize {
+        while let Some(into) = self.regions[region].merged_into {
+            region = into;
+        }
+        region
+    }
+
+    // a tile is wall on the map edge, or when the tile to its right or
+    // below belongs to another region, which keeps borders one tile thick
+    fn draw_walls(&mut self) {
+        let (width, height) = (self.map.width, self.map.height);
+        for y in 0..height {
+            for x in 0..width {
+                let idx = y * width + x;
+                let owner = self.live_region(self.owners[idx]);
+                let edge = x == 0 || y == 0 || x + 1 == width || y + 1 == height;
+                let differs = |next: usize| self.live_region(self.owners[next]) != owner;
+                let border = (x + 1 < width && differs(idx + 1)) || (y + 1 < height && differs(idx + width));
+                self.map.tiles[idx] = if edge || border { TileType::Wall } else { TileType::Floor };
+            }
+        }
+
+        for door in &self.doorways {
+            self.map.set(TileType::Floor, door.position.0, door.position.1);
+        }
+        self.collect_regions();
+    }
+
+    fn collect_regions(&mut self) {
+        let width = self.map.width;
+        let live: Vec<usize> = (0..self.regions.len()).map(|r| self.live_region(r)).collect();
+
+        for region in self.regions.iter_mut() {
+            region.tiles.clear();
+            region.neighbours.clear();
+        }
+
+        for (idx, tile) in self.map.tiles.iter().enumerate() {
+            let owner = live[self.owners[idx]];
+            let (x, y) = (idx % width, idx / width);
+            if *tile == TileType::Floor {
+                self.regions[owner].tiles.push((x, y));
+            }
+
+            // regions touching across the right or lower edge of a tile are
+            // neighbours
+            let right = (x + 1 < width).then(|| idx + 1);
+            let below = (y + 1 < self.map.height).then(|| idx + width);
+            for neighbour in [right, below].into_iter().flatten() {
+                let other = live[self.owners[neighbour]];
+                if other != owner {
+                    if !self.regions[owner].neighbours.contains(&other) {
+                        self.regions[owner].neighbours.push(other);
+                    }
+                    if !self.regions[other].neighbours.contains(&owner) {
+                        self.regions[other].neighbours.push(owner);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for VoronoiBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_builders::export;
+
+    fn built(seed: u64) -> VoronoiBuilder {
+        random::seed(seed);
+        let mut builder = VoronoiBuilder::new();
+        builder.build();
+        builder
+    }
+
+    #[test]
+    fn iterating_joins_every_area() {
+        let mut builder = built(7);
+        for _ in 0..500 {
+            if builder.is_connected() {
+                break;
+            }
+            builder.iterate();
+        }
+        assert!(builder.is_connected());
+        assert_eq!(Regions::new(builder.get_map()).count(), 1);
+        assert_eq!(builder.progress(), Some(1.0));
+    }
+
+    #[test]
+    fn the_export_lists_the_regions() {
+        let builder = built(5);
+        let json = export::to_json(builder.get_map(), None, builder.regions(), &[]).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let regions = value["regions"].as_array().unwrap();
+        assert_eq!(regions.len(), builder.num_seeds);
+        assert!(regions.iter().all(|r| r["seed"].is_array() && r["neighbours"].is_array()));
+    }
+
+    #[test]
+    fn floor_painted_on_the_edge_is_handled() {
+        let mut builder = built(3);
+        let (width, height) = (builder.map.width, builder.map.height);
+        for x in 0..width {
+            builder.map.set(TileType::Floor, x, 0);
+            builder.map.set(TileType::Floor, x, height - 1);
+        }
+        for y in 0..height {
+            builder.map.set(TileType::Floor, 0, y);
+            builder.map.set(TileType::Floor, width - 1, y);
+        }
+
+        builder.metrics();
+        builder.progress();
+        for _ in 0..50 {
+            builder.iterate();
+        }
+        // redrawing the walls closes the edge again
+        builder.merge_regions(0, 1);
+        assert!((0..width).all(|x| builder.map.get(x, 0) == Some(&TileType::Wall)));
+        assert!((0..height).all(|y| builder.map.get(0, y) == Some(&TileType::Wall)));
+    }
+}
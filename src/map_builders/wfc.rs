@@ -0,0 +1,561 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                     Wave Function Collapse                            ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::collections::HashMap;
+use std::{fs, io, path::Path};
+
+use rand::Rng;
+
+use super::MapBuilder;
+use super::metrics::Metric;
+use super::random;
+use super::settings;
+use super::Map;
+use super::map::TileType;
+
+const PATTERN_SIZES: [usize; 3] = [2, 3, 4];
+const CELLS_PER_ITERATION: [usize; 4] = [10, 50, 200, 2000];
+const MAX_RESTARTS: usize = 10;
+
+// right, down, left, up
+const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+const SAMPLES: [(&str, &str); 2] = [
+    ("Rooms", include_str!("../../samples/wfc_rooms.txt")),
+    ("Caves", include_str!("../../samples/wfc_caves.txt")),
+];
+
+
+/// Which symmetries of each sample pattern are learned as well.
+#[derive(PartialEq, Copy, Clone)]
+pub enum Augmentation {
+    None,
+    Rotations,
+    RotationsAndReflections,
+}
+
+impl Augmentation {
+    pub const ALL: [Augmentation; 3] = [Augmentation::None, Augmentation::Rotations, Augmentation::RotationsAndReflections];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Augmentation::None => "None",
+            Augmentation::Rotations => "Rotations",
+            Augmentation::RotationsAndReflections => "Rotations + Reflections",
+        }
+    }
+}
+
+/// The overlapping model of wave function collapse. Every N x N window of
+/// the sample map becomes a pattern, and the output is a grid of pattern
+/// slots whose neighbours have to agree wherever their windows overlap.
+pub struct WaveFunctionCollapseBuilder {
+    map : Map,
+    sample: Map,
+    sample_name: String,
+    pattern_size: usize,
+    augmentation: Augmentation,
+    cells_per_iteration: usize,
+    model: Option<Model>,
+    wave: Option<Wave>,
+    restarts: usize,
+    failed: bool,
+}
+
+impl MapBuilder for WaveFunctionCollapseBuilder {
+    fn build(&mut self) {
+        self.map = Map::with_size(self.map.width, self.map.height);
+        self.restarts = 0;
+        self.failed = false;
+
+        if self.sample.width < self.pattern_size || self.sample.height < self.pattern_size {
+            self.model = None;
+            self.wave = None;
+            self.failed = true;
+            return;
+        }
+
+        let model = Model::learn(&self.sample, self.pattern_size, self.augmentation);
+        self.wave = Some(Wave::new(&model, self.map.width, self.map.height));
+        self.model = Some(model);
+    }
+
+    fn iterate(&mut self) {
+        let (Some(model), Some(wave)) = (&self.model, &mut self.wave) else {
+            return;
+        };
+        if self.failed || wave.is_collapsed() {
+            return;
+        }
+
+        let mut rng = random::rng();
+        for _ in 0..self.cells_per_iteration {
+            match wave.collapse_next(model, &mut rng) {
+                Step::Collapsed => {},
+                Step::Finished => break,
+                Step::Contradiction => {
+                    if self.restarts == MAX_RESTARTS {
+                        self.failed = true;
+                        break;
+                    }
+                    self.restarts += 1;
+                    *wave = Wave::new(model, self.map.width, self.map.height);
+                },
+            }
+        }
+
+        wave.draw(model, &mut self.map);
+    }
+
+    fn get_map(&self) -> &Map {
+        &self.map
+    }
+
+    fn get_map_mut(&mut self) -> &mut Map {
+        &mut self.map
+    }
+
+    fn metrics(&self) -> Vec<Metric> {
+        let mut metrics = vec![
+            Metric::text("Name", "Wave Function Collapse"),
+            Metric::text("Sample", format!("{} ({}x{})", self.sample_name, self.sample.width, self.sample.height)),
+            Metric::integer("Patterns", self.model.as_ref().map_or(0, |m| m.patterns.len())),
+        ];
+        if let Some(wave) = &self.wave {
+            metrics.push(Metric::integer("Cells Collapsed", wave.collapsed).with_unit("cells"));
+            metrics.push(Metric::percent("Collapsed", wave.collapsed, wave.cells()));
+        }
+        metrics.push(Metric::integer("Restarts", self.restarts));
+        metrics.push(Metric::integer("Max Restarts", MAX_RESTARTS));
+        metrics.push(Metric::flag("Failed", self.failed));
+        metrics
+    }
+
+    fn progress(&self) -> Option<f64> {
+        if self.is_finished() {
+            return Some(1.0);
+        }
+        self.wave.as_ref().map(|wave| wave.collapsed as f64 / wave.cells() as f64)
+    }
+
+    fn notes(&self) -> &str {
+        "Each iteration collapses a batch of the lowest entropy cells. Cells \
+        that are still undecided are drawn as wall. Press y on another tab \
+        to learn from that tab's map, or give a recipe the path of a sample \
+        file as its Sample."
+    }
+
+    fn settings(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Sample", self.sample_name.clone()),
+            ("Pattern Size", format!("{}", self.pattern_size)),
+            ("Augmentation", self.augmentation.name().to_string()),
+            ("Cells Per Iteration", format!("{}", self.cells_per_iteration)),
+        ]
+    }
+
+    fn cycle_setting(&mut self, index: usize) {
+        match index {
+            0 => {
+                let idx = SAMPLES.iter().position(|(name, _)| *name == self.sample_name).map_or(0, |i| i + 1);
+                let (name, text) = SAMPLES[idx % SAMPLES.len()];
+                self.sample = Map::from_text(text).expect("built-in samples are valid");
+                self.sample_name = name.to_string();
+            },
+            1 => {
+                let idx = PATTERN_SIZES.iter().position(|n| *n == self.pattern_size).map_or(0, |i| i + 1);
+                self.pattern_size = PATTERN_SIZES[idx % PATTERN_SIZES.len()];
+            },
+            2 => {
+                self.augmentation = match self.augmentation {
+                    Augmentation::None => Augmentation::Rotations,
+                    Augmentation::Rotations => Augmentation::RotationsAndReflections,
+                    Augmentation::RotationsAndReflections => Augmentation::None,
+                }
+            },
+            3 => {
+                let idx = CELLS_PER_ITERATION.iter().position(|c| *c == self.cells_per_iteration).map_or(0, |i| i + 1);
+                self.cells_per_iteration = CELLS_PER_ITERATION[idx % CELLS_PER_ITERATION.len()];
+            },
+            _ => {}
+        }
+    }
+
+    fn set_setting(&mut self, index: usize, value: &str) -> Result<(), String> {
+        match index {
+            // a built in sample by name, or a sample file by its path
+            0 => match settings::choice("Sample", value, &SAMPLES, |(name, _)| *name) {
+                Ok((name, text)) => {
+                    self.sample = Map::from_text(text).expect("built-in samples are valid");
+                    self.sample_name = name.to_string();
+                },
+                Err(_) if Path::new(value.trim()).is_file() => {
+                    self.load_sample(value.trim()).map_err(|err| format!("couldn't read the sample {}: {}", value.trim(), err))?;
+                },
+                Err(err) => return Err(format!("{}, or the path of a sample file", err)),
+            },
+            1 => self.pattern_size = settings::number("Pattern Size", value, PATTERN_SIZES[0]..=PATTERN_SIZES[PATTERN_SIZES.len() - 1])?,
+            2 => self.augmentation = settings::choice("Augmentation", value, &Augmentation::ALL, Augmentation::name)?,
+            3 => self.cells_per_iteration = settings::at_least("Cells Per Iteration", value, 1)?,
+            _ => return Err(settings::no_setting(index)),
+        }
+        Ok(())
+    }
+
+    fn learn_from(&mut self, sample: &Map, name: &str) -> bool {
+        self.set_sample(sample.clone(), name);
+        true
+    }
+}
+
+impl WaveFunctionCollapseBuilder {
+    pub fn new() -> WaveFunctionCollapseBuilder {
+        let (name, text) = SAMPLES[0];
+        WaveFunctionCollapseBuilder {
+            map : Map::new(),
+            sample: Map::from_text(text).expect("built-in samples are valid"),
+            sample_name: name.to_string(),
+            pattern_size: 3,
+            augmentation: Augmentation::RotationsAndReflections,
+            cells_per_iteration: 50,
+            model: None,
+            wave: None,
+            restarts: 0,
+            failed: false,
+        }
+    }
+
+    /// Learns from `sample` on the next build.
+    pub fn set_sample(&mut self, sample: Map, name: &str) {
+        self.sample = sample;
+        self.sample_name = name.to_string();
+    }
+
+    /// Reads a sample written as `#` and `.` rows, see `Map::from_text`. It
+    /// is named by its path, so a recipe saved from the builder reads the
+    /// same file again.
+    pub fn load_sample<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let text = fs::read_to_string(&path)?;
+        let sample = Map::from_text(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        self.set_sample(sample, &path.as_ref().to_string_lossy());
+        Ok(())
+    }
+
+    pub fn set_pattern_size(&mut self, n: usize) {
+        self.pattern_size = n.max(1);
+    }
+
+    pub fn set_augmentation(&mut self, augmentation: Augmentation) {
+        self.augmentation = augmentation;
+    }
+
+    pub fn set_cells_per_iteration(&mut self, cells: usize) {
+        self.cells_per_iteration = cells.max(1);
+    }
+
+    /// True once every cell has collapsed, or the restart budget ran out.
+    pub fn is_finished(&self) -> bool {
+        self.failed || self.wave.as_ref().is_none_or(|w| w.is_collapsed())
+    }
+}
+
+impl Default for WaveFunctionCollapseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+// the learned patterns, how often each appeared, and which patterns may sit
+// next to each other in each direction
+struct Model {
+    n: usize,
+    patterns: Vec<Vec<bool>>,
+    weights: Vec<f64>,
+    propagator: [Vec<Vec<usize>>; 4],
+}
+
+impl Model {
+    fn learn(sample: &Map, n: usize, augmentation: Augmentation) -> Model {
+        let mut counts: HashMap<Vec<bool>, usize> = HashMap::new();
+        let mut order: Vec<Vec<bool>> = Vec::new();
+
+        for y in 0..=sample.height - n {
+            for x in 0..=sample.width - n {
+                let base: Vec<bool> = (0..n * n)
+                    .map(|i| sample.tiles[(y + i / n) * sample.width + x + i % n] == TileType::Floor)
+                    .collect();
+
+                for pattern in symmetries(&base, n, augmentation) {
+                    let count = counts.entry(pattern.clone()).or_insert(0);
+                    if *count == 0 {
+                        order.push(pattern);
+                    }
+                    *count += 1;
+                }
+            }
+        }
+
+        let weights = order.iter().map(|p| counts[p] as f64).collect();
+        let propagator = std::array::from_fn(|d| {
+            let (dx, dy) = DIRECTIONS[d];
+            (0..order.len())
+                .map(|a| (0..order.len()).filter(|&b| agrees(&order[a], &order[b], n, dx, dy)).collect())
+                .collect()
+        });
+
+        Model { n, patterns: order, weights, propagator }
+    }
+}
+
+fn symmetries(base: &[bool], n: usize, augmentation: Augmentation) -> Vec<Vec<bool>> {
+    let rotate = |p: &[bool]| -> Vec<bool> { (0..n * n).map(|i| p[(n - 1 - i % n) * n + i / n]).collect() };
+    let reflect = |p: &[bool]| -> Vec<bool> { (0..n * n).map(|i| p[(i / n) * n + n - 1 - i % n]).collect() };
+
+    let mut variants = vec![base.to_vec()];
+    if augmentation != Augmentation::None {
+        for _ in 0..3 {
+            let next = rotate(variants.last().expect("starts with the base"));
+            variants.push(next);
+        }
+    }
+    if augmentation == Augmentation::RotationsAndReflections {
+        let reflected: Vec<Vec<bool>> = variants.iter().map(|p| reflect(p)).collect();
+        variants.extend(reflected);
+    }
+    variants
+}
+
+// whether pattern b, placed at offset (dx, dy) from pattern a, matches a
+// everywhere they overlap
+fn agrees(a: &[bool], b: &[bool], n: usize, dx: i32, dy: i32) -> bool {
+    let n = n as i32;
+    for y in dy.max(0)..(n + dy).min(n) {
+        for x in dx.max(0)..(n + dx).min(n) {
+            if a[(y * n + x) as usize] != b[((y - dy) * n + x - dx) as usize] {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+
+enum Step {
+    Collapsed,
+    Finished,
+    Contradiction,
+}
+
+// One slot per position a pattern can be placed at without running off the
+// map. Propagation keeps, for every slot, pattern and direction, a count of
+// the patterns in the neighbouring slot that still allow it; a pattern is
+// banned as soon as any of its counts reaches zero.
+struct Wave {
+    width: usize,
+    height: usize,
+    possible: Vec<Vec<bool>>,
+    support: Vec<Vec<[usize; 4]>>,
+    remaining: Vec<usize>,
+    sum_weights: Vec<f64>,
+    sum_weight_logs: Vec<f64>,
+    collapsed: usize,
+    contradiction: bool,
+    pending: Vec<(usize, usize)>,
+}
+
+impl Wave {
+    fn new(model: &Model, map_width: usize, map_height: usize) -> Wave {
+        let width = map_width + 1 - model.n;
+        let height = map_height + 1 - model.n;
+        let count = model.patterns.len();
+        let cells = width * height;
+
+        // a pattern's support from direction d is how many patterns it
+        // accepts in the opposite direction
+        let initial_support: Vec<[usize; 4]> = (0..count)
+            .map(|p| std::array::from_fn(|d| model.propagator[(d + 2) % 4][p].len()))
+            .collect();
+        let sum_weights: f64 = model.weights.iter().sum();
+        let sum_weight_logs: f64 = model.weights.iter().map(|w| w * w.ln()).sum();
+
+        Wave {
+            width,
+            height,
+            possible: vec![vec![true; count]; cells],
+            support: vec![initial_support; cells],
+            remaining: vec![count; cells],
+            sum_weights: vec![sum_weights; cells],
+            sum_weight_logs: vec![sum_weight_logs; cells],
+            collapsed: if count == 1 { cells } else { 0 },
+            contradiction: false,
+            pending: Vec::new(),
+        }
+    }
+
+    fn cells(&self) -> usize {
+        self.width * self.height
+    }
+
+    fn is_collapsed(&self) -> bool {
+        self.collapsed == self.cells()
+    }
+
+    fn collapse_next<R: Rng>(&mut self, model: &Model, rng: &mut R) -> Step {
+        // lowest entropy slot, with a little noise to break ties
+        let mut best: Option<(usize, f64)> = None;
+        for cell in 0..self.cells() {
+            if self.remaining[cell] <= 1 {
+                continue;
+            }
+            let entropy = self.sum_weights[cell].ln() - self.sum_weight_logs[cell] / self.sum_weights[cell]
+                + rng.gen::<f64>() * 1e-6;
+            if best.is_none_or(|(_, e)| entropy < e) {
+                best = Some((cell, entropy));
+            }
+        }
+        let Some((cell, _)) = best else {
+            return Step::Finished;
+        };
+
+        let mut roll = rng.gen::<f64>() * self.sum_weights[cell];
+        let mut chosen = 0;
+        for p in 0..model.patterns.len() {
+            if self.possible[cell][p] {
+                chosen = p;
+                if roll < model.weights[p] {
+                    break;
+                }
+                roll -= model.weights[p];
+            }
+        }
+
+        for p in 0..model.patterns.len() {
+            if p != chosen && self.possible[cell][p] {
+                self.ban(model, cell, p);
+            }
+        }
+        self.propagate(model);
+
+        if self.contradiction { Step::Contradiction } else { Step::Collapsed }
+    }
+
+    fn ban(&mut self, model: &Model, cell: usize, pattern: usize) {
+        self.possible[cell][pattern] = false;
+        self.support[cell][pattern] = [0; 4];
+        self.remaining[cell] -= 1;
+        self.sum_weights[cell] -= model.weights[pattern];
+        self.sum_weight_logs[cell] -= model.weights[pattern] * model.weights[pattern].ln();
+
+        match self.remaining[cell] {
+            0 => self.contradiction = true,
+            1 => self.collapsed += 1,
+            _ => {},
+        }
+        self.pending.push((cell, pattern));
+    }
+
+    fn propagate(&mut self, model: &Model) {
+        while let Some((cell, pattern)) = self.pending.pop() {
+            let (x, y) = ((cell % self.width) as i32, (cell / self.width) as i32);
+            for (d, (dx, dy)) in DIRECTIONS.iter().enumerate() {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= self.width as i32 || ny >= self.height as i32 {
+                    continue;
+                }
+                let neighbour = ny as usize * self.width + nx as usize;
+                for &other in &model.propagator[d][pattern] {
+                    let support = &mut self.support[neighbour][other][d];
+                    if *support == 0 {
+                        continue;
+                    }
+                    *support -= 1;
+                    if *support == 0 && self.possible[neighbour][other] {
+                        self.ban(model, neighbour, other);
+                    }
+                }
+            }
+            if self.contradiction {
+                self.pending.clear();
+            }
+        }
+    }
+
+    // decided tiles take their pattern's value, undecided tiles stay walls
+    fn draw(&self, model: &Model, map: &mut Map) {
+        for y in 0..map.height {
+            for x in 0..map.width {
+                let (cx, dx) = if x < self.width { (x, 0) } else { (self.width - 1, x + 1 - self.width) };
+                let (cy, dy) = if y < self.height { (y, 0) } else { (self.height - 1, y + 1 - self.height) };
+                let cell = cy * self.width + cx;
+
+                let floor = self.remaining[cell] == 1 && {
+                    let p = self.possible[cell].iter().position(|p| *p).expect("one pattern remains");
+                    model.patterns[p][dy * model.n + dx]
+                };
+                map.tiles[y * map.width + x] = if floor { TileType::Floor } else { TileType::Wall };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collapsed(augmentation: Augmentation, seed: u64) -> WaveFunctionCollapseBuilder {
+        random::seed(seed);
+        let mut builder = WaveFunctionCollapseBuilder::new();
+        builder.set_augmentation(augmentation);
+        builder.set_cells_per_iteration(2000);
+        builder.build();
+        while !builder.is_finished() {
+            builder.iterate();
+        }
+        builder
+    }
+
+    #[test]
+    fn every_window_of_the_output_is_a_learned_pattern() {
+        // without rotations the built-in samples hit contradictions too
+        // often to collapse a whole map
+        for augmentation in [Augmentation::Rotations, Augmentation::RotationsAndReflections] {
+            let builder = collapsed(augmentation, 2);
+            assert!(!builder.failed, "{}", augmentation.name());
+            let (map, model) = (builder.get_map(), builder.model.as_ref().unwrap());
+            let n = model.n;
+
+            for y in 0..=map.height - n {
+                for x in 0..=map.width - n {
+                    let window: Vec<bool> = (0..n * n)
+                        .map(|i| map.tiles[(y + i / n) * map.width + x + i % n] == TileType::Floor)
+                        .collect();
+                    assert!(model.patterns.contains(&window), "{} at {}, {}", augmentation.name(), x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_sample_file_is_read_and_named_by_its_path() {
+        let path = std::env::temp_dir().join(format!("procgengo-wfc-sample-{}.txt", std::process::id()));
+        fs::write(&path, "#####\n#..##\n#.#.#\n##..#\n#####\n").unwrap();
+
+        let mut builder = WaveFunctionCollapseBuilder::new();
+        builder.load_sample(&path).unwrap();
+        assert_eq!((builder.sample.width, builder.sample.height), (5, 5));
+        assert_eq!(builder.sample_name, path.to_string_lossy());
+        assert!(builder.sample.get(1, 1) == Some(&TileType::Floor) && builder.sample.get(2, 2) == Some(&TileType::Wall));
+
+        // the Sample setting takes the path too, and a bad file is refused
+        let mut from_setting = WaveFunctionCollapseBuilder::new();
+        from_setting.set_setting(0, &path.to_string_lossy()).unwrap();
+        assert_eq!(from_setting.sample.to_text(), builder.sample.to_text());
+        fs::write(&path, "#x#\n").unwrap();
+        assert!(builder.load_sample(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
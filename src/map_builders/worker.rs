@@ -0,0 +1,260 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                              Worker                                   ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::any::Any;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use super::MapBuilder;
+use super::Map;
+use super::random;
+use super::run_limit;
+
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Job {
+    Build,
+    Iterate,
+    /// Iterates until the builder's progress reaches 1, or until the run
+    /// limit of its map for builders that can't tell.
+    Run,
+}
+
+pub enum Progress {
+    /// Sent after every iteration, with a copy of the map as it stands and
+    /// the name of the step the builder runs next, if it has named steps.
+    Step { iteration: usize, percent: Option<f64>, next_step: Option<String>, snapshot: Map },
+    /// Sent once at the end, handing the builder back.
+    Done(Box<Outcome>),
+    /// Reported by `poll` in place of `Done` when the job panicked, with the
+    /// panic's message. The builder is lost with the thread.
+    Failed(String),
+}
+
+pub struct Outcome {
+    pub builder: Box<dyn MapBuilder>,
+    /// The generator the job drew from, ready for the next one.
+    pub rng: StdRng,
+    pub job: Job,
+    /// Time spent in `build` and `iterate`, leaving out the snapshots.
+    pub duration: Duration,
+    pub iterations: usize,
+    pub cancelled: bool,
+}
+
+/// A thread sending back events of type `T`, with a flag asking it to
+/// stop. Dropping it raises the flag, so a thread nobody is listening to
+/// winds down on its own.
+pub struct Background<T> {
+    events: Receiver<T>,
+    cancel: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> Background<T> {
+    /// Runs `work` on a new thread with the cancel flag and the sender.
+    pub fn spawn<F>(work: F) -> Background<T>
+    where
+        F: FnOnce(&AtomicBool, &Sender<T>) + Send + 'static,
+    {
+        let (sender, events) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancel);
+        let handle = thread::spawn(move || work(&flag, &sender));
+
+        Background { events, cancel, handle: Some(handle) }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// Every event sent since the last call, without waiting. If the thread
+    /// panicked, the last event is `failed` made from its message.
+    pub fn poll(&mut self, failed: impl FnOnce(String) -> T) -> Vec<T> {
+        let mut events = Vec::new();
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => events.push(event),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    // a thread that finished joins cleanly, so only a panic
+                    // is reported, and only the first time round
+                    if let Some(Err(payload)) = self.handle.take().map(JoinHandle::join) {
+                        events.push(failed(panic_message(payload)));
+                    }
+                    break;
+                },
+            }
+        }
+        events
+    }
+
+    /// Waits for the first event `find` picks out, then for the thread to
+    /// end.
+    pub fn wait<R>(mut self, find: impl FnMut(T) -> Option<R>) -> Option<R> {
+        let found = self.events.iter().find_map(find);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        found
+    }
+}
+
+impl<T> Drop for Background<T> {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Runs a job on its own thread. The builder and its generator are moved to
+/// the thread and come back in the final `Progress::Done`.
+pub struct Worker {
+    thread: Background<Progress>,
+}
+
+impl Worker {
+    pub fn spawn(builder: Box<dyn MapBuilder>, rng: StdRng, job: Job) -> Worker {
+        Worker { thread: Background::spawn(move |cancel, sender| run(builder, rng, job, cancel, sender)) }
+    }
+
+    /// Asks the job to stop. A build or iteration that has already started
+    /// is finished first, so the builder is never left half way through one.
+    pub fn cancel(&self) {
+        self.thread.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.thread.is_cancelled()
+    }
+
+    /// Every event sent since the last call, without waiting, ending with a
+    /// `Progress::Failed` if the thread died without sending `Done`.
+    pub fn poll(&mut self) -> Vec<Progress> {
+        self.thread.poll(Progress::Failed)
+    }
+
+    /// Waits for the job to end and returns its outcome.
+    pub fn wait(self) -> Option<Outcome> {
+        self.thread.wait(|event| match event {
+            Progress::Done(outcome) => Some(*outcome),
+            Progress::Step { .. } | Progress::Failed(_) => None,
+        })
+    }
+}
+
+fn run(mut builder: Box<dyn MapBuilder>, rng: StdRng, job: Job, cancel: &AtomicBool, sender: &Sender<Progress>) {
+    random::replace(rng);
+    let mut duration = Duration::ZERO;
+    let mut iterations = 0;
+
+    let max_iterations = match job {
+        Job::Build => {
+            let start = Instant::now();
+            builder.build();
+            duration += start.elapsed();
+            0
+        },
+        Job::Iterate => 1,
+        Job::Run => run_limit(builder.get_map()),
+    };
+
+    let mut cancelled = false;
+    while iterations < max_iterations {
+        if cancel.load(Ordering::Relaxed) {
+            cancelled = true;
+            break;
+        }
+        if job != Job::Iterate && builder.progress().is_some_and(|p| p >= 1.0) {
+            break;
+        }
+        let start = Instant::now();
+        builder.iterate();
+        duration += start.elapsed();
+        iterations += 1;
+
+        let step = Progress::Step {
+            iteration: iterations,
+            percent: builder.progress().map(|p| 100.0 * p.min(1.0)),
+            next_step: builder.running_step(false),
+            snapshot: builder.get_map().clone(),
+        };
+        // the receiver going away means nobody wants the result
+        if sender.send(step).is_err() {
+            return;
+        }
+    }
+
+    // the thread ends here, so what it is left with doesn't matter
+    let rng = random::replace(StdRng::seed_from_u64(0));
+    let _ = sender.send(Progress::Done(Box::new(Outcome { builder, rng, job, duration, iterations, cancelled })));
+}
+
+/// The message a thread panicked with, from what `JoinHandle::join` returns.
+pub fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or("the builder panicked".to_string(), |m| m.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_builders::metrics::Metric;
+
+    struct PanickingBuilder {
+        map: Map,
+    }
+
+    impl MapBuilder for PanickingBuilder {
+        fn build(&mut self) {
+            panic!("no room for the map");
+        }
+        fn get_map(&self) -> &Map {
+            &self.map
+        }
+        fn get_map_mut(&mut self) -> &mut Map {
+            &mut self.map
+        }
+        fn metrics(&self) -> Vec<Metric> {
+            Vec::new()
+        }
+        fn iterate(&mut self) {}
+        fn notes(&self) -> &str {
+            ""
+        }
+    }
+
+    #[test]
+    fn a_panicking_job_is_reported_once() {
+        let builder = Box::new(PanickingBuilder { map: Map::new() });
+        let mut worker = Worker::spawn(builder, StdRng::seed_from_u64(0), Job::Build);
+
+        let start = Instant::now();
+        let failure = loop {
+            let events = worker.poll();
+            if let Some(Progress::Failed(message)) = events.into_iter().last() {
+                break message;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "the failure was never reported");
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        assert_eq!(failure, "no room for the map");
+        assert!(worker.poll().is_empty());
+    }
+}
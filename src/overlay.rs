@@ -0,0 +1,113 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                              Overlays                                 ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use tui::style::Color;
+
+use crate::map_builders::analysis::{self, Regions};
+use crate::map_builders::map::Map;
+
+// told apart on dark and light terminals alike
+const REGION_COLOURS: [Color; 6] = [Color::Blue, Color::Green, Color::Magenta, Color::Cyan, Color::Red, Color::Yellow];
+
+
+/// An analysis of the map drawn over its floors.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Overlay {
+    None,
+    /// Each connected region in its own colour.
+    Regions,
+    /// Distance from the start, from blue near it to red far away.
+    Distance,
+    DeadEnds,
+    Chokepoints,
+}
+
+/// How an overlay changes the look of a floor tile.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Tint {
+    Background(Color),
+    Mark(char, Color),
+}
+
+impl Overlay {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Overlay::None => "no overlay",
+            Overlay::Regions => "regions",
+            Overlay::Distance => "distance from the start",
+            Overlay::DeadEnds => "dead ends",
+            Overlay::Chokepoints => "chokepoints",
+        }
+    }
+
+    pub fn next(&self) -> Overlay {
+        match self {
+            Overlay::None => Overlay::Regions,
+            Overlay::Regions => Overlay::Distance,
+            Overlay::Distance => Overlay::DeadEnds,
+            Overlay::DeadEnds => Overlay::Chokepoints,
+            Overlay::Chokepoints => Overlay::None,
+        }
+    }
+
+    /// A line on what the overlay found, for the status bar.
+    pub fn summary(&self, map: &Map) -> String {
+        match self {
+            Overlay::None => "Overlay off".to_string(),
+            Overlay::Regions => format!("Overlay: {} regions", Regions::new(map).count()),
+            Overlay::Distance => match analysis::start_point(map) {
+                Some(start) => {
+                    let furthest = analysis::dijkstra_map(map, start).into_iter().flatten().max().unwrap_or(0);
+                    format!("Overlay: distance from the start at {}, {}, furthest {} steps", start.0, start.1, furthest)
+                },
+                None => "Overlay: distance, but there's no floor to start on".to_string(),
+            },
+            Overlay::DeadEnds => format!("Overlay: {} dead ends", analysis::dead_ends(map).len()),
+            Overlay::Chokepoints => format!("Overlay: {} chokepoints", analysis::chokepoints(map).len()),
+        }
+    }
+
+    /// The tint of every tile by index, or `None` with no overlay.
+    pub fn tints(&self, map: &Map) -> Option<Vec<Option<Tint>>> {
+        let mut tints = vec![None; map.tiles.len()];
+        let mut mark = |tiles: Vec<(usize, usize)>, tint: Tint| {
+            for (x, y) in tiles {
+                tints[y * map.width + x] = Some(tint);
+            }
+        };
+
+        match self {
+            Overlay::None => return None,
+            Overlay::Regions => {
+                let labels = Regions::new(map).labels;
+                return Some(labels.into_iter().map(|label| label.map(|l| Tint::Background(REGION_COLOURS[l % REGION_COLOURS.len()]))).collect());
+            },
+            Overlay::Distance => {
+                let Some(start) = analysis::start_point(map) else {
+                    return Some(tints);
+                };
+                let distances = analysis::dijkstra_map(map, start);
+                let furthest = distances.iter().flatten().max().copied().unwrap_or(0).max(1);
+                let mut tints: Vec<_> = distances.into_iter().map(|d| d.map(|d| Tint::Background(heat(d as f64 / furthest as f64)))).collect();
+                tints[start.1 * map.width + start.0] = Some(Tint::Mark('*', Color::White));
+                return Some(tints);
+            },
+            Overlay::DeadEnds => mark(analysis::dead_ends(map), Tint::Mark('x', Color::LightRed)),
+            Overlay::Chokepoints => mark(analysis::chokepoints(map), Tint::Mark('◆', Color::LightMagenta)),
+        }
+        Some(tints)
+    }
+}
+
+// blue at 0 through green to red at 1
+fn heat(t: f64) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    let (r, g, b) = match t < 0.5 {
+        true => (0.0, t * 2.0, 1.0 - t * 2.0),
+        false => ((t - 0.5) * 2.0, 1.0 - (t - 0.5) * 2.0, 0.0),
+    };
+    Color::Rgb((r * 200.0) as u8, (g * 200.0) as u8, (b * 200.0) as u8)
+}
@@ -0,0 +1,193 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                               Themes                                  ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Deserializer};
+use tui::style::{Color, Style};
+
+
+/// How one kind of tile is drawn.
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+pub struct TileStyle {
+    pub glyph: char,
+    #[serde(default = "reset", deserialize_with = "colour")]
+    pub fg: Color,
+    #[serde(default = "reset", deserialize_with = "colour")]
+    pub bg: Color,
+}
+
+impl TileStyle {
+    pub const fn new(glyph: char, fg: Color, bg: Color) -> TileStyle {
+        TileStyle { glyph, fg, bg }
+    }
+
+    pub fn style(&self) -> Style {
+        Style::default().fg(self.fg).bg(self.bg)
+    }
+}
+
+/// Glyphs and colours for every kind of tile the map panel draws. Themes
+/// read from a file start from the default theme, so they only need the
+/// parts they change.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub name: String,
+    pub wall: TileStyle,
+    pub floor: TileStyle,
+    pub spawn: TileStyle,
+    pub loot: TileStyle,
+    /// The `@` walking the map while exploring.
+    pub player: TileStyle,
+}
+
+impl Theme {
+    pub fn built_in() -> Vec<Theme> {
+        vec![
+            Theme::default(),
+            Theme {
+                name: "Classic Roguelike".to_string(),
+                wall: TileStyle::new('#', Color::Gray, Color::Black),
+                floor: TileStyle::new('.', Color::DarkGray, Color::Black),
+                spawn: TileStyle::new('S', Color::LightRed, Color::Black),
+                loot: TileStyle::new('$', Color::Yellow, Color::Black),
+                player: TileStyle::new('@', Color::White, Color::Black),
+            },
+            Theme {
+                name: "High Contrast".to_string(),
+                wall: TileStyle::new('█', Color::White, Color::White),
+                floor: TileStyle::new(' ', Color::Black, Color::Black),
+                spawn: TileStyle::new('S', Color::Black, Color::LightYellow),
+                loot: TileStyle::new('$', Color::Black, Color::LightCyan),
+                player: TileStyle::new('@', Color::Black, Color::LightGreen),
+            },
+            // the Okabe-Ito palette, with every marker told apart by its
+            // glyph as well as its colour
+            Theme {
+                name: "Colour-Blind Safe".to_string(),
+                wall: TileStyle::new('■', Color::Rgb(0, 114, 178), Color::Reset),
+                floor: TileStyle::new(' ', Color::Reset, Color::Reset),
+                spawn: TileStyle::new('S', Color::Rgb(213, 94, 0), Color::Reset),
+                loot: TileStyle::new('$', Color::Rgb(240, 228, 66), Color::Reset),
+                player: TileStyle::new('@', Color::Rgb(0, 158, 115), Color::Reset),
+            },
+        ]
+    }
+
+    pub fn from_toml(text: &str) -> Result<Theme, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Theme> {
+        let text = fs::read_to_string(path)?;
+        Theme::from_toml(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Reads every `.toml` theme in `dir`, in name order.
+    pub fn load_dir(dir: impl AsRef<Path>) -> io::Result<Vec<Theme>> {
+        let mut paths: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        paths.sort();
+
+        paths.iter().map(Theme::load).collect()
+    }
+}
+
+/// Yellow walls on the terminal's own background, as the map has always
+/// been drawn.
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: "Default".to_string(),
+            wall: TileStyle::new('■', Color::Yellow, Color::Reset),
+            floor: TileStyle::new(' ', Color::Yellow, Color::Reset),
+            spawn: TileStyle::new('S', Color::Yellow, Color::Reset),
+            loot: TileStyle::new('$', Color::Yellow, Color::Reset),
+            player: TileStyle::new('@', Color::LightGreen, Color::Reset),
+        }
+    }
+}
+
+fn reset() -> Color {
+    Color::Reset
+}
+
+// a colour name like `light_blue`, or `#rrggbb`
+fn colour<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    parse_colour(&name).ok_or_else(|| serde::de::Error::custom(format!("unknown colour '{}', use a name like light_blue or #rrggbb", name)))
+}
+
+fn parse_colour(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        // from_str_radix would take a leading sign as well as the digits
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        return Some(Color::Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8));
+    }
+    let colour = match name.to_ascii_lowercase().replace(['-', ' '], "_").as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "dark_gray" | "dark_grey" => Color::DarkGray,
+        "light_red" => Color::LightRed,
+        "light_green" => Color::LightGreen,
+        "light_yellow" => Color::LightYellow,
+        "light_blue" => Color::LightBlue,
+        "light_magenta" => Color::LightMagenta,
+        "light_cyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    };
+    Some(colour)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colours_are_read_by_name() {
+        assert_eq!(parse_colour("light_blue"), Some(Color::LightBlue));
+        assert_eq!(parse_colour("Light Blue"), Some(Color::LightBlue));
+        assert_eq!(parse_colour("dark-grey"), Some(Color::DarkGray));
+        assert_eq!(parse_colour("default"), Some(Color::Reset));
+        assert_eq!(parse_colour("mauve"), None);
+    }
+
+    #[test]
+    fn hex_colours_need_six_digits() {
+        assert_eq!(parse_colour("#0072b2"), Some(Color::Rgb(0, 114, 178)));
+        assert_eq!(parse_colour("#FFaa00"), Some(Color::Rgb(255, 170, 0)));
+        for bad in ["#+12345", "#-12345", "#12345", "#1234567", "#12345g", "#", "0072b2"] {
+            assert_eq!(parse_colour(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn a_partial_theme_keeps_the_default_for_the_rest() {
+        let theme = Theme::from_toml("name = \"Moss\"\n[wall]\nglyph = \"%\"\nfg = \"#00ff00\"\n").unwrap();
+        let default = Theme::default();
+        assert_eq!(theme.name, "Moss");
+        assert_eq!(theme.wall, TileStyle::new('%', Color::Rgb(0, 255, 0), Color::Reset));
+        assert_eq!((theme.floor, theme.spawn, theme.loot, theme.player), (default.floor, default.spawn, default.loot, default.player));
+
+        assert!(Theme::from_toml("[wall]\nglyph = \"%\"\nfg = \"#+12345\"\n").unwrap_err().contains("unknown colour '#+12345'"));
+        assert!(Theme::from_toml("walls = 1").is_err());
+        assert!(Theme::load("themes/swamp.toml").is_ok());
+    }
+}
@@ -0,0 +1,313 @@
+/////////// ------------------------------------------------------///////////
+///                                                                       ///
+///                             Viewport                                  ///
+///                                                                       ///
+/////////// ------------------------------------------------------///////////
+
+use tui::layout::Rect;
+use tui::style::{Color, Style};
+use tui::text::{Span, Spans, Text};
+
+use crate::explore::Exploration;
+use crate::map_builders::autotile;
+use crate::map_builders::map::{Map, TileType};
+use crate::map_builders::prefabs::MarkerKind;
+use crate::overlay::{Overlay, Tint};
+use crate::theme::{Theme, TileStyle};
+
+// the most cells the minimap takes up
+const MINIMAP_WIDTH: usize = 32;
+const MINIMAP_HEIGHT: usize = 16;
+
+// indexed by the floors in a 2x2 block: 1 top left, 2 top right, 4 bottom
+// left and 8 bottom right, so walls are drawn solid like the other zooms
+const QUADRANTS: [char; 16] = ['█', '▟', '▙', '▄', '▜', '▐', '▚', '▗', '▛', '▞', '▌', '▖', '▀', '▝', '▘', ' '];
+
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Zoom {
+    /// Two characters per tile, so tiles come out about square.
+    Double,
+    Single,
+    /// A quarter block character for each 2x2 square of tiles.
+    HalfBlock,
+}
+
+impl Zoom {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Zoom::Double => "2 chars per tile",
+            Zoom::Single => "1 char per tile",
+            Zoom::HalfBlock => "2x2 tiles per char",
+        }
+    }
+
+    pub fn next(&self) -> Zoom {
+        match self {
+            Zoom::Double => Zoom::Single,
+            Zoom::Single => Zoom::HalfBlock,
+            Zoom::HalfBlock => Zoom::Double,
+        }
+    }
+
+    // tiles that fit across and down a block of cells
+    fn tiles(&self, columns: u16, rows: u16) -> (usize, usize) {
+        let (columns, rows) = (columns as usize, rows as usize);
+        match self {
+            Zoom::Double => (columns / 2, rows),
+            Zoom::Single => (columns, rows),
+            Zoom::HalfBlock => (columns * 2, rows * 2),
+        }
+    }
+
+    // cells taken up by a block of tiles
+    fn cells(&self, width: usize, height: usize) -> (u16, u16) {
+        let (columns, rows) = match self {
+            Zoom::Double => (width * 2, height),
+            Zoom::Single => (width, height),
+            Zoom::HalfBlock => (width.div_ceil(2), height.div_ceil(2)),
+        };
+        (columns as u16, rows as u16)
+    }
+}
+
+/// The part of a map shown on the Map panel. `x` and `y` are the tile in
+/// the top left corner.
+pub struct Viewport {
+    pub x: usize,
+    pub y: usize,
+    pub zoom: Zoom,
+    /// Draws walls as box-drawing lines and leaves out the rock behind them.
+    /// Zoomed out to quarter blocks, walls are drawn as usual.
+    pub autotile: bool,
+    /// Drawn over the floors, except zoomed out to quarter blocks.
+    pub overlay: Overlay,
+}
+
+impl Viewport {
+    pub fn new() -> Viewport {
+        Viewport { x: 0, y: 0, zoom: Zoom::Double, autotile: false, overlay: Overlay::None }
+    }
+
+    /// Tiles across and down that can be seen in `area`.
+    pub fn visible(&self, map: &Map, area: Rect) -> (usize, usize) {
+        let (width, height) = self.zoom.tiles(area.width, area.height);
+        (width.min(map.width), height.min(map.height))
+    }
+
+    /// Keeps the corner where the view doesn't run past the map.
+    pub fn clamp(&mut self, map: &Map, area: Rect) {
+        let (width, height) = self.visible(map, area);
+        self.x = self.x.min(map.width - width);
+        self.y = self.y.min(map.height - height);
+    }
+
+    /// Moves the view by an eighth of what can be seen, at least a tile.
+    pub fn pan(&mut self, dx: i32, dy: i32, map: &Map, area: Rect) {
+        let (width, height) = self.visible(map, area);
+        let step_x = (width / 8).max(1) as i32 * dx;
+        let step_y = (height / 8).max(1) as i32 * dy;
+        self.x = (self.x as i32 + step_x).max(0) as usize;
+        self.y = (self.y as i32 + step_y).max(0) as usize;
+        self.clamp(map, area);
+    }
+
+    /// Moves the view so the tile is as near the middle as it can be.
+    pub fn centre_on(&mut self, x: usize, y: usize, map: &Map, area: Rect) {
+        let (width, height) = self.visible(map, area);
+        self.x = x.saturating_sub(width / 2);
+        self.y = y.saturating_sub(height / 2);
+        self.clamp(map, area);
+    }
+
+    pub fn set_zoom(&mut self, zoom: Zoom, map: &Map, area: Rect) {
+        // keeps the middle of the view where it was
+        let (width, height) = self.visible(map, area);
+        let (x, y) = (self.x + width / 2, self.y + height / 2);
+        self.zoom = zoom;
+        self.centre_on(x, y, map, area);
+    }
+
+    /// Whether part of the map is out of view.
+    pub fn is_cropped(&self, map: &Map, area: Rect) -> bool {
+        self.visible(map, area) != (map.width, map.height)
+    }
+
+    // the cell the top left tile is drawn in, centring maps that fit
+    fn origin(&self, map: &Map, area: Rect) -> (u16, u16) {
+        let (width, height) = self.visible(map, area);
+        let (columns, _) = self.zoom.cells(width, height);
+        (area.x + (area.width - columns) / 2, area.y)
+    }
+
+    /// The tile drawn at a cell, if there is one. Zoomed out, it is the top
+    /// left of the tiles the cell holds.
+    pub fn tile_at(&self, map: &Map, area: Rect, column: u16, row: u16) -> Option<(usize, usize)> {
+        let (left, top) = self.origin(map, area);
+        if column < left || row < top || column >= area.right() || row >= area.bottom() {
+            return None;
+        }
+        let (dx, dy) = ((column - left) as usize, (row - top) as usize);
+        let (dx, dy) = match self.zoom {
+            Zoom::Double => (dx / 2, dy),
+            Zoom::Single => (dx, dy),
+            Zoom::HalfBlock => (dx * 2, dy * 2),
+        };
+
+        let (width, height) = self.visible(map, area);
+        (dx < width && dy < height).then_some((self.x + dx, self.y + dy))
+    }
+
+    /// The rows of the part of the map in view, styled by `theme`, with
+    /// `markers` drawn over floors when there is room for them. While
+    /// exploring, only the tiles the player has seen are drawn, and those out
+    /// of view are greyed out.
+    pub fn lines(&self, map: &Map, area: Rect, markers: &[(usize, usize, MarkerKind)], theme: &Theme, exploration: Option<&Exploration>) -> Vec<Spans<'static>> {
+        let (width, height) = self.visible(map, area);
+        let (left, _) = self.origin(map, area);
+        let indent = " ".repeat((left - area.x) as usize);
+        let floor = |x: usize, y: usize| map.get(x, y) == Some(&TileType::Floor);
+        let explored = |x: usize, y: usize| exploration.is_none_or(|e| e.explored[y * map.width + x]);
+        let marker = |x: usize, y: usize| markers.iter().find(|m| (m.0, m.1) == (x, y) && floor(x, y)).map(|m| m.2);
+
+        let mut lines = Vec::new();
+        match self.zoom {
+            Zoom::Double | Zoom::Single => {
+                let tints = self.overlay.tints(map);
+                let tint = |x: usize, y: usize| tints.as_ref().and_then(|tints| tints[y * map.width + x]);
+                for y in self.y..self.y + height {
+                    let mut row = Row::new(&indent);
+                    for x in self.x..self.x + width {
+                        let tile = match marker(x, y) {
+                            Some(MarkerKind::Spawn) => theme.spawn,
+                            Some(MarkerKind::Loot) => theme.loot,
+                            None if floor(x, y) => match tint(x, y) {
+                                Some(Tint::Background(colour)) => TileStyle { bg: colour, ..theme.floor },
+                                Some(Tint::Mark(glyph, colour)) => TileStyle { glyph, fg: colour, ..theme.floor },
+                                None => theme.floor,
+                            },
+                            // lines are drawn over the floor, so they stand
+                            // out in themes with solid walls
+                            None if self.autotile => {
+                                let glyph = autotile::glyph(map, x, y);
+                                let fg = if glyph == ' ' { theme.floor.fg } else { theme.wall.fg };
+                                TileStyle::new(glyph, fg, theme.floor.bg)
+                            },
+                            None => theme.wall,
+                        };
+                        let tile = match exploration {
+                            Some(e) if e.player == (x, y) => theme.player,
+                            Some(_) if !explored(x, y) => TileStyle::new(' ', Color::Reset, Color::Reset),
+                            Some(e) if !e.visible[y * map.width + x] => TileStyle { fg: Color::DarkGray, bg: Color::Reset, ..tile },
+                            _ => tile,
+                        };
+                        row.push(tile.glyph, tile.style());
+                        if self.zoom == Zoom::Double {
+                            // the second cell carries the background, and any
+                            // line running on to the wall to the east
+                            let joined = self.autotile && explored(x, y) && autotile::mask4(map, x, y) & autotile::EAST != 0;
+                            row.push(if joined { '─' } else { ' ' }, tile.style());
+                        }
+                    }
+                    lines.push(row.finish());
+                }
+            },
+            Zoom::HalfBlock => {
+                // quarter blocks are drawn in the wall colour over the floor's
+                let style = Style::default().fg(theme.wall.fg).bg(theme.floor.bg);
+                for y in (self.y..self.y + height).step_by(2) {
+                    let mut row = Row::new(&indent);
+                    for x in (self.x..self.x + width).step_by(2) {
+                        // tiles past the edge of the view, or not yet explored,
+                        // count as floor so they're left blank
+                        let open = |dx: usize, dy: usize| {
+                            x + dx >= self.x + width || y + dy >= self.y + height || floor(x + dx, y + dy) || !explored(x + dx, y + dy)
+                        };
+                        let index = open(0, 0) as usize | (open(1, 0) as usize) << 1 | (open(0, 1) as usize) << 2 | (open(1, 1) as usize) << 3;
+                        row.push(QUADRANTS[index], style);
+                    }
+                    lines.push(row.finish());
+                }
+            },
+        }
+        lines
+    }
+
+    /// A shrunk copy of the whole map with the view marked on it, or `None`
+    /// if the whole map is in view anyway. Also returns its width and height
+    /// in cells.
+    pub fn minimap(&self, map: &Map, area: Rect) -> Option<(Text<'static>, u16, u16)> {
+        if !self.is_cropped(map, area) {
+            return None;
+        }
+        let (view_width, view_height) = self.visible(map, area);
+        let scale = map.width.div_ceil(MINIMAP_WIDTH).max(map.height.div_ceil(MINIMAP_HEIGHT)).max(1);
+        let (width, height) = (map.width.div_ceil(scale), map.height.div_ceil(scale));
+
+        let mut text = Text::default();
+        for row in 0..height {
+            let spans: Vec<Span> = (0..width)
+                .map(|column| {
+                    let (x, y) = (column * scale, row * scale);
+                    let tiles: Vec<bool> = (y..(y + scale).min(map.height))
+                        .flat_map(|ty| (x..(x + scale).min(map.width)).map(move |tx| (tx, ty)))
+                        .map(|(tx, ty)| map.get(tx, ty) == Some(&TileType::Floor))
+                        .collect();
+                    let floors = tiles.iter().filter(|f| **f).count();
+                    let symbol = if floors * 2 >= tiles.len() { " " } else { "█" };
+
+                    let in_view = x + scale > self.x && x < self.x + view_width && y + scale > self.y && y < self.y + view_height;
+                    let style = match in_view {
+                        true => Style::default().fg(Color::Yellow).bg(Color::DarkGray),
+                        false => Style::default().fg(Color::Gray),
+                    };
+                    Span::styled(symbol, style)
+                })
+                .collect();
+            text.lines.push(Spans::from(spans));
+        }
+        Some((text, width as u16, height as u16))
+    }
+
+    /// The tile shown by a cell of the minimap drawn in `minimap`.
+    pub fn minimap_tile(&self, map: &Map, minimap: Rect, column: u16, row: u16) -> Option<(usize, usize)> {
+        let scale = map.width.div_ceil(MINIMAP_WIDTH).max(map.height.div_ceil(MINIMAP_HEIGHT)).max(1);
+        if column < minimap.x || row < minimap.y || column >= minimap.right() || row >= minimap.bottom() {
+            return None;
+        }
+        let (x, y) = ((column - minimap.x) as usize * scale, (row - minimap.y) as usize * scale);
+        (x < map.width && y < map.height).then_some((x, y))
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// a row of text, run together into one span for each stretch of a style
+struct Row {
+    spans: Vec<Span<'static>>,
+    text: String,
+    style: Style,
+}
+
+impl Row {
+    fn new(indent: &str) -> Row {
+        Row { spans: vec![Span::raw(indent.to_string())], text: String::new(), style: Style::default() }
+    }
+
+    fn push(&mut self, glyph: char, style: Style) {
+        if style != self.style && !self.text.is_empty() {
+            self.spans.push(Span::styled(std::mem::take(&mut self.text), self.style));
+        }
+        self.style = style;
+        self.text.push(glyph);
+    }
+
+    fn finish(mut self) -> Spans<'static> {
+        self.spans.push(Span::styled(self.text, self.style));
+        Spans::from(self.spans)
+    }
+}